@@ -1,15 +1,15 @@
 use std::collections::HashMap;
-use std::fs;
 use std::path::Path;
 use std::process::Command;
 
 use anyhow::Error;
 use libcnb::{BuildContext, GenericPlatform};
 use libcnb::data::layer_content_metadata::LayerContentMetadata;
-use libcnb::layer_lifecycle::{LayerLifecycle, ValidateResult};
-use serde::Deserialize;
-use serde::Serialize;
-use sha2::Digest;
+use libcnb::layer_name;
+use libcnb::layer_lifecycle::{
+    validate_cache_key, CacheKey, CacheKeyBuilder, CacheKeyError, CacheKeyMetadata,
+    LayerLifecycle, ValidateResult,
+};
 
 use crate::RubyBuildpackMetadata;
 
@@ -17,26 +17,54 @@ pub struct BundlerLayerLifecycle {
     pub ruby_env: HashMap<String, String>,
 }
 
-#[derive(Deserialize, Serialize, Debug)]
-pub struct BundlerLayerMetadata {
-    checksum: String,
-}
+impl LayerLifecycle<GenericPlatform, RubyBuildpackMetadata, CacheKeyMetadata, Option<()>, anyhow::Error> for BundlerLayerLifecycle {
+    fn validate(&self, layer_path: &Path, layer_content_metadata: &LayerContentMetadata<CacheKeyMetadata>, build_context: &BuildContext<GenericPlatform, RubyBuildpackMetadata>) -> ValidateResult {
+        match gemfile_lock_cache_key(build_context) {
+            Ok(cache_key) => validate_cache_key(&cache_key, layer_content_metadata, ValidateResult::UpdateLayer),
+            Err(_) => ValidateResult::RecreateLayer,
+        }
+    }
 
-impl LayerLifecycle<GenericPlatform, RubyBuildpackMetadata, BundlerLayerMetadata, Option<()>, anyhow::Error> for BundlerLayerLifecycle {
-    fn validate(&self, layer_path: &Path, layer_content_metadata: &LayerContentMetadata<BundlerLayerMetadata>, build_context: &BuildContext<GenericPlatform, RubyBuildpackMetadata>) -> ValidateResult {
-        let checksum_matches = sha256_checksum(layer_path.join("Gemfile.lock"))
-            .map(|local_checksum| local_checksum == layer_content_metadata.metadata.checksum)
-            .unwrap_or(false);
+    fn update(&self, layer_path: &Path, _layer_content_metadata: LayerContentMetadata<CacheKeyMetadata>, build_context: &BuildContext<GenericPlatform, RubyBuildpackMetadata>) -> Result<LayerContentMetadata<CacheKeyMetadata>, Error> {
+        println!("---> Reusing gems, installing changes from Gemfile.lock");
+        self.bundle_install(layer_path, build_context)
+    }
 
-        if checksum_matches {
-            ValidateResult::KeepLayer
-        } else {
-            ValidateResult::UpdateLayer
+    fn create(&self, layer_path: &Path, build_context: &BuildContext<GenericPlatform, RubyBuildpackMetadata>) -> Result<LayerContentMetadata<CacheKeyMetadata>, Error> {
+        println!("---> Installing gems");
+        self.bundle_install(layer_path, build_context)
+    }
+}
+
+impl BundlerLayerLifecycle {
+    fn bundle_install(&self, layer_path: &Path, build_context: &BuildContext<GenericPlatform, RubyBuildpackMetadata>) -> Result<LayerContentMetadata<CacheKeyMetadata>, Error> {
+        let cmd = Command::new("bundle")
+            .args(&[
+                "install",
+                "--path",
+                layer_path.to_str().unwrap(),
+                "--binstubs",
+                layer_path.join("bin").to_str().unwrap(),
+            ])
+            .envs(&self.ruby_env)
+            .spawn()?
+            .wait()?;
+        if !cmd.success() {
+            return Err(anyhow::anyhow!("Could not bundle install"));
         }
+
+        self.configure_bundler(build_context)?;
+
+        Ok(LayerContentMetadata::new(CacheKeyMetadata {
+            cache_key: gemfile_lock_cache_key(build_context)?
+        }).launch(true).cache(true))
     }
 
-    fn update(&self, layer_path: &Path, layer_content_metadata: LayerContentMetadata<BundlerLayerMetadata>, build_context: &BuildContext<GenericPlatform, RubyBuildpackMetadata>) -> Result<LayerContentMetadata<BundlerLayerMetadata>, Error> {
-        println!("---> Reusing gems");
+    // `create` and `update` work in a staging directory, bundler has to be pointed at the path
+    // the layer ends up at.
+    fn configure_bundler(&self, build_context: &BuildContext<GenericPlatform, RubyBuildpackMetadata>) -> Result<(), Error> {
+        let layer_path = build_context.layer_path(&layer_name!("bundler"));
+
         Command::new("bundle")
             .args(&[
                 "config",
@@ -59,35 +87,12 @@ impl LayerLifecycle<GenericPlatform, RubyBuildpackMetadata, BundlerLayerMetadata
             .spawn()?
             .wait()?;
 
-        Ok(layer_content_metadata)
-    }
-
-    fn create(&self, layer_path: &Path, build_context: &BuildContext<GenericPlatform, RubyBuildpackMetadata>) -> Result<LayerContentMetadata<BundlerLayerMetadata>, Error> {
-        println!("---> Installing gems");
-
-        let cmd = Command::new("bundle")
-            .args(&[
-                "install",
-                "--path",
-                layer_path.to_str().unwrap(),
-                "--binstubs",
-                layer_path.join("bin").to_str().unwrap(),
-            ])
-            .envs(&self.ruby_env)
-            .spawn()?
-            .wait()?;
-        if !cmd.success() {
-            anyhow::anyhow!("Could not bundle install");
-        }
-
-        Ok(LayerContentMetadata::default().launch(true).cache(true).metadata(BundlerLayerMetadata {
-            checksum: sha256_checksum(layer_path.join("Gemfile.lock"))?
-        }))
+        Ok(())
     }
 }
 
-fn sha256_checksum(path: impl AsRef<Path>) -> anyhow::Result<String> {
-    Ok(fs::read(path)
-        .map(|bytes| sha2::Sha256::digest(bytes.as_ref()))
-        .map(|bytes| format!("{:x}", bytes))?)
+fn gemfile_lock_cache_key(build_context: &BuildContext<GenericPlatform, RubyBuildpackMetadata>) -> Result<CacheKey, CacheKeyError> {
+    CacheKeyBuilder::from_context(build_context)
+        .file("Gemfile.lock")
+        .build()
 }
@@ -2,10 +2,11 @@ use std::collections::HashMap;
 use std::process::{Command, Stdio};
 
 use anyhow::Error;
-use libcnb::{BuildContext, cnb_runtime, DetectContext, DetectOutcome, GenericErrorHandler, GenericPlatform};
+use libcnb::{BuildContext, cnb_runtime, DetectContext, DetectOutcome, DetectRule, GenericErrorHandler, GenericPlatform};
 use libcnb::data::build_plan::BuildPlan;
-use libcnb::data;
+use libcnb::data::launch::{LaunchBuilder, ProcessBuilder};
 use libcnb::layer_lifecycle::execute_layer_lifecycle;
+use libcnb::{layer_name, process_type};
 use serde::Deserialize;
 
 use crate::layers::bundler::BundlerLayerLifecycle;
@@ -19,24 +20,19 @@ fn main() {
 }
 
 fn detect(context: DetectContext<GenericPlatform, RubyBuildpackMetadata>) -> libcnb::Result<DetectOutcome, anyhow::Error> {
-    let outcome = if context.app_dir.join("Gemfile.lock").exists() {
-        DetectOutcome::Pass(BuildPlan::new())
-    } else {
-        DetectOutcome::Fail
-    };
-
-    Ok(outcome)
+    let rule = DetectRule::file_exists("Gemfile.lock");
+    Ok(context.detect(&rule).into_detect_outcome(BuildPlan::new()))
 }
 
 fn build(context: BuildContext<GenericPlatform, RubyBuildpackMetadata>) -> libcnb::Result<(), anyhow::Error> {
     println!("---> Ruby Buildpack");
     println!("---> Download and extracting Ruby");
 
-    let ruby_env = execute_layer_lifecycle("ruby", RubyLayerLifecycle, &context)?;
+    let ruby_env = execute_layer_lifecycle(&layer_name!("ruby"), RubyLayerLifecycle, &context)?;
 
     println!("---> Installing bundler");
     install_bundler(&ruby_env)?;
-    execute_layer_lifecycle("bundler", BundlerLayerLifecycle { ruby_env }, &context)?;
+    execute_layer_lifecycle(&layer_name!("bundler"), BundlerLayerLifecycle { ruby_env }, &context)?;
 
     write_launch(&context);
     Ok(())
@@ -64,12 +60,10 @@ fn install_bundler(ruby_env: &HashMap<String, String>) -> anyhow::Result<()> {
 }
 
 fn write_launch(context: &BuildContext<GenericPlatform, RubyBuildpackMetadata>) -> anyhow::Result<()> {
-    let mut launch_toml = data::launch::Launch::new();
-    let web = data::launch::Process::new("web", "bundle", vec!["exec", "ruby", "app.rb"], false)?;
-    let worker =
-        data::launch::Process::new("worker", "bundle", vec!["exec", "ruby", "worker.rb"], false)?;
-    launch_toml.processes.push(web);
-    launch_toml.processes.push(worker);
+    let launch_toml = LaunchBuilder::new()
+        .process(ProcessBuilder::new(process_type!("web"), "bundle").args(vec!["exec", "ruby", "app.rb"]))
+        .process(ProcessBuilder::new(process_type!("worker"), "bundle").args(vec!["exec", "ruby", "worker.rb"]))
+        .build()?;
 
     context.write_launch(launch_toml)?;
     Ok(())
@@ -0,0 +1,233 @@
+//! Readers for version pin files commonly found in app directories.
+//!
+//! Each reader returns `Ok(None)` if the file does not exist or does not pin a version for the
+//! requested runtime. Plain versions such as `3.0.1` or `3.0` are pinned exactly, i.e. `3.0`
+//! matches any `3.0.x` release. Anything else is parsed as a [`VersionReq`].
+//!
+//! # Examples
+//! ```no_run
+//! use libcnb::app;
+//!
+//! if let Some(pin) = app::ruby_version("/workspace").unwrap() {
+//!     // Prints i.e. "Using Ruby =3.0.1 from .ruby-version:1"
+//!     println!("Using Ruby {}", pin);
+//! }
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use semver::{ReqParseError, VersionReq};
+
+/// A version requirement read from a version pin file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VersionPin {
+    pub requirement: VersionReq,
+    /// The path of the file, relative to the app directory.
+    pub file: PathBuf,
+    /// The 1-based line number the version was read from.
+    pub line: usize,
+}
+
+impl fmt::Display for VersionPin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} from {}:{}",
+            self.requirement,
+            self.file.display(),
+            self.line
+        )
+    }
+}
+
+/// Reads `.ruby-version`. Versions may be prefixed with `ruby-`.
+pub fn ruby_version(app_dir: impl AsRef<Path>) -> Result<Option<VersionPin>, VersionPinError> {
+    read_first_line(app_dir.as_ref(), ".ruby-version", |value| {
+        Some(value.strip_prefix("ruby-").unwrap_or(value))
+    })
+}
+
+/// Reads `.node-version`. Versions may be prefixed with `v`.
+pub fn node_version(app_dir: impl AsRef<Path>) -> Result<Option<VersionPin>, VersionPinError> {
+    read_first_line(app_dir.as_ref(), ".node-version", |value| {
+        Some(value.strip_prefix('v').unwrap_or(value))
+    })
+}
+
+/// Reads `.python-version`. If multiple versions are listed, the first one is used.
+pub fn python_version(app_dir: impl AsRef<Path>) -> Result<Option<VersionPin>, VersionPinError> {
+    read_first_line(app_dir.as_ref(), ".python-version", |value| Some(value))
+}
+
+/// Reads the version of `tool` from an asdf `.tool-versions` file, i.e. `nodejs` or `ruby`. If
+/// multiple versions are listed for the tool, the first one is used.
+pub fn tool_versions(
+    app_dir: impl AsRef<Path>,
+    tool: &str,
+) -> Result<Option<VersionPin>, VersionPinError> {
+    read_first_line(app_dir.as_ref(), ".tool-versions", |value| {
+        let mut words = value.split_whitespace();
+        match words.next() {
+            Some(name) if name == tool => words.next(),
+            _ => None,
+        }
+    })
+}
+
+/// Reads a Heroku-style `runtime.txt`, i.e. `python-3.9.5` for the runtime `python`.
+pub fn runtime_txt(
+    app_dir: impl AsRef<Path>,
+    runtime: &str,
+) -> Result<Option<VersionPin>, VersionPinError> {
+    read_first_line(app_dir.as_ref(), "runtime.txt", |value| {
+        value
+            .strip_prefix(runtime)
+            .and_then(|value| value.strip_prefix('-'))
+    })
+}
+
+// Finds the first line that `extract` returns a version for. Blank lines and `#` comments are
+// skipped.
+fn read_first_line(
+    app_dir: &Path,
+    file: &str,
+    extract: impl Fn(&str) -> Option<&str>,
+) -> Result<Option<VersionPin>, VersionPinError> {
+    let contents = match fs::read_to_string(app_dir.join(file)) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(VersionPinError::IoError(PathBuf::from(file), error)),
+    };
+
+    for (index, line) in contents.lines().enumerate() {
+        let value = line.split('#').next().unwrap_or_default().trim();
+        if value.is_empty() {
+            continue;
+        }
+
+        if let Some(version) = extract(value) {
+            return parse_requirement(version)
+                .map(|requirement| {
+                    Some(VersionPin {
+                        requirement,
+                        file: PathBuf::from(file),
+                        line: index + 1,
+                    })
+                })
+                .map_err(|error| VersionPinError::InvalidVersion {
+                    file: PathBuf::from(file),
+                    line: index + 1,
+                    value: String::from(version),
+                    source: error,
+                });
+        }
+    }
+
+    Ok(None)
+}
+
+fn parse_requirement(version: &str) -> Result<VersionReq, ReqParseError> {
+    if version.chars().all(|c| c.is_ascii_digit() || c == '.') {
+        VersionReq::parse(&format!("={}", version))
+    } else {
+        VersionReq::parse(version)
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum VersionPinError {
+    #[error("Could not read {0}: {1}")]
+    IoError(PathBuf, io::Error),
+
+    #[error("{}:{line}: Invalid version `{value}`: {source}", .file.display())]
+    InvalidVersion {
+        file: PathBuf,
+        line: usize,
+        value: String,
+        source: ReqParseError,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use semver::Version;
+
+    fn app_dir(files: &[(&str, &str)]) -> tempfile::TempDir {
+        let temp_dir = tempfile::tempdir().unwrap();
+        for (file, contents) in files {
+            fs::write(temp_dir.path().join(file), contents).unwrap();
+        }
+        temp_dir
+    }
+
+    fn matches(pin: &VersionPin, version: &str) -> bool {
+        pin.requirement.matches(&Version::parse(version).unwrap())
+    }
+
+    #[test]
+    fn reads_plain_version_files() {
+        let app_dir = app_dir(&[
+            (".ruby-version", "ruby-3.0.1\n"),
+            (".node-version", "\nv16.3\n"),
+            (".python-version", "3.9.5\n2.7.18\n"),
+        ]);
+
+        let ruby = ruby_version(&app_dir).unwrap().unwrap();
+        assert!(matches(&ruby, "3.0.1"));
+        assert!(!matches(&ruby, "3.0.2"));
+        assert_eq!(ruby.to_string(), "=3.0.1 from .ruby-version:1");
+
+        let node = node_version(&app_dir).unwrap().unwrap();
+        assert!(matches(&node, "16.3.7"));
+        assert!(!matches(&node, "16.4.0"));
+        assert_eq!(node.line, 2);
+
+        let python = python_version(&app_dir).unwrap().unwrap();
+        assert!(matches(&python, "3.9.5"));
+    }
+
+    #[test]
+    fn reads_tool_versions() {
+        let app_dir = app_dir(&[(
+            ".tool-versions",
+            "# asdf\nnodejs 16.3.0 # LTS\nruby 3.0.1 2.7.4\n",
+        )]);
+
+        let ruby = tool_versions(&app_dir, "ruby").unwrap().unwrap();
+        assert!(matches(&ruby, "3.0.1"));
+        assert_eq!(ruby.line, 3);
+
+        let node = tool_versions(&app_dir, "nodejs").unwrap().unwrap();
+        assert!(matches(&node, "16.3.0"));
+
+        assert!(tool_versions(&app_dir, "python").unwrap().is_none());
+    }
+
+    #[test]
+    fn reads_runtime_txt() {
+        let app_dir = app_dir(&[("runtime.txt", "python-3.9.5\n")]);
+
+        let python = runtime_txt(&app_dir, "python").unwrap().unwrap();
+        assert!(matches(&python, "3.9.5"));
+        assert!(runtime_txt(&app_dir, "ruby").unwrap().is_none());
+    }
+
+    #[test]
+    fn handles_missing_files_and_invalid_versions() {
+        let app_dir = app_dir(&[(".node-version", "lts/fermium\n")]);
+
+        assert!(ruby_version(&app_dir).unwrap().is_none());
+        assert_eq!(
+            node_version(&app_dir)
+                .unwrap_err()
+                .to_string()
+                .split(": ")
+                .next(),
+            Some(".node-version:1")
+        );
+    }
+}
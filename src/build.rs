@@ -5,9 +5,18 @@ use serde::Serialize;
 
 use crate::{
     data::{
-        buildpack::BuildpackToml, buildpack_plan::BuildpackPlan, launch::Launch,
+        bom::BomCollector,
+        build::Build,
+        buildpack::BuildpackToml,
+        buildpack_plan::BuildpackPlan,
+        launch::Launch,
+        layer::LayerName,
         layer_content_metadata::LayerContentMetadata,
+        layer_env::LayerEnv,
+        sbom::{Sbom, SbomError, SbomFormat},
     },
+    diagnostics::Diagnostics,
+    layer_lifecycle::LayerTracker,
     platform::Platform,
     toml_file::{read_toml_file, write_toml_file, TomlFileError},
 };
@@ -21,21 +30,28 @@ pub struct BuildContext<P: Platform, BM> {
     pub platform: P,
     pub buildpack_plan: BuildpackPlan,
     pub buildpack_descriptor: BuildpackToml<BM>,
+    /// Collects warnings and non-fatal errors. A summary is printed after the build.
+    pub diagnostics: Diagnostics,
+    /// Bill-of-materials entries contributed by layers. They are merged into `launch.toml` and
+    /// `build.toml` when these are written.
+    pub bom: BomCollector,
+    /// Tracks the layers touched during this build, see
+    /// [`LayerCleanupPolicy`](crate::layer_lifecycle::LayerCleanupPolicy).
+    pub layer_tracker: LayerTracker,
 }
 
 impl<P: Platform, BM> BuildContext<P, BM> {
-    pub fn layer_path(&self, layer_name: impl AsRef<str>) -> PathBuf {
-        self.layers_dir.join(layer_name.as_ref())
+    pub fn layer_path(&self, layer_name: &LayerName) -> PathBuf {
+        self.layers_dir.join(layer_name.as_str())
     }
 
-    pub fn layer_content_metadata_path(&self, layer_name: impl AsRef<str>) -> PathBuf {
-        self.layers_dir
-            .join(format!("{}.toml", layer_name.as_ref()))
+    pub fn layer_content_metadata_path(&self, layer_name: &LayerName) -> PathBuf {
+        self.layers_dir.join(format!("{}.toml", layer_name))
     }
 
     pub fn read_layer_content_metadata<M: DeserializeOwned>(
         &self,
-        layer_name: impl AsRef<str>,
+        layer_name: &LayerName,
     ) -> Result<Option<LayerContentMetadata<M>>, TomlFileError> {
         let path = self.layer_content_metadata_path(layer_name);
 
@@ -46,20 +62,41 @@ impl<P: Platform, BM> BuildContext<P, BM> {
         }
     }
 
+    /// Writes layer content metadata for the given layer.
+    ///
+    /// The metadata is written in the shape required by the Buildpack API declared in
+    /// buildpack.toml, see [`LayerContentMetadata::for_api`].
+    ///
+    /// The metadata is written to a temporary file first which is then moved into place. This
+    /// ensures that an interrupted write never leaves a truncated `<layer>.toml` behind.
     pub fn write_layer_content_metadata<M: Serialize>(
         &self,
-        layer_name: impl AsRef<str>,
+        layer_name: &LayerName,
         layer_content_metadata: &LayerContentMetadata<M>,
     ) -> Result<(), TomlFileError> {
-        write_toml_file(
-            layer_content_metadata,
-            self.layer_content_metadata_path(layer_name),
+        self.layer_tracker.touch(layer_name);
+
+        let path = self.layer_content_metadata_path(layer_name);
+        let temporary_path = self
+            .layers_dir
+            .join(format!("{}.toml.libcnb-tmp", layer_name.as_str()));
+
+        let result = write_toml_file(
+            &layer_content_metadata.for_api(&self.buildpack_descriptor.api),
+            &temporary_path,
         )
+        .and_then(|_| fs::rename(&temporary_path, &path).map_err(TomlFileError::from));
+
+        if result.is_err() {
+            let _ = fs::remove_file(&temporary_path);
+        }
+
+        result
     }
 
-    pub fn delete_layer(&self, layer_name: impl AsRef<str>) -> Result<(), std::io::Error> {
+    pub fn delete_layer(&self, layer_name: &LayerName) -> Result<(), std::io::Error> {
         // Do not fail if the metadata file does not exist
-        match fs::remove_file(self.layer_content_metadata_path(&layer_name)) {
+        match fs::remove_file(self.layer_content_metadata_path(layer_name)) {
             Err(io_error) => match io_error.kind() {
                 std::io::ErrorKind::NotFound => Ok(()),
                 _ => Err(io_error),
@@ -67,7 +104,7 @@ impl<P: Platform, BM> BuildContext<P, BM> {
             Ok(_) => Ok(()),
         }?;
 
-        match fs::remove_dir_all(self.layer_path(&layer_name)) {
+        match fs::remove_dir_all(self.layer_path(layer_name)) {
             Err(io_error) => match io_error.kind() {
                 std::io::ErrorKind::NotFound => Ok(()),
                 _ => Err(io_error),
@@ -80,11 +117,11 @@ impl<P: Platform, BM> BuildContext<P, BM> {
 
     pub fn read_layer<M: DeserializeOwned>(
         &self,
-        layer_name: impl AsRef<str>,
+        layer_name: &LayerName,
     ) -> Result<Option<(PathBuf, LayerContentMetadata<M>)>, TomlFileError> {
-        let layer_path = self.layer_path(&layer_name);
+        let layer_path = self.layer_path(layer_name);
 
-        self.read_layer_content_metadata(&layer_name)
+        self.read_layer_content_metadata(layer_name)
             .map(|maybe_content_layer_metadata| {
                 maybe_content_layer_metadata.and_then(
                     |layer_content_metadata: LayerContentMetadata<M>| {
@@ -98,13 +135,132 @@ impl<P: Platform, BM> BuildContext<P, BM> {
             })
     }
 
-    pub fn layer_exists(&self, layer_name: impl AsRef<str>) -> bool {
-        let layer_path = self.layer_path(&layer_name);
-        let content_metadata_path = self.layer_content_metadata_path(&layer_name);
+    pub fn layer_exists(&self, layer_name: &LayerName) -> bool {
+        let layer_path = self.layer_path(layer_name);
+        let content_metadata_path = self.layer_content_metadata_path(layer_name);
         layer_path.exists() && content_metadata_path.exists()
     }
 
+    /// Writes environment variable modifications to the given layer.
+    ///
+    /// Modifications scoped to a process type are checked against `launch.toml` after the build,
+    /// a warning is recorded for each process type that is not declared there.
+    pub fn write_layer_env(
+        &self,
+        layer_name: &LayerName,
+        layer_env: &LayerEnv,
+    ) -> Result<(), std::io::Error> {
+        self.layer_tracker.touch(layer_name);
+
+        layer_env.write_to_layer_dir(self.layer_path(layer_name))
+    }
+
+    /// Reads `launch.toml` if it was written before, for example by an earlier step of the build.
+    pub fn read_launch(&self) -> Result<Option<Launch>, TomlFileError> {
+        let path = self.launch_path();
+
+        if path.exists() {
+            read_toml_file(path).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Writes `launch.toml`, including all bill-of-materials entries contributed by `launch`
+    /// layers so far.
+    ///
+    /// To add to an existing `launch.toml` instead of replacing it, use [`Launch::merge`] with
+    /// the result of [`BuildContext::read_launch`].
+    ///
+    /// Process fields that the Buildpack API of the buildpack does not support are not written,
+    /// a warning is recorded for each of them. See [`Launch::for_api`].
     pub fn write_launch(&self, data: Launch) -> Result<(), TomlFileError> {
-        write_toml_file(&data, self.layers_dir.join("launch.toml"))
+        let mut contributed = Launch::new();
+        contributed.bom = self.bom.launch_entries();
+
+        let launch = data.merge(contributed);
+        let launch_for_api = launch.for_api(&self.buildpack_descriptor.api);
+
+        for (process_type, field) in launch_for_api.unsupported_fields() {
+            self.diagnostics.warning(format!(
+                "`{}` of process `{}` is not supported by Buildpack API {} and was not written",
+                field,
+                process_type.as_str(),
+                self.buildpack_descriptor.api
+            ));
+        }
+
+        write_toml_file(&launch_for_api, self.launch_path())
+    }
+
+    /// Writes `build.toml`, including all bill-of-materials entries contributed by `build`
+    /// layers so far.
+    pub fn write_build(&self, mut data: Build) -> Result<(), TomlFileError> {
+        for entry in self.bom.build_entries() {
+            if !data.bom.contains(&entry) {
+                data.bom.push(entry);
+            }
+        }
+
+        write_toml_file(&data, self.build_path())
+    }
+
+    /// Writes the SBOM file `<layer>.sbom.<ext>` for the given layer.
+    ///
+    /// Fails with [`SbomError::UndeclaredFormat`] if `format` is not declared in the
+    /// `sbom-formats` of buildpack.toml.
+    pub fn write_layer_sbom(
+        &self,
+        layer_name: &LayerName,
+        sbom: &Sbom,
+        format: SbomFormat,
+    ) -> Result<(), SbomError> {
+        self.write_sbom(layer_name.as_str(), sbom, format)
+    }
+
+    /// Writes the SBOM file `launch.sbom.<ext>` describing launch dependencies that are not
+    /// associated with a layer.
+    pub fn write_launch_sbom(&self, sbom: &Sbom, format: SbomFormat) -> Result<(), SbomError> {
+        self.write_sbom("launch", sbom, format)
+    }
+
+    /// Writes the SBOM file `build.sbom.<ext>` describing build dependencies that are not
+    /// associated with a layer.
+    pub fn write_build_sbom(&self, sbom: &Sbom, format: SbomFormat) -> Result<(), SbomError> {
+        self.write_sbom("build", sbom, format)
+    }
+
+    /// Path of the SBOM file `<layer>.sbom.<ext>` for the given layer.
+    pub fn sbom_path(&self, layer_name: &LayerName, format: SbomFormat) -> PathBuf {
+        self.sbom_file_path(layer_name.as_str(), format)
+    }
+
+    fn sbom_file_path(&self, name: &str, format: SbomFormat) -> PathBuf {
+        self.layers_dir
+            .join(format!("{}.sbom.{}", name, format.extension()))
+    }
+
+    fn write_sbom(&self, name: &str, sbom: &Sbom, format: SbomFormat) -> Result<(), SbomError> {
+        if !self
+            .buildpack_descriptor
+            .buildpack
+            .sbom_formats
+            .contains(&format)
+        {
+            return Err(SbomError::UndeclaredFormat(format));
+        }
+
+        let contents = serde_json::to_vec_pretty(&sbom.to_json(format, name)?)?;
+        fs::write(self.sbom_file_path(name, format), contents)?;
+
+        Ok(())
+    }
+
+    pub(crate) fn launch_path(&self) -> PathBuf {
+        self.layers_dir.join("launch.toml")
+    }
+
+    pub(crate) fn build_path(&self) -> PathBuf {
+        self.layers_dir.join("build.toml")
     }
 }
@@ -0,0 +1,92 @@
+use std::fmt::{Debug, Display};
+
+use serde::de::DeserializeOwned;
+
+use crate::build::BuildContext;
+use crate::detect::{DetectContext, DetectOutcome};
+use crate::error::{Error, ErrorHandler};
+use crate::generic::GenericErrorHandler;
+use crate::platform::Platform;
+use crate::Result;
+
+/// A Cloud Native Buildpack.
+///
+/// Implementing this trait for a single type of the buildpack ties together the platform,
+/// buildpack metadata and error types and gives shared configuration and helpers a home. Types
+/// generic over a buildpack, such as [`Layer`](crate::layer_lifecycle::Layer), only need to be
+/// parameterised with that one type.
+///
+/// Use [`buildpack_main!`](crate::buildpack_main) to run a buildpack. This is an alternative to
+/// passing free functions to [`cnb_runtime`](crate::cnb_runtime).
+///
+/// # Examples
+/// ```no_run
+/// use libcnb::data::build_plan::BuildPlan;
+/// use libcnb::{
+///     buildpack_main, Buildpack, DetectOutcome, GenericBuildContext, GenericDetectContext,
+///     GenericMetadata, GenericPlatform,
+/// };
+///
+/// struct RubyBuildpack {
+///     default_ruby_version: &'static str,
+/// }
+///
+/// impl Buildpack for RubyBuildpack {
+///     type Platform = GenericPlatform;
+///     type Metadata = GenericMetadata;
+///     type Error = std::io::Error;
+///
+///     fn detect(&self, context: GenericDetectContext) -> libcnb::Result<DetectOutcome, Self::Error> {
+///         if context.app_dir.join("Gemfile.lock").exists() {
+///             Ok(DetectOutcome::Pass(BuildPlan::new()))
+///         } else {
+///             Ok(DetectOutcome::Fail(None))
+///         }
+///     }
+///
+///     fn build(&self, context: GenericBuildContext) -> libcnb::Result<(), Self::Error> {
+///         println!("---> Installing Ruby {}", self.default_ruby_version);
+///         Ok(())
+///     }
+/// }
+///
+/// buildpack_main!(RubyBuildpack {
+///     default_ruby_version: "3.0.1"
+/// });
+/// ```
+pub trait Buildpack {
+    type Platform: Platform;
+    type Metadata: DeserializeOwned;
+    type Error: Debug + Display;
+
+    fn detect(
+        &self,
+        context: DetectContext<Self::Platform, Self::Metadata>,
+    ) -> Result<DetectOutcome, Self::Error>;
+
+    fn build(
+        &self,
+        context: BuildContext<Self::Platform, Self::Metadata>,
+    ) -> Result<(), Self::Error>;
+
+    /// Handles errors returned from [`Buildpack::detect`] or [`Buildpack::build`] and returns
+    /// the exit code of the buildpack.
+    ///
+    /// The default implementation behaves like [`GenericErrorHandler`].
+    fn handle_error(&self, error: Error<Self::Error>) -> i32 {
+        GenericErrorHandler.handle_error(error)
+    }
+}
+
+/// Generates a `main` function that runs the given [`Buildpack`] with
+/// [`buildpack_runtime`](crate::buildpack_runtime).
+///
+/// See [`Buildpack`] for an example.
+#[macro_export]
+macro_rules! buildpack_main {
+    ($buildpack:expr) => {
+        fn main() {
+            $crate::buildpack_runtime($buildpack);
+        }
+    };
+}
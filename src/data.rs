@@ -6,6 +6,13 @@ pub mod build_plan;
 pub mod buildpack;
 pub mod buildpack_plan;
 pub mod defaults;
+pub mod dependency;
 pub mod launch;
+pub mod layer;
 pub mod layer_content_metadata;
+pub mod layer_env;
+pub mod procfile;
+pub mod sbom;
 pub mod store;
+#[doc(hidden)]
+pub mod validation;
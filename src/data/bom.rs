@@ -1,10 +1,248 @@
-use serde::Serialize;
-use toml;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use serde::{Deserialize, Serialize, Serializer};
+use toml::value::Table;
+
+use crate::data::dependency::Dependency;
 
 pub type Bom = Vec<Entry>;
 
-#[derive(Serialize, Debug)]
+/// A bill-of-materials entry as used in `launch.toml` and `build.toml`.
+///
+/// # Examples
+/// ```
+/// use libcnb::data::bom::Entry;
+///
+/// let entry = Entry::new("ruby")
+///     .version("3.0.1")
+///     .license("BSD-2-Clause")
+///     .uri("https://example.com/ruby-3.0.1.tgz")
+///     .sha256("369825db2199f6aeef16b408e6c6d2c6e8b6bd5b1ed2e1d4e0c6b5fb3d4c5e6f")
+///     .purl("pkg:generic/ruby@3.0.1")
+///     .stack("heroku-20")
+///     .metadata("patchlevel", 64);
+///
+/// assert!(toml::to_string(&entry).is_ok());
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Entry {
     pub name: String,
-    pub metadata: toml::value::Table,
+    #[serde(default)]
+    pub metadata: EntryMetadata,
+}
+
+/// Metadata of a bill-of-materials entry.
+///
+/// Well-known fields are typed, everything else ends up in `extra`.
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct EntryMetadata {
+    pub version: Option<String>,
+    #[serde(default)]
+    pub licenses: Vec<String>,
+    pub uri: Option<String>,
+    pub sha256: Option<String>,
+    pub cpe: Option<String>,
+    pub purl: Option<String>,
+    /// The stacks the described software was built for.
+    #[serde(default)]
+    pub stacks: Vec<String>,
+    #[serde(flatten)]
+    pub extra: Table,
+}
+
+impl Entry {
+    pub fn new(name: impl Into<String>) -> Self {
+        Entry {
+            name: name.into(),
+            metadata: EntryMetadata::default(),
+        }
+    }
+
+    pub fn version(mut self, version: impl Into<String>) -> Self {
+        self.metadata.version = Some(version.into());
+        self
+    }
+
+    pub fn license(mut self, license: impl Into<String>) -> Self {
+        self.metadata.licenses.push(license.into());
+        self
+    }
+
+    pub fn uri(mut self, uri: impl Into<String>) -> Self {
+        self.metadata.uri = Some(uri.into());
+        self
+    }
+
+    pub fn sha256(mut self, sha256: impl Into<String>) -> Self {
+        self.metadata.sha256 = Some(sha256.into());
+        self
+    }
+
+    pub fn cpe(mut self, cpe: impl Into<String>) -> Self {
+        self.metadata.cpe = Some(cpe.into());
+        self
+    }
+
+    pub fn purl(mut self, purl: impl Into<String>) -> Self {
+        self.metadata.purl = Some(purl.into());
+        self
+    }
+
+    pub fn stack(mut self, stack_id: impl Into<String>) -> Self {
+        self.metadata.stacks.push(stack_id.into());
+        self
+    }
+
+    /// Adds arbitrary metadata that is not covered by the well-known fields.
+    pub fn metadata(mut self, key: impl Into<String>, value: impl Into<toml::Value>) -> Self {
+        self.metadata.extra.insert(key.into(), value.into());
+        self
+    }
+}
+
+impl From<&Dependency> for Entry {
+    fn from(dependency: &Dependency) -> Self {
+        Entry {
+            name: dependency.id.clone(),
+            metadata: EntryMetadata {
+                version: Some(dependency.version.to_string()),
+                licenses: dependency.licenses.clone(),
+                uri: Some(dependency.uri.clone()),
+                sha256: Some(dependency.sha256.clone()),
+                stacks: dependency.stacks.clone(),
+                ..EntryMetadata::default()
+            },
+        }
+    }
+}
+
+// Serialized as a single table so that TOML can order nested tables from `extra` after all
+// plain values, which a derived implementation with `#[serde(flatten)]` cannot guarantee.
+impl Serialize for EntryMetadata {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut table = self.extra.clone();
+
+        let optional_strings = vec![
+            ("version", &self.version),
+            ("uri", &self.uri),
+            ("sha256", &self.sha256),
+            ("cpe", &self.cpe),
+            ("purl", &self.purl),
+        ];
+
+        for (key, value) in optional_strings {
+            if let Some(value) = value {
+                table.insert(String::from(key), toml::Value::from(value.as_str()));
+            }
+        }
+
+        for (key, values) in &[("licenses", &self.licenses), ("stacks", &self.stacks)] {
+            if !values.is_empty() {
+                table.insert(String::from(*key), toml::Value::from(values.to_vec()));
+            }
+        }
+
+        toml::Value::Table(table).serialize(serializer)
+    }
+}
+
+/// Collects bill-of-materials entries contributed by layers during the build.
+///
+/// Clones share the same entries. Entries of `launch` layers are merged into `launch.toml`,
+/// entries of `build` layers into `build.toml`. See
+/// [`LayerLifecycle::bom`](crate::layer_lifecycle::LayerLifecycle::bom).
+#[derive(Debug, Clone, Default)]
+pub struct BomCollector {
+    state: Arc<Mutex<BomCollectorState>>,
+}
+
+#[derive(Debug, Default)]
+struct BomCollectorState {
+    launch: Bom,
+    build: Bom,
+}
+
+impl BomCollector {
+    pub fn new() -> Self {
+        BomCollector::default()
+    }
+
+    pub fn add_launch_entries(&self, entries: impl IntoIterator<Item = Entry>) {
+        self.lock().launch.extend(entries);
+    }
+
+    pub fn add_build_entries(&self, entries: impl IntoIterator<Item = Entry>) {
+        self.lock().build.extend(entries);
+    }
+
+    pub fn launch_entries(&self) -> Bom {
+        self.lock().launch.clone()
+    }
+
+    pub fn build_entries(&self) -> Bom {
+        self.lock().build.clone()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BomCollectorState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serializes_well_known_fields_and_extra_metadata() {
+        let entry = Entry::new("ruby")
+            .version("3.0.1")
+            .license("BSD-2-Clause")
+            .stack("heroku-20")
+            .metadata("nested", toml::Value::Table(Table::new()))
+            .metadata("patchlevel", 64);
+
+        let serialized = toml::to_string(&entry).unwrap();
+        let deserialized: Entry = toml::from_str(&serialized).unwrap();
+
+        assert_eq!(deserialized, entry);
+        assert_eq!(
+            deserialized.metadata.extra.get("patchlevel"),
+            Some(&toml::Value::Integer(64))
+        );
+    }
+
+    #[test]
+    fn deserializes_untyped_metadata() {
+        let entry: Entry = toml::from_str(
+            r#"
+name = "bundler"
+
+[metadata]
+version = "2.2.21"
+sha256 = "abc"
+source = "rubygems"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(entry.metadata.version.as_deref(), Some("2.2.21"));
+        assert_eq!(entry.metadata.sha256.as_deref(), Some("abc"));
+        assert_eq!(
+            entry.metadata.extra.get("source"),
+            Some(&toml::Value::from("rubygems"))
+        );
+        assert!(entry.metadata.licenses.is_empty());
+    }
+
+    #[test]
+    fn collector_clones_share_entries() {
+        let collector = BomCollector::new();
+        collector
+            .clone()
+            .add_launch_entries(vec![Entry::new("ruby")]);
+        collector.add_build_entries(vec![Entry::new("gcc")]);
+
+        assert_eq!(collector.launch_entries(), vec![Entry::new("ruby")]);
+        assert_eq!(collector.build_entries(), vec![Entry::new("gcc")]);
+    }
 }
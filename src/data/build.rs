@@ -1,8 +1,55 @@
 use crate::data::bom;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Debug)]
+/// Data Structure for the build.toml file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Build {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub bom: bom::Bom,
-    pub unmet: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unmet: Vec<Unmet>,
+}
+
+impl Build {
+    pub fn new() -> Self {
+        Build {
+            bom: bom::Bom::new(),
+            unmet: Vec::new(),
+        }
+    }
+}
+
+impl Default for Build {
+    fn default() -> Self {
+        Build::new()
+    }
+}
+
+/// A buildpack plan entry the buildpack did not satisfy, see [`Build::unmet`]. Written as an
+/// `[[unmet]]` table, just like the entries of the buildpack plan.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Unmet {
+    pub name: String,
+}
+
+impl Unmet {
+    pub fn new(name: impl Into<String>) -> Self {
+        Unmet { name: name.into() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_unmet_entries_as_tables() {
+        let mut build = Build::new();
+        build.bom.push(bom::Entry::new("bundler"));
+        build.unmet.push(Unmet::new("node"));
+
+        let build_toml = toml::to_string(&build).unwrap();
+        assert!(build_toml.contains("[[unmet]]\nname = \"node\""));
+        assert_eq!(toml::from_str::<Build>(&build_toml).unwrap(), build);
+    }
 }
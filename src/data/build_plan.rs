@@ -1,15 +1,21 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
 use toml::value::Table;
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct BuildPlan {
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub provides: Vec<Provide>,
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub requires: Vec<Require>,
-    #[serde(skip_serializing_if = "Vec::is_empty")]
-    or: Vec<Or>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) or: Vec<Or>,
+}
+
+impl Default for BuildPlan {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl BuildPlan {
@@ -28,6 +34,12 @@ pub struct BuildPlanBuilder {
     current_requires: Vec<Require>,
 }
 
+impl Default for BuildPlanBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl BuildPlanBuilder {
     pub fn new() -> Self {
         BuildPlanBuilder {
@@ -78,17 +90,17 @@ impl BuildPlanBuilder {
     }
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Or {
-    #[serde(skip_serializing_if = "Vec::is_empty")]
-    provides: Vec<Provide>,
-    #[serde(skip_serializing_if = "Vec::is_empty")]
-    requires: Vec<Require>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) provides: Vec<Provide>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) requires: Vec<Require>,
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Provide {
-    name: String,
+    pub(crate) name: String,
 }
 
 impl Provide {
@@ -97,10 +109,15 @@ impl Provide {
     }
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Require {
-    name: String,
-    metadata: Table,
+    pub(crate) name: String,
+    #[serde(
+        default,
+        skip_serializing_if = "Table::is_empty",
+        serialize_with = "toml::ser::tables_last"
+    )]
+    pub(crate) metadata: Table,
 }
 
 impl Require {
@@ -1,8 +1,10 @@
 use crate::data::defaults;
+use crate::data::sbom::SbomFormat;
+use crate::data::validation;
 use lazy_static::lazy_static;
 use regex::Regex;
 use semver::Version;
-use serde::{de, Deserialize};
+use serde::{de, Deserialize, Serialize, Serializer};
 use std::{fmt, str::FromStr};
 use thiserror;
 
@@ -33,42 +35,50 @@ use thiserror;
 ///         let result = toml::from_str::<BuildpackToml<toml::value::Table>>(raw);
 ///         assert!(result.is_ok());
 /// ```
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct BuildpackToml<BM> {
     // MUST be in form <major>.<minor> or <major>, where <major> is equivalent to <major>.0.
     pub api: BuildpackApi,
     pub buildpack: Buildpack,
+    // MAY be omitted by meta-buildpacks, which declare an order instead.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub stacks: Vec<Stack>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub order: Vec<Order>,
     pub metadata: BM,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Buildpack {
     pub id: BuildpackId,
     pub name: String,
     // MUST be in the form <X>.<Y>.<Z> where X, Y, and Z are non-negative integers and must not contain leading zeroes
     pub version: Version,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub homepage: Option<String>,
     #[serde(rename = "clear-env")]
     #[serde(default = "defaults::r#false")]
     pub clear_env: bool,
+    /// SBOM formats the buildpack writes, see [`SbomFormat`]. A warning is recorded after the
+    /// build for each declared format that no SBOM file was written in.
+    #[serde(rename = "sbom-formats")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sbom_formats: Vec<SbomFormat>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Stack {
     pub id: StackId,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub mixins: Vec<String>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Order {
-    group: Vec<Group>,
+    pub(crate) group: Vec<Group>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Group {
     pub id: BuildpackId,
     pub version: Version,
@@ -76,7 +86,7 @@ pub struct Group {
     pub optional: bool,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct BuildpackApi {
     pub major: u32,
     pub minor: u32,
@@ -115,6 +125,18 @@ impl FromStr for BuildpackApi {
     }
 }
 
+impl fmt::Display for BuildpackApi {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+impl Serialize for BuildpackApi {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
 impl<'de> de::Deserialize<'de> for BuildpackApi {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -156,19 +178,15 @@ impl<'de> de::Deserialize<'de> for BuildpackApi {
 /// let invalid = BuildpackId::from_str("!nvalid");
 /// assert!(invalid.is_err());
 /// ```
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct BuildpackId(String);
 
 impl FromStr for BuildpackId {
     type Err = BuildpackTomlError;
 
     fn from_str(value: &str) -> Result<Self, Self::Err> {
-        lazy_static! {
-            static ref RE: Regex = Regex::new(r"^[[:alnum:]./-]+$").unwrap();
-        }
-
         let string = String::from(value);
-        if value != "app" && value != "config" && RE.is_match(value) {
+        if is_valid_buildpack_id(value) {
             Ok(BuildpackId(string))
         } else {
             Err(BuildpackTomlError::InvalidBuildpackId(string))
@@ -180,6 +198,47 @@ impl BuildpackId {
     pub fn as_str(&self) -> &str {
         &self.0
     }
+
+    /// Creates a buildpack id without validation. Used by the
+    /// [`buildpack_id!`](crate::buildpack_id) macro after validating the id at compile time.
+    #[doc(hidden)]
+    pub fn new_unchecked(value: &str) -> Self {
+        BuildpackId(String::from(value))
+    }
+}
+
+/// Validates a buildpack id, see [`BuildpackId`].
+#[doc(hidden)]
+pub const fn is_valid_buildpack_id(value: &str) -> bool {
+    validation::is_alphanumeric_or(value, b"./-")
+        && !validation::is_any_of(value, &["app", "config"])
+}
+
+/// Creates a [`BuildpackId`] from a string literal, validating it at compile time.
+///
+/// # Examples
+/// ```
+/// use libcnb::buildpack_id;
+///
+/// let buildpack_id = buildpack_id!("heroku/ruby");
+/// assert_eq!(buildpack_id.as_str(), "heroku/ruby");
+/// ```
+///
+/// Invalid ids fail to compile:
+/// ```compile_fail
+/// use libcnb::buildpack_id;
+///
+/// let buildpack_id = buildpack_id!("app");
+/// ```
+#[macro_export]
+macro_rules! buildpack_id {
+    ($value:expr) => {{
+        const _: () = assert!(
+            $crate::data::buildpack::is_valid_buildpack_id($value),
+            "Invalid buildpack id, see libcnb::data::buildpack::BuildpackId for the restrictions"
+        );
+        $crate::data::buildpack::BuildpackId::new_unchecked($value)
+    }};
 }
 
 /// buildpack.toml Stack Id. This is a newtype wrapper around a String. It MUST only contain numbers, letters, and the characters ., /, and -. Use [`std::str::FromStr`] to create a new instance of this struct.
@@ -195,20 +254,15 @@ impl BuildpackId {
 /// let invalid = StackId::from_str("!nvalid");
 /// assert!(invalid.is_err());
 /// ```
-
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct StackId(String);
 
 impl FromStr for StackId {
     type Err = BuildpackTomlError;
 
     fn from_str(value: &str) -> Result<Self, Self::Err> {
-        lazy_static! {
-            static ref RE: Regex = Regex::new(r"^[[:alnum:]./-]+$").unwrap();
-        }
-
         let string = String::from(value);
-        if RE.is_match(value) {
+        if is_valid_stack_id(value) {
             Ok(StackId(string))
         } else {
             Err(BuildpackTomlError::InvalidStackId(string))
@@ -220,6 +274,46 @@ impl StackId {
     pub fn as_str(&self) -> &str {
         &self.0
     }
+
+    /// Creates a stack id without validation. Used by the [`stack_id!`](crate::stack_id) macro
+    /// after validating the id at compile time.
+    #[doc(hidden)]
+    pub fn new_unchecked(value: &str) -> Self {
+        StackId(String::from(value))
+    }
+}
+
+/// Validates a stack id, see [`StackId`].
+#[doc(hidden)]
+pub const fn is_valid_stack_id(value: &str) -> bool {
+    validation::is_alphanumeric_or(value, b"./-")
+}
+
+/// Creates a [`StackId`] from a string literal, validating it at compile time.
+///
+/// # Examples
+/// ```
+/// use libcnb::stack_id;
+///
+/// let stack_id = stack_id!("heroku-20");
+/// assert_eq!(stack_id.as_str(), "heroku-20");
+/// ```
+///
+/// Invalid ids fail to compile:
+/// ```compile_fail
+/// use libcnb::stack_id;
+///
+/// let stack_id = stack_id!("heroku 20");
+/// ```
+#[macro_export]
+macro_rules! stack_id {
+    ($value:expr) => {{
+        const _: () = assert!(
+            $crate::data::buildpack::is_valid_stack_id($value),
+            "Invalid stack id, see libcnb::data::buildpack::StackId for the restrictions"
+        );
+        $crate::data::buildpack::StackId::new_unchecked($value)
+    }};
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -239,6 +333,7 @@ pub enum BuildpackTomlError {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
 
     #[test]
     fn buildpack_id_does_not_allow_app() {
@@ -323,7 +418,7 @@ checksum = "awesome"
         let result = toml::from_str::<BuildpackToml<toml::value::Table>>(raw);
         assert!(result.is_ok());
         if let Ok(toml) = result {
-            assert_eq!(false, toml.buildpack.clear_env);
+            assert!(!toml.buildpack.clear_env);
         }
     }
 
@@ -349,10 +444,57 @@ id = "io.buildpacks.stacks.bionic"
         let result = toml::from_str::<BuildpackToml<Option<toml::value::Table>>>(raw);
         assert!(result.is_ok());
         if let Ok(toml) = result {
-            assert_eq!(
-                false,
-                toml.order.get(0).unwrap().group.get(0).unwrap().optional
-            );
+            assert!(!toml.order.first().unwrap().group.first().unwrap().optional);
+        }
+    }
+
+    #[test]
+    fn can_deserialize_metabuildpack_without_stacks() {
+        let raw = r#"
+api = "0.4"
+
+[buildpack]
+id = "foo/bar"
+name = "Bar Buildpack"
+version = "0.0.1"
+
+[[order]]
+[[order.group]]
+id = "foo/baz"
+version = "0.0.2"
+"#;
+
+        let buildpack_toml =
+            toml::from_str::<BuildpackToml<Option<toml::value::Table>>>(raw).unwrap();
+        assert!(buildpack_toml.stacks.is_empty());
+
+        let serialized = toml::to_string(&buildpack_toml).unwrap();
+        assert!(!serialized.contains("stacks"));
+        assert_eq!(
+            toml::from_str::<BuildpackToml<Option<toml::value::Table>>>(&serialized).unwrap(),
+            buildpack_toml
+        );
+    }
+
+    #[test]
+    fn id_macros_match_from_str() {
+        assert_eq!(
+            crate::buildpack_id!("heroku/ruby"),
+            BuildpackId::from_str("heroku/ruby").unwrap()
+        );
+        assert_eq!(
+            crate::stack_id!("io.buildpacks.stacks.bionic"),
+            StackId::from_str("io.buildpacks.stacks.bionic").unwrap()
+        );
+        assert!(!is_valid_buildpack_id("config"));
+        assert!(!is_valid_stack_id("heroku 20"));
+    }
+
+    proptest! {
+        #[test]
+        fn buildpack_api_round_trips(major: u32, minor: u32) {
+            let api = BuildpackApi { major, minor };
+            prop_assert_eq!(BuildpackApi::from_str(&api.to_string()).unwrap(), api);
         }
     }
 }
@@ -1,16 +1,20 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use toml::value::Table;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct BuildpackPlan {
     #[serde(default)]
     pub entries: Vec<Entry>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct Entry {
     pub name: String,
-    #[serde(default)]
+    #[serde(
+        default,
+        skip_serializing_if = "Table::is_empty",
+        serialize_with = "toml::ser::tables_last"
+    )]
     pub metadata: Table,
 }
 
@@ -0,0 +1,388 @@
+use std::env::VarError;
+use std::ffi::OsString;
+
+use semver::{ReqParseError, Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use toml::value::Datetime;
+
+use crate::data::buildpack_plan::BuildpackPlan;
+use crate::platform::PlatformEnv;
+
+/// Typed `[metadata]` of a buildpack.toml that contains a dependency manifest.
+///
+/// Buildpacks that need additional metadata can embed this struct with `#[serde(flatten)]`.
+///
+/// # Examples
+/// ```
+/// use libcnb::data::buildpack::BuildpackToml;
+/// use libcnb::data::dependency::DependenciesMetadata;
+/// use semver::VersionReq;
+///
+/// let raw = r#"
+/// api = "0.4"
+///
+/// [buildpack]
+/// id = "heroku/ruby"
+/// name = "Ruby Buildpack"
+/// version = "0.0.1"
+///
+/// [[stacks]]
+/// id = "heroku-20"
+///
+/// [[metadata.dependencies]]
+/// id = "ruby"
+/// version = "3.0.1"
+/// uri = "https://example.com/heroku-20/ruby-3.0.1.tgz"
+/// sha256 = "369825db2199f6aeef16b408e6c6d2c6e8b6bd5b1ed2e1d4e0c6b5fb3d4c5e6f"
+/// stacks = ["heroku-20"]
+/// licenses = ["BSD-2-Clause"]
+/// deprecation-date = 2024-03-31
+///
+/// [[metadata.dependencies]]
+/// id = "ruby"
+/// version = "2.7.3"
+/// uri = "https://example.com/heroku-20/ruby-2.7.3.tgz"
+/// sha256 = "c2a3e2d5f4b3a1c0d9e8f7a6b5c4d3e2f1a0b9c8d7e6f5a4b3c2d1e0f9a8b7c6"
+/// stacks = ["heroku-20"]
+/// "#;
+///
+/// let buildpack_toml = toml::from_str::<BuildpackToml<DependenciesMetadata>>(raw).unwrap();
+/// let ruby = buildpack_toml
+///     .metadata
+///     .resolve("ruby", &VersionReq::parse("^3").unwrap(), "heroku-20")
+///     .unwrap();
+///
+/// assert_eq!(ruby.version.to_string(), "3.0.1");
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct DependenciesMetadata {
+    #[serde(default)]
+    pub dependencies: Vec<Dependency>,
+}
+
+/// A single dependency in a dependency manifest.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct Dependency {
+    pub id: String,
+    pub version: Version,
+    pub uri: String,
+    pub sha256: String,
+    /// The stacks this dependency is compatible with. `*` matches any stack.
+    pub stacks: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub licenses: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deprecation_date: Option<Datetime>,
+}
+
+impl Dependency {
+    pub fn supports_stack(&self, stack_id: &str) -> bool {
+        self.stacks
+            .iter()
+            .any(|stack| stack == "*" || stack == stack_id)
+    }
+}
+
+impl DependenciesMetadata {
+    /// Returns the dependency with the given id and the highest version that matches
+    /// `requirement` and supports the given stack.
+    pub fn resolve(
+        &self,
+        id: &str,
+        requirement: &VersionReq,
+        stack_id: &str,
+    ) -> Result<&Dependency, DependencyResolutionError> {
+        let candidates: Vec<&Dependency> = self
+            .dependencies
+            .iter()
+            .filter(|dependency| dependency.id == id)
+            .collect();
+
+        if candidates.is_empty() {
+            return Err(DependencyResolutionError::UnknownDependency(String::from(
+                id,
+            )));
+        }
+
+        let stack_candidates: Vec<&Dependency> = candidates
+            .into_iter()
+            .filter(|dependency| dependency.supports_stack(stack_id))
+            .collect();
+
+        if stack_candidates.is_empty() {
+            return Err(DependencyResolutionError::UnsupportedStack {
+                id: String::from(id),
+                stack_id: String::from(stack_id),
+            });
+        }
+
+        let mut available: Vec<Version> = stack_candidates
+            .iter()
+            .map(|dependency| dependency.version.clone())
+            .collect();
+        available.sort();
+
+        stack_candidates
+            .into_iter()
+            .filter(|dependency| requirement.matches(&dependency.version))
+            .max_by(|a, b| a.version.cmp(&b.version))
+            .ok_or_else(|| DependencyResolutionError::NoMatchingVersion {
+                id: String::from(id),
+                requirement: requirement.clone(),
+                stack_id: String::from(stack_id),
+                available,
+            })
+    }
+}
+
+/// Reads a version requirement from the `version` key of the metadata of the buildpack plan
+/// entry with the given name.
+///
+/// Returns `None` if there is no such entry or none of the entries have a version. If there are
+/// multiple entries with the same name, the first one with a version wins.
+pub fn version_requirement_from_buildpack_plan(
+    buildpack_plan: &BuildpackPlan,
+    name: &str,
+) -> Result<Option<VersionReq>, DependencyResolutionError> {
+    buildpack_plan
+        .entries
+        .iter()
+        .filter(|entry| entry.name == name)
+        .find_map(|entry| entry.metadata.get("version"))
+        .map(|value| match value.as_str() {
+            Some(requirement) => parse_version_requirement(requirement),
+            None => Err(DependencyResolutionError::InvalidVersionRequirementType(
+                value.clone(),
+            )),
+        })
+        .transpose()
+}
+
+/// Reads a version requirement from the platform environment variable `key`.
+///
+/// Returns `None` if the variable is not set and an error if it is not valid unicode.
+pub fn version_requirement_from_env(
+    env: &PlatformEnv,
+    key: &str,
+) -> Result<Option<VersionReq>, DependencyResolutionError> {
+    match env.var(key) {
+        Ok(requirement) => parse_version_requirement(&requirement).map(Some),
+        Err(VarError::NotPresent) => Ok(None),
+        Err(VarError::NotUnicode(value)) => Err(
+            DependencyResolutionError::NonUnicodeVersionRequirement(String::from(key), value),
+        ),
+    }
+}
+
+fn parse_version_requirement(requirement: &str) -> Result<VersionReq, DependencyResolutionError> {
+    let requirement = requirement.trim();
+
+    VersionReq::parse(requirement).map_err(|error| {
+        DependencyResolutionError::InvalidVersionRequirement(String::from(requirement), error)
+    })
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum DependencyResolutionError {
+    #[error("Dependency `{0}` is not part of the dependency manifest")]
+    UnknownDependency(String),
+
+    #[error("Dependency `{id}` is not available for stack `{stack_id}`")]
+    UnsupportedStack { id: String, stack_id: String },
+
+    #[error("No version of dependency `{id}` matches `{requirement}` on stack `{stack_id}`. Available versions: {}", format_versions(.available))]
+    NoMatchingVersion {
+        id: String,
+        requirement: VersionReq,
+        stack_id: String,
+        available: Vec<Version>,
+    },
+
+    #[error("Invalid version requirement `{0}`: {1}")]
+    InvalidVersionRequirement(String, ReqParseError),
+
+    #[error("Version requirement must be a string, found: {0}")]
+    InvalidVersionRequirementType(toml::Value),
+
+    #[error("Version requirement in `{0}` is not valid unicode: {1:?}")]
+    NonUnicodeVersionRequirement(String, OsString),
+}
+
+fn format_versions(versions: &[Version]) -> String {
+    versions
+        .iter()
+        .map(Version::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn dependencies() -> DependenciesMetadata {
+        toml::from_str(
+            r#"
+[[dependencies]]
+id = "ruby"
+version = "2.7.3"
+uri = "https://example.com/ruby-2.7.3.tgz"
+sha256 = "aaaa"
+stacks = ["heroku-18", "heroku-20"]
+
+[[dependencies]]
+id = "ruby"
+version = "3.0.1"
+uri = "https://example.com/ruby-3.0.1.tgz"
+sha256 = "bbbb"
+stacks = ["heroku-20"]
+licenses = ["BSD-2-Clause", "Ruby"]
+deprecation-date = 2024-03-31
+
+[[dependencies]]
+id = "ruby"
+version = "3.0.2"
+uri = "https://example.com/ruby-3.0.2.tgz"
+sha256 = "cccc"
+stacks = ["heroku-20"]
+
+[[dependencies]]
+id = "bundler"
+version = "2.2.21"
+uri = "https://example.com/bundler-2.2.21.tgz"
+sha256 = "dddd"
+stacks = ["*"]
+"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn parses_dependency() {
+        let dependencies = dependencies();
+        let ruby = &dependencies.dependencies[1];
+
+        assert_eq!(ruby.licenses, vec!["BSD-2-Clause", "Ruby"]);
+        assert_eq!(
+            ruby.deprecation_date.as_ref().map(ToString::to_string),
+            Some(String::from("2024-03-31"))
+        );
+    }
+
+    #[test]
+    fn resolves_highest_matching_version() {
+        let dependencies = dependencies();
+
+        let ruby = dependencies
+            .resolve("ruby", &VersionReq::parse("~3.0").unwrap(), "heroku-20")
+            .unwrap();
+        assert_eq!(ruby.version, Version::new(3, 0, 2));
+
+        let ruby = dependencies
+            .resolve("ruby", &VersionReq::parse("*").unwrap(), "heroku-18")
+            .unwrap();
+        assert_eq!(ruby.version, Version::new(2, 7, 3));
+    }
+
+    #[test]
+    fn resolves_wildcard_stack() {
+        let bundler = dependencies()
+            .resolve("bundler", &VersionReq::parse("^2").unwrap(), "heroku-22")
+            .map(|dependency| dependency.sha256.clone());
+
+        assert_eq!(bundler.unwrap(), "dddd");
+    }
+
+    #[test]
+    fn fails_for_unknown_dependency_or_stack() {
+        let dependencies = dependencies();
+        let requirement = VersionReq::parse("*").unwrap();
+
+        assert!(matches!(
+            dependencies.resolve("node", &requirement, "heroku-20"),
+            Err(DependencyResolutionError::UnknownDependency(_))
+        ));
+        assert!(matches!(
+            dependencies.resolve("ruby", &requirement, "heroku-16"),
+            Err(DependencyResolutionError::UnsupportedStack { .. })
+        ));
+    }
+
+    #[test]
+    fn fails_without_matching_version() {
+        let error = dependencies()
+            .resolve("ruby", &VersionReq::parse("^3.1").unwrap(), "heroku-20")
+            .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "No version of dependency `ruby` matches `>=3.1.0, <4.0.0` on stack `heroku-20`. Available versions: 2.7.3, 3.0.1, 3.0.2"
+        );
+    }
+
+    #[test]
+    fn reads_requirement_from_buildpack_plan() {
+        let buildpack_plan: BuildpackPlan = toml::from_str(
+            r#"
+[[entries]]
+name = "ruby"
+
+[[entries]]
+name = "ruby"
+    [entries.metadata]
+    version = "~3.0"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            version_requirement_from_buildpack_plan(&buildpack_plan, "ruby").unwrap(),
+            Some(VersionReq::parse("~3.0").unwrap())
+        );
+        assert_eq!(
+            version_requirement_from_buildpack_plan(&buildpack_plan, "node").unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn reads_requirement_from_env() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let env_dir = temp_dir.path().join("env");
+        fs::create_dir_all(&env_dir).unwrap();
+        fs::write(env_dir.join("RUBY_VERSION"), "3.0.x\n").unwrap();
+        fs::write(env_dir.join("NODE_VERSION"), "not a version").unwrap();
+
+        let env = PlatformEnv::from_path(temp_dir.path()).unwrap();
+
+        assert_eq!(
+            version_requirement_from_env(&env, "RUBY_VERSION").unwrap(),
+            Some(VersionReq::parse("3.0.x").unwrap())
+        );
+        assert!(version_requirement_from_env(&env, "BUNDLER_VERSION")
+            .unwrap()
+            .is_none());
+        assert!(matches!(
+            version_requirement_from_env(&env, "NODE_VERSION"),
+            Err(DependencyResolutionError::InvalidVersionRequirement(..))
+        ));
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn rejects_non_unicode_requirement_from_env() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let env_dir = temp_dir.path().join("env");
+        fs::create_dir_all(&env_dir).unwrap();
+        fs::write(env_dir.join("RUBY_VERSION"), [0x33, 0xff, 0xfe]).unwrap();
+
+        let env = PlatformEnv::from_path(temp_dir.path()).unwrap();
+
+        assert!(matches!(
+            version_requirement_from_env(&env, "RUBY_VERSION"),
+            Err(DependencyResolutionError::NonUnicodeVersionRequirement(key, _)) if key == "RUBY_VERSION"
+        ));
+    }
+}
@@ -1,19 +1,24 @@
 use crate::data::bom;
+use crate::data::buildpack::BuildpackApi;
+use crate::data::validation;
 use lazy_static::lazy_static;
 use regex::Regex;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::path::{Component, Path, PathBuf};
 use std::str::FromStr;
 use thiserror;
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Launch {
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub bom: bom::Bom,
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub labels: Vec<Label>,
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub processes: Vec<Process>,
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub slices: Vec<Slice>,
 }
 
@@ -39,10 +44,84 @@ impl Launch {
         }
     }
 
+    /// Adds a process without any validation. Use [`LaunchBuilder`] to detect duplicate
+    /// process types.
     pub fn process(mut self, process: Process) -> Self {
         self.processes.push(process);
         self
     }
+
+    /// Merges `other` into this launch data.
+    ///
+    /// - Processes of `other` replace processes with the same type. If a process of `other` is
+    ///   the default process, no other process stays the default.
+    /// - Labels of `other` replace labels with the same key.
+    /// - Bill-of-materials entries and slices are added unless an equal one already exists.
+    ///
+    /// # Examples
+    /// ```
+    /// use libcnb::data::launch::{Launch, LaunchBuilder, ProcessBuilder};
+    /// use libcnb::process_type;
+    ///
+    /// let launch = LaunchBuilder::new()
+    ///     .process(ProcessBuilder::new(process_type!("web"), "bundle").args(vec!["exec", "rackup"]))
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// let merged = launch.merge(
+    ///     LaunchBuilder::new()
+    ///         .process(ProcessBuilder::new(process_type!("web"), "bundle").args(vec!["exec", "puma"]))
+    ///         .process(ProcessBuilder::new(process_type!("worker"), "bundle").args(vec!["exec", "sidekiq"]))
+    ///         .build()
+    ///         .unwrap(),
+    /// );
+    ///
+    /// assert_eq!(merged.processes.len(), 2);
+    /// assert_eq!(merged.processes[0].args, vec!["exec", "puma"]);
+    /// ```
+    pub fn merge(mut self, other: Launch) -> Self {
+        for process in other.processes {
+            if process.default {
+                for existing in &mut self.processes {
+                    existing.default = false;
+                }
+            }
+
+            match self
+                .processes
+                .iter_mut()
+                .find(|existing| existing.r#type == process.r#type)
+            {
+                Some(existing) => *existing = process,
+                None => self.processes.push(process),
+            }
+        }
+
+        for label in other.labels {
+            match self
+                .labels
+                .iter_mut()
+                .find(|existing| existing.key == label.key)
+            {
+                Some(existing) => *existing = label,
+                None => self.labels.push(label),
+            }
+        }
+
+        for entry in other.bom {
+            if !self.bom.contains(&entry) {
+                self.bom.push(entry);
+            }
+        }
+
+        for slice in other.slices {
+            if !self.slices.contains(&slice) {
+                self.slices.push(slice);
+            }
+        }
+
+        self
+    }
 }
 
 impl Default for Launch {
@@ -51,18 +130,117 @@ impl Default for Launch {
     }
 }
 
-#[derive(Serialize, Debug)]
+impl Launch {
+    /// Returns a view that serializes only the process fields supported by the given Buildpack
+    /// API. `default` requires Buildpack API 0.6, `working-dir` requires Buildpack API 0.8.
+    ///
+    /// # Examples
+    /// ```
+    /// use libcnb::data::buildpack::BuildpackApi;
+    /// use libcnb::data::launch::{LaunchBuilder, ProcessBuilder};
+    /// use libcnb::process_type;
+    /// use std::str::FromStr;
+    ///
+    /// let launch = LaunchBuilder::new()
+    ///     .process(ProcessBuilder::new(process_type!("web"), "bundle").default(true))
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// let api = BuildpackApi::from_str("0.5").unwrap();
+    /// let serialized = toml::to_string(&launch.for_api(&api)).unwrap();
+    /// assert!(!serialized.contains("default"));
+    /// ```
+    pub fn for_api<'a>(&'a self, api: &BuildpackApi) -> LaunchForApi<'a> {
+        LaunchForApi {
+            launch: self,
+            process_defaults: *api >= PROCESS_DEFAULT_API,
+            process_working_directories: *api >= PROCESS_WORKING_DIRECTORY_API,
+        }
+    }
+}
+
+/// The first Buildpack API that supports `default` processes.
+const PROCESS_DEFAULT_API: BuildpackApi = BuildpackApi { major: 0, minor: 6 };
+
+/// The first Buildpack API that supports process working directories.
+const PROCESS_WORKING_DIRECTORY_API: BuildpackApi = BuildpackApi { major: 0, minor: 8 };
+
+/// Serializes [`Launch`] for a specific Buildpack API.
+///
+/// See [`Launch::for_api`].
+pub struct LaunchForApi<'a> {
+    launch: &'a Launch,
+    process_defaults: bool,
+    process_working_directories: bool,
+}
+
+impl<'a> LaunchForApi<'a> {
+    /// Process fields that are set, but not supported by the Buildpack API and therefore not
+    /// serialized, as `(process type, field)`.
+    pub(crate) fn unsupported_fields(&self) -> Vec<(&'a ProcessType, &'static str)> {
+        let mut unsupported = vec![];
+
+        for process in &self.launch.processes {
+            if process.default && !self.process_defaults {
+                unsupported.push((&process.r#type, "default"));
+            }
+
+            if process.working_directory.is_some() && !self.process_working_directories {
+                unsupported.push((&process.r#type, "working-dir"));
+            }
+        }
+
+        unsupported
+    }
+}
+
+impl<'a> Serialize for LaunchForApi<'a> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut launch = self.launch.clone();
+
+        for process in &mut launch.processes {
+            process.default &= self.process_defaults;
+
+            if !self.process_working_directories {
+                process.working_directory = None;
+            }
+        }
+
+        launch.serialize(serializer)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Label {
     pub key: String,
     pub value: String,
 }
 
-#[derive(Serialize, Debug)]
+impl Label {
+    pub fn new(key: impl Into<String>, value: impl Into<String>) -> Self {
+        Label {
+            key: key.into(),
+            value: value.into(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Process {
     pub r#type: ProcessType,
     pub command: String,
+    #[serde(default)]
     pub args: Vec<String>,
+    #[serde(default)]
     pub direct: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub default: bool,
+    #[serde(
+        rename = "working-dir",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub working_directory: Option<PathBuf>,
 }
 
 impl Process {
@@ -72,20 +250,264 @@ impl Process {
         args: impl IntoIterator<Item = impl Into<String>>,
         direct: bool,
     ) -> Result<Self, ProcessTypeError> {
-        Ok(Process {
-            r#type: ProcessType::from_str(r#type.as_ref())?,
+        Ok(Process::with_type(
+            ProcessType::from_str(r#type.as_ref())?,
+            command,
+            args,
+            direct,
+        ))
+    }
+
+    /// Creates a process with an already validated process type, i.e. from
+    /// [`process_type!`](crate::process_type).
+    pub fn with_type(
+        r#type: ProcessType,
+        command: impl Into<String>,
+        args: impl IntoIterator<Item = impl Into<String>>,
+        direct: bool,
+    ) -> Self {
+        Process {
+            r#type,
             command: command.into(),
             args: args.into_iter().map(|i| i.into()).collect(),
             direct,
-        })
+            default: false,
+            working_directory: None,
+        }
     }
 }
 
-#[derive(Serialize, Debug)]
+/// Builds a [`Process`]. The process type is validated up front, usually at compile time with
+/// [`process_type!`](crate::process_type).
+///
+/// # Examples
+/// ```
+/// use libcnb::data::launch::ProcessBuilder;
+/// use libcnb::process_type;
+///
+/// let process = ProcessBuilder::new(process_type!("web"), "bundle")
+///     .args(vec!["exec", "puma"])
+///     .arg("--port=$PORT")
+///     .default(true)
+///     .working_directory("/workspace/api")
+///     .build();
+///
+/// assert_eq!(process.r#type.as_str(), "web");
+/// assert_eq!(process.args, vec!["exec", "puma", "--port=$PORT"]);
+/// assert!(process.default);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ProcessBuilder {
+    r#type: ProcessType,
+    command: String,
+    args: Vec<String>,
+    direct: bool,
+    default: bool,
+    working_directory: Option<PathBuf>,
+}
+
+impl ProcessBuilder {
+    pub fn new(r#type: ProcessType, command: impl Into<String>) -> Self {
+        ProcessBuilder {
+            r#type,
+            command: command.into(),
+            args: Vec::new(),
+            direct: false,
+            default: false,
+            working_directory: None,
+        }
+    }
+
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    pub fn args(mut self, args: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Executes the command directly instead of through a shell.
+    pub fn direct(mut self, direct: bool) -> Self {
+        self.direct = direct;
+        self
+    }
+
+    /// Makes this process the default process of the image.
+    pub fn default(mut self, default: bool) -> Self {
+        self.default = default;
+        self
+    }
+
+    pub fn working_directory(mut self, working_directory: impl Into<PathBuf>) -> Self {
+        self.working_directory = Some(working_directory.into());
+        self
+    }
+
+    pub fn build(self) -> Process {
+        Process {
+            r#type: self.r#type,
+            command: self.command,
+            args: self.args,
+            direct: self.direct,
+            default: self.default,
+            working_directory: self.working_directory,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Slice {
     pub paths: Vec<String>,
 }
 
+impl Slice {
+    pub fn new(paths: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Slice {
+            paths: paths.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+/// Builds a validated [`Launch`].
+///
+/// Validation happens in [`LaunchBuilder::build`] which rejects data that the lifecycle would
+/// reject as well:
+///
+/// - Process types must be unique, and at most one process can be the default.
+/// - Label keys must follow the OCI label key conventions and must be unique.
+/// - Slice paths must be valid glob patterns relative to the app directory.
+///
+/// # Examples
+/// ```
+/// use libcnb::data::launch::{LaunchBuilder, LaunchBuilderError, ProcessBuilder};
+/// use libcnb::process_type;
+///
+/// let launch = LaunchBuilder::new()
+///     .process(ProcessBuilder::new(process_type!("web"), "bundle").args(vec!["exec", "puma"]).default(true))
+///     .process(ProcessBuilder::new(process_type!("worker"), "bundle").args(vec!["exec", "sidekiq"]))
+///     .label("com.example.ruby-version", "3.0.1")
+///     .slice(vec!["public/assets/**/*"])
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(launch.processes.len(), 2);
+///
+/// let result = LaunchBuilder::new()
+///     .process(ProcessBuilder::new(process_type!("web"), "bundle"))
+///     .process(ProcessBuilder::new(process_type!("web"), "rackup"))
+///     .build();
+///
+/// assert!(matches!(result, Err(LaunchBuilderError::DuplicateProcessType(_))));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct LaunchBuilder {
+    processes: Vec<ProcessBuilder>,
+    labels: Vec<(String, String)>,
+    slices: Vec<Vec<String>>,
+}
+
+impl LaunchBuilder {
+    pub fn new() -> Self {
+        LaunchBuilder::default()
+    }
+
+    pub fn process(mut self, process: ProcessBuilder) -> Self {
+        self.processes.push(process);
+        self
+    }
+
+    pub fn label(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.labels.push((key.into(), value.into()));
+        self
+    }
+
+    pub fn slice(mut self, paths: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.slices
+            .push(paths.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn build(self) -> Result<Launch, LaunchBuilderError> {
+        let mut launch = Launch::new();
+
+        let mut process_types = HashSet::new();
+        let mut default_process_types = Vec::new();
+        for process in self.processes {
+            let process = process.build();
+
+            if !process_types.insert(String::from(process.r#type.as_str())) {
+                return Err(LaunchBuilderError::DuplicateProcessType(String::from(
+                    process.r#type.as_str(),
+                )));
+            }
+
+            if process.default {
+                default_process_types.push(String::from(process.r#type.as_str()));
+            }
+
+            launch.processes.push(process);
+        }
+
+        if default_process_types.len() > 1 {
+            return Err(LaunchBuilderError::MultipleDefaultProcesses(
+                default_process_types,
+            ));
+        }
+
+        let mut label_keys = HashSet::new();
+        for (key, value) in self.labels {
+            validate_label_key(&key)?;
+
+            if !label_keys.insert(key.clone()) {
+                return Err(LaunchBuilderError::DuplicateLabel(key));
+            }
+
+            launch.labels.push(Label { key, value });
+        }
+
+        for paths in self.slices {
+            for path in &paths {
+                validate_slice_path(path)?;
+            }
+
+            launch.slices.push(Slice { paths });
+        }
+
+        Ok(launch)
+    }
+}
+
+fn validate_label_key(key: &str) -> Result<(), LaunchBuilderError> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"^[a-z0-9]+([.-][a-z0-9]+)*$").unwrap();
+    }
+
+    if RE.is_match(key) {
+        Ok(())
+    } else {
+        Err(LaunchBuilderError::InvalidLabelKey(String::from(key)))
+    }
+}
+
+fn validate_slice_path(path: &str) -> Result<(), LaunchBuilderError> {
+    glob::Pattern::new(path)
+        .map_err(|error| LaunchBuilderError::InvalidSlicePattern(String::from(path), error))?;
+
+    let escapes_app_dir = Path::new(path)
+        .components()
+        .any(|component| !matches!(component, Component::Normal(_) | Component::CurDir));
+
+    if escapes_app_dir {
+        Err(LaunchBuilderError::SlicePathOutsideAppDir(String::from(
+            path,
+        )))
+    } else {
+        Ok(())
+    }
+}
+
 /// launch.toml Process Type. This is a newtype wrapper around a String. It MUST only contain numbers, letters, and the characters ., _, and -. Use [`std::str::FromStr`] to create a new instance of this struct.
 ///
 /// # Examples
@@ -99,25 +521,63 @@ pub struct Slice {
 /// let invalid = ProcessType::from_str("!nv4lid");
 /// assert!(invalid.is_err());
 /// ```
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(try_from = "String")]
 pub struct ProcessType(String);
 
 impl ProcessType {
     pub fn as_str(&self) -> &str {
         &self.0
     }
+
+    /// Creates a process type without validation. Used by the
+    /// [`process_type!`](crate::process_type) macro after validating the type at compile time.
+    #[doc(hidden)]
+    pub fn new_unchecked(value: &str) -> Self {
+        ProcessType(String::from(value))
+    }
+}
+
+/// Validates a process type, see [`ProcessType`].
+#[doc(hidden)]
+pub const fn is_valid_process_type(value: &str) -> bool {
+    validation::is_alphanumeric_or(value, b"_-")
+}
+
+/// Creates a [`ProcessType`] from a string literal, validating it at compile time.
+///
+/// # Examples
+/// ```
+/// use libcnb::data::launch::Process;
+/// use libcnb::process_type;
+///
+/// let process = Process::with_type(process_type!("web"), "bundle", vec!["exec", "puma"], false);
+/// assert_eq!(process.r#type.as_str(), "web");
+/// ```
+///
+/// Invalid types fail to compile:
+/// ```compile_fail
+/// use libcnb::process_type;
+///
+/// let process_type = process_type!("web server");
+/// ```
+#[macro_export]
+macro_rules! process_type {
+    ($value:expr) => {{
+        const _: () = assert!(
+            $crate::data::launch::is_valid_process_type($value),
+            "Invalid process type, see libcnb::data::launch::ProcessType for the restrictions"
+        );
+        $crate::data::launch::ProcessType::new_unchecked($value)
+    }};
 }
 
 impl FromStr for ProcessType {
     type Err = ProcessTypeError;
 
     fn from_str(value: &str) -> Result<Self, Self::Err> {
-        lazy_static! {
-            static ref RE: Regex = Regex::new(r"^[[:alnum:]_-]+$").unwrap();
-        }
-
         let string = String::from(value);
-        if RE.is_match(value) {
+        if is_valid_process_type(value) {
             Ok(ProcessType(string))
         } else {
             Err(ProcessTypeError::InvalidProcessType(string))
@@ -125,6 +585,35 @@ impl FromStr for ProcessType {
     }
 }
 
+impl TryFrom<String> for ProcessType {
+    type Error = ProcessTypeError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        ProcessType::from_str(&value)
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum LaunchBuilderError {
+    #[error("Process type `{0}` was added more than once")]
+    DuplicateProcessType(String),
+
+    #[error("Only one process can be the default, found: {}", .0.join(", "))]
+    MultipleDefaultProcesses(Vec<String>),
+
+    #[error("Found label key `{0}` but value MUST only contain lowercase letters, numbers and the characters . and -, separators MUST NOT be adjacent and MUST NOT start or end the key.")]
+    InvalidLabelKey(String),
+
+    #[error("Label `{0}` was added more than once")]
+    DuplicateLabel(String),
+
+    #[error("Invalid slice glob pattern `{0}`: {1}")]
+    InvalidSlicePattern(String, glob::PatternError),
+
+    #[error("Slice path `{0}` MUST be relative to the app directory and MUST NOT contain `..`")]
+    SlicePathOutsideAppDir(String),
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum ProcessTypeError {
     #[error(
@@ -132,3 +621,276 @@ pub enum ProcessTypeError {
     )]
     InvalidProcessType(String),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::buildpack::BuildpackApi;
+    use crate::test_support::build_context;
+
+    #[test]
+    fn builds_processes() {
+        let launch = LaunchBuilder::new()
+            .process(
+                ProcessBuilder::new(process_type!("web"), "bin/server")
+                    .direct(true)
+                    .default(true)
+                    .working_directory("api"),
+            )
+            .build()
+            .unwrap();
+
+        let launch_toml = toml::to_string(&launch).unwrap();
+        assert!(launch_toml.contains("default = true"));
+        assert!(launch_toml.contains("working-dir = \"api\""));
+        assert!(launch_toml.contains("direct = true"));
+    }
+
+    #[test]
+    fn omits_optional_process_fields() {
+        let launch = LaunchBuilder::new()
+            .process(ProcessBuilder::new(process_type!("web"), "bin/server"))
+            .build()
+            .unwrap();
+
+        let launch_toml = toml::to_string(&launch).unwrap();
+        assert!(!launch_toml.contains("default"));
+        assert!(!launch_toml.contains("working-dir"));
+    }
+
+    #[test]
+    fn rejects_multiple_default_processes() {
+        let result = LaunchBuilder::new()
+            .process(ProcessBuilder::new(process_type!("web"), "bin/server").default(true))
+            .process(ProcessBuilder::new(process_type!("worker"), "bin/worker").default(true))
+            .build();
+
+        assert!(matches!(
+            result,
+            Err(LaunchBuilderError::MultipleDefaultProcesses(types)) if types == vec!["web", "worker"]
+        ));
+    }
+
+    #[test]
+    fn validates_labels() {
+        assert!(LaunchBuilder::new()
+            .label("io.buildpacks.ruby-version", "3.0.1")
+            .build()
+            .is_ok());
+
+        for key in &[
+            "Uppercase",
+            "double..dot",
+            ".leading",
+            "trailing-",
+            "under_score",
+        ] {
+            assert!(
+                matches!(
+                    LaunchBuilder::new().label(*key, "value").build(),
+                    Err(LaunchBuilderError::InvalidLabelKey(_))
+                ),
+                "{}",
+                key
+            );
+        }
+
+        assert!(matches!(
+            LaunchBuilder::new()
+                .label("maintainer", "a")
+                .label("maintainer", "b")
+                .build(),
+            Err(LaunchBuilderError::DuplicateLabel(_))
+        ));
+    }
+
+    #[test]
+    fn validates_slices() {
+        assert!(LaunchBuilder::new()
+            .slice(vec!["public/**/*.js", "./vendor/*"])
+            .build()
+            .is_ok());
+
+        assert!(matches!(
+            LaunchBuilder::new().slice(vec!["public/[a-"]).build(),
+            Err(LaunchBuilderError::InvalidSlicePattern(..))
+        ));
+        assert!(matches!(
+            LaunchBuilder::new().slice(vec!["../secrets/*"]).build(),
+            Err(LaunchBuilderError::SlicePathOutsideAppDir(_))
+        ));
+        assert!(matches!(
+            LaunchBuilder::new().slice(vec!["/etc/*"]).build(),
+            Err(LaunchBuilderError::SlicePathOutsideAppDir(_))
+        ));
+    }
+
+    #[test]
+    fn deserializes_launch_toml() {
+        let launch: Launch = toml::from_str(
+            r#"
+[[processes]]
+type = "web"
+command = "bundle"
+args = ["exec", "puma"]
+direct = false
+default = true
+
+[[labels]]
+key = "maintainer"
+value = "Heroku"
+
+[[slices]]
+paths = ["public/*"]
+
+[[bom]]
+name = "ruby"
+[bom.metadata]
+version = "3.0.1"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(launch.processes[0].r#type.as_str(), "web");
+        assert!(launch.processes[0].default);
+        assert_eq!(launch.labels, vec![Label::new("maintainer", "Heroku")]);
+        assert_eq!(launch.slices, vec![Slice::new(vec!["public/*"])]);
+        assert_eq!(launch.bom, vec![bom::Entry::new("ruby").version("3.0.1")]);
+    }
+
+    #[test]
+    fn rejects_invalid_process_type_when_deserializing() {
+        let result = toml::from_str::<Launch>(
+            r#"
+[[processes]]
+type = "w3b!"
+command = "bundle"
+"#,
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn merges_launch() {
+        let launch = Launch::new()
+            .process(
+                ProcessBuilder::new(process_type!("web"), "rackup")
+                    .default(true)
+                    .build(),
+            )
+            .process(ProcessBuilder::new(process_type!("worker"), "sidekiq").build());
+
+        let mut first = launch;
+        first.labels.push(Label::new("maintainer", "a"));
+        first.bom.push(bom::Entry::new("ruby"));
+
+        let mut second = Launch::new()
+            .process(ProcessBuilder::new(process_type!("web"), "puma").build())
+            .process(
+                ProcessBuilder::new(process_type!("console"), "irb")
+                    .default(true)
+                    .build(),
+            );
+        second.labels.push(Label::new("maintainer", "b"));
+        second.labels.push(Label::new("version", "1"));
+        second.bom.push(bom::Entry::new("ruby"));
+        second.bom.push(bom::Entry::new("bundler"));
+
+        let merged = first.merge(second);
+
+        let processes: Vec<(&str, &str, bool)> = merged
+            .processes
+            .iter()
+            .map(|process| {
+                (
+                    process.r#type.as_str(),
+                    process.command.as_str(),
+                    process.default,
+                )
+            })
+            .collect();
+        assert_eq!(
+            processes,
+            vec![
+                ("web", "puma", false),
+                ("worker", "sidekiq", false),
+                ("console", "irb", true)
+            ]
+        );
+        assert_eq!(
+            merged.labels,
+            vec![Label::new("maintainer", "b"), Label::new("version", "1")]
+        );
+        assert_eq!(
+            merged.bom,
+            vec![bom::Entry::new("ruby"), bom::Entry::new("bundler")]
+        );
+    }
+
+    #[test]
+    fn reads_written_launch_toml() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+        assert_eq!(context.read_launch().unwrap(), None);
+
+        let launch = LaunchBuilder::new()
+            .process(ProcessBuilder::new(process_type!("web"), "bin/server").direct(true))
+            .label("maintainer", "Heroku")
+            .build()
+            .unwrap();
+        context.write_launch(launch.clone()).unwrap();
+
+        assert_eq!(context.read_launch().unwrap(), Some(launch));
+    }
+
+    #[test]
+    fn writes_process_fields_supported_by_api() {
+        let launch = LaunchBuilder::new()
+            .process(
+                ProcessBuilder::new(process_type!("web"), "bin/server")
+                    .default(true)
+                    .working_directory("api"),
+            )
+            .build()
+            .unwrap();
+
+        for (api, default, working_directory) in &[
+            ("0.5", false, None),
+            ("0.6", true, None),
+            ("0.8", true, Some(PathBuf::from("api"))),
+        ] {
+            let launch_for_api = launch.for_api(&BuildpackApi::from_str(api).unwrap());
+            let written: Launch =
+                toml::from_str(&toml::to_string(&launch_for_api).unwrap()).unwrap();
+
+            assert_eq!(&written.processes[0].default, default);
+            assert_eq!(&written.processes[0].working_directory, working_directory);
+        }
+    }
+
+    #[test]
+    fn warns_about_process_fields_unsupported_by_api() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+
+        let launch = LaunchBuilder::new()
+            .process(
+                ProcessBuilder::new(process_type!("web"), "bin/server")
+                    .default(true)
+                    .working_directory("api"),
+            )
+            .build()
+            .unwrap();
+        context.write_launch(launch).unwrap();
+
+        assert_eq!(context.diagnostics.warning_count(), 2);
+        assert!(!context.read_launch().unwrap().unwrap().processes[0].default);
+    }
+
+    #[test]
+    fn process_type_macro_matches_from_str() {
+        assert_eq!(process_type!("web"), ProcessType::from_str("web").unwrap());
+        assert!(!is_valid_process_type("web.server"));
+    }
+}
@@ -0,0 +1,195 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::data::validation;
+
+/// Layer name. This is a newtype wrapper around a String. It MUST only contain numbers, letters,
+/// and the characters ., _, and -. It also cannot be `.`, `..`, `launch`, `build` or `store`,
+/// since these would escape the layers directory or collide with `launch.toml`, `build.toml` and
+/// `store.toml`. Names ending in `.toml` or containing `.sbom.` or `.libcnb-` are rejected as well,
+/// since these would collide with the metadata and SBOM files of other layers or with the work
+/// directories libcnb uses while updating a layer.
+///
+/// Use [`std::str::FromStr`] to create a new instance of this struct, or the
+/// [`layer_name!`](crate::layer_name) macro to validate string literals at compile time.
+///
+/// # Examples
+/// ```
+/// use libcnb::data::layer::LayerName;
+/// use libcnb::layer_name;
+/// use std::str::FromStr;
+///
+/// let valid = LayerName::from_str("ruby-3.0_x");
+/// assert_eq!(valid.unwrap().as_str(), "ruby-3.0_x");
+///
+/// let invalid = LayerName::from_str("../app");
+/// assert!(invalid.is_err());
+///
+/// let literal = layer_name!("bundler");
+/// assert_eq!(literal.as_str(), "bundler");
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(try_from = "String")]
+pub struct LayerName(String);
+
+impl LayerName {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Creates a layer name without validation. Used by the [`layer_name!`](crate::layer_name)
+    /// macro after validating the name at compile time.
+    #[doc(hidden)]
+    pub fn new_unchecked(value: &str) -> Self {
+        LayerName(String::from(value))
+    }
+}
+
+impl FromStr for LayerName {
+    type Err = LayerNameError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if is_valid_layer_name(value) {
+            Ok(LayerName(String::from(value)))
+        } else {
+            Err(LayerNameError::InvalidLayerName(String::from(value)))
+        }
+    }
+}
+
+impl TryFrom<String> for LayerName {
+    type Error = LayerNameError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        LayerName::from_str(&value)
+    }
+}
+
+impl AsRef<str> for LayerName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for LayerName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Validates a layer name, see [`LayerName`].
+#[doc(hidden)]
+pub const fn is_valid_layer_name(value: &str) -> bool {
+    validation::is_alphanumeric_or(value, b"._-")
+        && !validation::is_any_of(value, &[".", "..", "launch", "build", "store"])
+        && !validation::ends_with(value, ".toml")
+        && !validation::contains_str(value, ".sbom.")
+        && !validation::contains_str(value, ".libcnb-")
+}
+
+/// Creates a [`LayerName`] from a string literal, validating it at compile time.
+///
+/// # Examples
+/// ```
+/// use libcnb::layer_name;
+///
+/// let layer_name = layer_name!("ruby");
+/// ```
+///
+/// Invalid names fail to compile:
+/// ```compile_fail
+/// use libcnb::layer_name;
+///
+/// let layer_name = layer_name!("../app");
+/// ```
+#[macro_export]
+macro_rules! layer_name {
+    ($value:expr) => {{
+        const _: () = assert!(
+            $crate::data::layer::is_valid_layer_name($value),
+            "Invalid layer name, see libcnb::data::layer::LayerName for the restrictions"
+        );
+        $crate::data::layer::LayerName::new_unchecked($value)
+    }};
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum LayerNameError {
+    #[error("Found `{0}` but value MUST only contain numbers, letters, and the characters ., _, and -. Value MUST NOT be '.', '..', 'launch', 'build' or 'store', end in '.toml' or contain '.sbom.' or '.libcnb-'.")]
+    InvalidLayerName(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{layer_name, toml_round_trip};
+    use proptest::prelude::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn rejects_names_escaping_the_layers_dir() {
+        for name in &["", ".", "..", "../app", "foo/bar", "/abs", "foo\\bar"] {
+            assert!(LayerName::from_str(name).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn rejects_reserved_names() {
+        for name in &["launch", "build", "store"] {
+            assert!(LayerName::from_str(name).is_err(), "{}", name);
+        }
+
+        assert!(LayerName::from_str("launcher").is_ok());
+        assert!(LayerName::from_str("build-cache").is_ok());
+    }
+
+    #[test]
+    fn rejects_names_colliding_with_layer_files() {
+        for name in &[
+            "ruby.toml",
+            ".toml",
+            "ruby.sbom.cdx.json",
+            "ruby.libcnb-backup",
+            "ruby.libcnb-staging",
+        ] {
+            assert!(LayerName::from_str(name).is_err(), "{}", name);
+        }
+
+        assert!(LayerName::from_str("ruby.tomlx").is_ok());
+        assert!(LayerName::from_str("toml").is_ok());
+        assert!(LayerName::from_str("ruby-libcnb").is_ok());
+        assert!(LayerName::from_str("sbom").is_ok());
+    }
+
+    #[test]
+    fn macro_creates_layer_name() {
+        assert_eq!(layer_name!("ruby"), LayerName::from_str("ruby").unwrap());
+    }
+
+    #[test]
+    fn deserializes_with_validation() {
+        assert!(toml::from_str::<toml::Value>("name = \"ruby\"")
+            .unwrap()
+            .try_into::<HashMap<String, LayerName>>()
+            .is_ok());
+        assert!(toml::from_str::<toml::Value>("name = \"launch\"")
+            .unwrap()
+            .try_into::<HashMap<String, LayerName>>()
+            .is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn layer_name_round_trips(name in layer_name()) {
+            let displayed = name.to_string();
+            prop_assert_eq!(&LayerName::from_str(&displayed).unwrap(), &name);
+
+            let names: HashMap<String, LayerName> =
+                vec![(String::from("name"), name)].into_iter().collect();
+            prop_assert_eq!(toml_round_trip(&names), names);
+        }
+    }
+}
@@ -1,41 +1,67 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, Serializer};
 
+use crate::data::buildpack::BuildpackApi;
 use crate::data::defaults;
-use crate::generic::GenericMetadata;
 
 /// Describes Layer Content Metadata
 ///
+/// Buildpack API 0.6 moved the `launch`, `build` and `cache` flags into a `[types]` table.
+/// Deserialization accepts both shapes, use [`LayerContentMetadata::for_api`] to serialize the
+/// shape of a specific Buildpack API. Serializing this struct directly writes the flags at the
+/// top level.
+///
 /// See [Cloud Native Buildpack specification](https://github.com/buildpacks/spec/blob/main/buildpack.md#layer-content-metadata-toml)
-#[derive(Debug, Deserialize, Serialize)]
+///
+/// # Examples
+/// ```
+/// use libcnb::data::buildpack::BuildpackApi;
+/// use libcnb::data::layer_content_metadata::LayerContentMetadata;
+/// use std::str::FromStr;
+///
+/// let layer_content_metadata = LayerContentMetadata::new(String::from("3.0.1"))
+///     .launch(true)
+///     .cache(true);
+///
+/// let api = BuildpackApi::from_str("0.6").unwrap();
+/// let serialized = toml::to_string(&layer_content_metadata.for_api(&api)).unwrap();
+/// assert!(serialized.contains("[types]"));
+///
+/// let deserialized: LayerContentMetadata<String> = toml::from_str(&serialized).unwrap();
+/// assert!(deserialized.launch);
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(from = "LayerContentMetadataShape<M>")]
 pub struct LayerContentMetadata<M> {
     /// Whether the layer is intended for launch.
-    #[serde(default = "defaults::r#false")]
     pub launch: bool,
 
     /// Whether the layer is intended for build.
-    #[serde(default = "defaults::r#false")]
     pub build: bool,
 
     /// Whether the layer is cached.
-    #[serde(default = "defaults::r#false")]
     pub cache: bool,
 
     /// Metadata that describes the layer contents.
     pub metadata: M,
 }
 
-impl Default for LayerContentMetadata<GenericMetadata> {
+impl<M: Default> Default for LayerContentMetadata<M> {
     fn default() -> Self {
+        LayerContentMetadata::new(M::default())
+    }
+}
+
+impl<M> LayerContentMetadata<M> {
+    /// Creates layer content metadata with the given metadata and all flags set to `false`.
+    pub fn new(metadata: M) -> Self {
         LayerContentMetadata {
             launch: false,
             build: false,
             cache: false,
-            metadata: GenericMetadata::default(),
+            metadata,
         }
     }
-}
 
-impl<M> LayerContentMetadata<M> {
     pub fn launch(mut self, launch: bool) -> Self {
         self.launch = launch;
         self
@@ -59,11 +85,105 @@ impl<M> LayerContentMetadata<M> {
             metadata,
         }
     }
+
+    /// Returns a view that serializes to the shape required by the given Buildpack API.
+    pub fn for_api<'a>(&'a self, api: &BuildpackApi) -> LayerContentMetadataForApi<'a, M> {
+        LayerContentMetadataForApi {
+            layer_content_metadata: self,
+            types_table: *api >= TYPES_TABLE_API,
+        }
+    }
+}
+
+/// The first Buildpack API that uses the `[types]` table.
+const TYPES_TABLE_API: BuildpackApi = BuildpackApi { major: 0, minor: 6 };
+
+/// Serializes [`LayerContentMetadata`] for a specific Buildpack API.
+///
+/// See [`LayerContentMetadata::for_api`].
+pub struct LayerContentMetadataForApi<'a, M> {
+    layer_content_metadata: &'a LayerContentMetadata<M>,
+    types_table: bool,
+}
+
+impl<'a, M: Serialize> Serialize for LayerContentMetadataForApi<'a, M> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let layer_content_metadata = self.layer_content_metadata;
+
+        if self.types_table {
+            TypesTableShape {
+                metadata: &layer_content_metadata.metadata,
+                types: Types {
+                    launch: layer_content_metadata.launch,
+                    build: layer_content_metadata.build,
+                    cache: layer_content_metadata.cache,
+                },
+            }
+            .serialize(serializer)
+        } else {
+            layer_content_metadata.serialize(serializer)
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Types {
+    #[serde(default = "defaults::r#false")]
+    launch: bool,
+    #[serde(default = "defaults::r#false")]
+    build: bool,
+    #[serde(default = "defaults::r#false")]
+    cache: bool,
+}
+
+// `metadata` comes first since TOML requires plain values to be written before any tables.
+#[derive(Serialize)]
+struct TypesTableShape<'a, M> {
+    metadata: &'a M,
+    types: Types,
+}
+
+// Accepts both the top-level flags and the `[types]` table. If a `[types]` table is present, it
+// takes precedence.
+#[derive(Deserialize)]
+struct LayerContentMetadataShape<M> {
+    #[serde(default = "defaults::r#false")]
+    launch: bool,
+    #[serde(default = "defaults::r#false")]
+    build: bool,
+    #[serde(default = "defaults::r#false")]
+    cache: bool,
+    types: Option<Types>,
+    metadata: M,
+}
+
+impl<M> From<LayerContentMetadataShape<M>> for LayerContentMetadata<M> {
+    fn from(shape: LayerContentMetadataShape<M>) -> Self {
+        match shape.types {
+            Some(types) => LayerContentMetadata {
+                launch: types.launch,
+                build: types.build,
+                cache: types.cache,
+                metadata: shape.metadata,
+            },
+            None => LayerContentMetadata {
+                launch: shape.launch,
+                build: shape.build,
+                cache: shape.cache,
+                metadata: shape.metadata,
+            },
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::layer_name;
+    use crate::test_support::build_context;
+    use crate::test_support::toml_table;
+    use proptest::prelude::*;
+    use std::str::FromStr;
 
     #[test]
     fn metadata_is_optional() {
@@ -76,6 +196,93 @@ mod tests {
             "#,
             );
 
-        assert!(!layer.is_err());
+        assert!(layer.is_ok());
+    }
+
+    #[test]
+    fn reads_types_table() {
+        let layer: LayerContentMetadata<Option<toml::value::Table>> = toml::from_str(
+            r#"
+            [types]
+            launch = true
+            cache = true
+            "#,
+        )
+        .unwrap();
+
+        assert!(layer.launch);
+        assert!(!layer.build);
+        assert!(layer.cache);
+    }
+
+    #[test]
+    fn writes_shape_of_buildpack_api() {
+        let layer = LayerContentMetadata::new(String::from("foo")).build(true);
+
+        let legacy: toml::Value =
+            toml::Value::try_from(layer.for_api(&BuildpackApi::from_str("0.5").unwrap())).unwrap();
+        assert_eq!(legacy["build"].as_bool(), Some(true));
+        assert!(legacy.get("types").is_none());
+
+        let current: toml::Value =
+            toml::Value::try_from(layer.for_api(&BuildpackApi::from_str("0.6").unwrap())).unwrap();
+        assert_eq!(current["types"]["build"].as_bool(), Some(true));
+        assert_eq!(current["metadata"].as_str(), Some("foo"));
+        assert!(current.get("build").is_none());
+    }
+
+    #[test]
+    fn default_works_for_any_metadata_with_default() {
+        let layer: LayerContentMetadata<Vec<String>> = LayerContentMetadata::default().launch(true);
+
+        assert!(layer.launch);
+        assert!(layer.metadata.is_empty());
+    }
+
+    #[test]
+    fn build_context_writes_shape_of_declared_buildpack_api() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut context = build_context(&temp_dir);
+        context.buildpack_descriptor.api = BuildpackApi::from_str("0.6").unwrap();
+
+        context
+            .write_layer_content_metadata(
+                &layer_name!("foo"),
+                &LayerContentMetadata::new(1).cache(true),
+            )
+            .unwrap();
+
+        let written =
+            std::fs::read_to_string(context.layer_content_metadata_path(&layer_name!("foo")))
+                .unwrap();
+        assert_eq!(
+            toml::from_str::<toml::Value>(&written).unwrap()["types"]["cache"].as_bool(),
+            Some(true)
+        );
+
+        let read = context
+            .read_layer_content_metadata::<i32>(&layer_name!("foo"))
+            .unwrap()
+            .unwrap();
+        assert!(read.cache);
+        assert_eq!(read.metadata, 1);
+    }
+
+    proptest! {
+        #[test]
+        fn round_trips_for_any_buildpack_api(
+            minor in 0..10u32,
+            (launch, build, cache) in any::<(bool, bool, bool)>(),
+            metadata in toml_table(),
+        ) {
+            let layer = LayerContentMetadata::new(toml::Value::Table(metadata))
+                .launch(launch)
+                .build(build)
+                .cache(cache);
+            let api = BuildpackApi { major: 0, minor };
+
+            let serialized = toml::to_string(&layer.for_api(&api)).unwrap();
+            prop_assert_eq!(toml::from_str::<LayerContentMetadata<_>>(&serialized).unwrap(), layer);
+        }
     }
 }
@@ -0,0 +1,413 @@
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::data::launch::{Launch, ProcessType};
+
+/// Environment variable modifications of a layer.
+///
+/// Modifications are written to the `env`, `env.build` and `env.launch` directories of a layer,
+/// depending on their [`Scope`]. Modifications scoped to a single process type are written to
+/// `env.launch/<process type>`.
+///
+/// See [Cloud Native Buildpack specification](https://github.com/buildpacks/spec/blob/main/buildpack.md#provided-by-the-buildpacks)
+///
+/// # Examples
+/// ```
+/// use libcnb::data::layer_env::{LayerEnv, ModificationBehavior, Scope};
+/// use libcnb::process_type;
+///
+/// let layer_env = LayerEnv::new()
+///     .chainable_insert(Scope::All, ModificationBehavior::Prepend, "PATH", "/layers/ruby/bin:")
+///     .chainable_insert(
+///         Scope::Process(process_type!("worker")),
+///         ModificationBehavior::Override,
+///         "MALLOC_ARENA_MAX",
+///         "2",
+///     );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LayerEnv {
+    pub modifications: Vec<EnvModification>,
+}
+
+/// A single environment variable modification, see [`LayerEnv`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvModification {
+    pub scope: Scope,
+    pub behavior: ModificationBehavior,
+    pub name: OsString,
+    pub value: OsString,
+}
+
+/// Determines when an [`EnvModification`] applies.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Scope {
+    /// Applies during build of subsequent buildpacks and at launch.
+    All,
+    /// Applies during build of subsequent buildpacks only.
+    Build,
+    /// Applies at launch for all processes.
+    Launch,
+    /// Applies at launch for the given process type only.
+    Process(ProcessType),
+}
+
+impl Scope {
+    fn directory(&self) -> PathBuf {
+        match self {
+            Scope::All => PathBuf::from("env"),
+            Scope::Build => PathBuf::from("env.build"),
+            Scope::Launch => PathBuf::from("env.launch"),
+            Scope::Process(process_type) => Path::new("env.launch").join(process_type.as_str()),
+        }
+    }
+}
+
+/// How an [`EnvModification`] is applied to the environment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ModificationBehavior {
+    /// Appends the value, using the delimiter of [`ModificationBehavior::Delimiter`] if set.
+    Append,
+    /// Sets the value if the variable is not set yet.
+    Default,
+    /// Sets the delimiter for [`ModificationBehavior::Append`] and
+    /// [`ModificationBehavior::Prepend`].
+    Delimiter,
+    /// Sets the value, replacing any existing value.
+    Override,
+    /// Prepends the value, using the delimiter of [`ModificationBehavior::Delimiter`] if set.
+    Prepend,
+}
+
+impl ModificationBehavior {
+    fn suffix(self) -> &'static str {
+        match self {
+            ModificationBehavior::Append => "append",
+            ModificationBehavior::Default => "default",
+            ModificationBehavior::Delimiter => "delim",
+            ModificationBehavior::Override => "override",
+            ModificationBehavior::Prepend => "prepend",
+        }
+    }
+
+    fn from_suffix(suffix: &str) -> Option<Self> {
+        match suffix {
+            "append" => Some(ModificationBehavior::Append),
+            "default" => Some(ModificationBehavior::Default),
+            "delim" => Some(ModificationBehavior::Delimiter),
+            "override" => Some(ModificationBehavior::Override),
+            "prepend" => Some(ModificationBehavior::Prepend),
+            _ => None,
+        }
+    }
+}
+
+impl LayerEnv {
+    pub fn new() -> Self {
+        LayerEnv::default()
+    }
+
+    pub fn insert(
+        &mut self,
+        scope: Scope,
+        behavior: ModificationBehavior,
+        name: impl Into<OsString>,
+        value: impl Into<OsString>,
+    ) {
+        self.modifications.push(EnvModification {
+            scope,
+            behavior,
+            name: name.into(),
+            value: value.into(),
+        });
+    }
+
+    pub fn chainable_insert(
+        mut self,
+        scope: Scope,
+        behavior: ModificationBehavior,
+        name: impl Into<OsString>,
+        value: impl Into<OsString>,
+    ) -> Self {
+        self.insert(scope, behavior, name, value);
+        self
+    }
+
+    /// Returns all process types with process-specific modifications that are not declared by
+    /// the given [`Launch`].
+    pub fn unknown_process_types(&self, launch: &Launch) -> Vec<&ProcessType> {
+        let mut unknown: Vec<&ProcessType> = Vec::new();
+
+        for modification in &self.modifications {
+            if let Scope::Process(process_type) = &modification.scope {
+                let declared = launch
+                    .processes
+                    .iter()
+                    .any(|process| &process.r#type == process_type);
+
+                if !declared && !unknown.contains(&process_type) {
+                    unknown.push(process_type);
+                }
+            }
+        }
+
+        unknown
+    }
+
+    /// Writes all modifications to the given layer directory.
+    pub fn write_to_layer_dir(&self, layer_path: impl AsRef<Path>) -> io::Result<()> {
+        for modification in &self.modifications {
+            let directory = layer_path.as_ref().join(modification.scope.directory());
+            fs::create_dir_all(&directory)?;
+
+            let mut file_name = modification.name.clone();
+            file_name.push(".");
+            file_name.push(modification.behavior.suffix());
+
+            fs::write(directory.join(file_name), os_str_bytes(&modification.value))?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads all modifications from the given layer directory.
+    ///
+    /// Files without a known suffix are read as [`ModificationBehavior::Override`]. Directories
+    /// in `env.launch` that are not valid process types are skipped.
+    pub fn read_from_layer_dir(layer_path: impl AsRef<Path>) -> io::Result<Self> {
+        let layer_path = layer_path.as_ref();
+        let mut layer_env = LayerEnv::new();
+
+        for scope in &[Scope::All, Scope::Build, Scope::Launch] {
+            read_scope(layer_path, scope.clone(), &mut layer_env)?;
+        }
+
+        for entry in read_dir_if_exists(&layer_path.join(Scope::Launch.directory()))? {
+            let path = entry?.path();
+            let process_type = path
+                .file_name()
+                .and_then(OsStr::to_str)
+                .and_then(|name| ProcessType::from_str(name).ok());
+
+            if let (true, Some(process_type)) = (path.is_dir(), process_type) {
+                read_scope(layer_path, Scope::Process(process_type), &mut layer_env)?;
+            }
+        }
+
+        Ok(layer_env)
+    }
+}
+
+fn read_scope(layer_path: &Path, scope: Scope, layer_env: &mut LayerEnv) -> io::Result<()> {
+    for entry in read_dir_if_exists(&layer_path.join(scope.directory()))? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            continue;
+        }
+
+        let behavior = path
+            .extension()
+            .and_then(OsStr::to_str)
+            .and_then(ModificationBehavior::from_suffix);
+
+        let name = match (behavior, path.file_stem(), path.file_name()) {
+            (Some(_), Some(stem), _) => stem.to_owned(),
+            (None, _, Some(file_name)) => file_name.to_owned(),
+            _ => continue,
+        };
+
+        layer_env.insert(
+            scope.clone(),
+            behavior.unwrap_or(ModificationBehavior::Override),
+            name,
+            os_string_from_bytes(fs::read(&path)?),
+        );
+    }
+
+    Ok(())
+}
+
+fn read_dir_if_exists(path: &Path) -> io::Result<Vec<io::Result<fs::DirEntry>>> {
+    match fs::read_dir(path) {
+        Ok(entries) => Ok(entries.collect()),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(error) => Err(error),
+    }
+}
+
+#[cfg(unix)]
+fn os_str_bytes(value: &OsStr) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    value.as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+fn os_str_bytes(value: &OsStr) -> Vec<u8> {
+    value.to_string_lossy().into_owned().into_bytes()
+}
+
+#[cfg(unix)]
+fn os_string_from_bytes(bytes: Vec<u8>) -> OsString {
+    use std::os::unix::ffi::OsStringExt;
+    OsString::from_vec(bytes)
+}
+
+#[cfg(not(unix))]
+fn os_string_from_bytes(bytes: Vec<u8>) -> OsString {
+    OsString::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::launch::{LaunchBuilder, ProcessBuilder};
+    use crate::process_type;
+    use crate::test_support::{process_type, text};
+    use proptest::prelude::*;
+
+    fn worker() -> ProcessType {
+        process_type!("worker")
+    }
+
+    #[test]
+    fn writes_scoped_directories() {
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        LayerEnv::new()
+            .chainable_insert(Scope::All, ModificationBehavior::Prepend, "PATH", "/bin:")
+            .chainable_insert(Scope::Build, ModificationBehavior::Default, "CC", "gcc")
+            .chainable_insert(Scope::Launch, ModificationBehavior::Override, "PORT", "80")
+            .chainable_insert(
+                Scope::Process(worker()),
+                ModificationBehavior::Override,
+                "MALLOC_ARENA_MAX",
+                "2",
+            )
+            .write_to_layer_dir(temp_dir.path())
+            .unwrap();
+
+        let read = |path: &str| fs::read_to_string(temp_dir.path().join(path)).unwrap();
+        assert_eq!(read("env/PATH.prepend"), "/bin:");
+        assert_eq!(read("env.build/CC.default"), "gcc");
+        assert_eq!(read("env.launch/PORT.override"), "80");
+        assert_eq!(read("env.launch/worker/MALLOC_ARENA_MAX.override"), "2");
+    }
+
+    #[test]
+    fn reads_written_modifications() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let layer_env = LayerEnv::new()
+            .chainable_insert(Scope::Launch, ModificationBehavior::Delimiter, "PATH", ":")
+            .chainable_insert(
+                Scope::Process(worker()),
+                ModificationBehavior::Append,
+                "PATH",
+                "/worker/bin",
+            );
+
+        layer_env.write_to_layer_dir(temp_dir.path()).unwrap();
+        fs::write(temp_dir.path().join("env.launch").join("FOO"), "bar").unwrap();
+
+        let read = LayerEnv::read_from_layer_dir(temp_dir.path()).unwrap();
+        assert_eq!(read.modifications.len(), 3);
+        for modification in &layer_env.modifications {
+            assert!(read.modifications.contains(modification));
+        }
+        assert!(read.modifications.contains(&EnvModification {
+            scope: Scope::Launch,
+            behavior: ModificationBehavior::Override,
+            name: OsString::from("FOO"),
+            value: OsString::from("bar"),
+        }));
+    }
+
+    #[test]
+    fn reports_unknown_process_types() {
+        let launch = LaunchBuilder::new()
+            .process(ProcessBuilder::new(process_type!("web"), "puma"))
+            .build()
+            .unwrap();
+
+        let layer_env = LayerEnv::new()
+            .chainable_insert(
+                Scope::Process(process_type!("web")),
+                ModificationBehavior::Override,
+                "WEB_CONCURRENCY",
+                "2",
+            )
+            .chainable_insert(
+                Scope::Process(worker()),
+                ModificationBehavior::Override,
+                "MALLOC_ARENA_MAX",
+                "2",
+            )
+            .chainable_insert(
+                Scope::Process(worker()),
+                ModificationBehavior::Override,
+                "RAILS_MAX_THREADS",
+                "5",
+            );
+
+        assert_eq!(layer_env.unknown_process_types(&launch), vec![&worker()]);
+    }
+
+    fn modification() -> impl Strategy<Value = EnvModification> {
+        let scope = prop_oneof![
+            Just(Scope::All),
+            Just(Scope::Build),
+            Just(Scope::Launch),
+            process_type().prop_map(Scope::Process),
+        ];
+
+        let behavior = prop::sample::select(vec![
+            ModificationBehavior::Append,
+            ModificationBehavior::Default,
+            ModificationBehavior::Delimiter,
+            ModificationBehavior::Override,
+            ModificationBehavior::Prepend,
+        ]);
+
+        (scope, behavior, "[A-Z_][A-Z0-9_]{0,8}", text()).prop_map(
+            |(scope, behavior, name, value)| EnvModification {
+                scope,
+                behavior,
+                name: OsString::from(name),
+                value: OsString::from(value),
+            },
+        )
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn layer_env_round_trips(modifications in prop::collection::vec(modification(), 0..8)) {
+            // Later modifications of the same file overwrite earlier ones.
+            let mut layer_env = LayerEnv::new();
+            for modification in modifications.into_iter().rev() {
+                let exists = layer_env.modifications.iter().any(|existing| {
+                    existing.scope == modification.scope
+                        && existing.behavior == modification.behavior
+                        && existing.name == modification.name
+                });
+
+                if !exists {
+                    layer_env.modifications.push(modification);
+                }
+            }
+
+            let temp_dir = tempfile::tempdir().unwrap();
+            layer_env.write_to_layer_dir(temp_dir.path()).unwrap();
+            let read = LayerEnv::read_from_layer_dir(temp_dir.path()).unwrap();
+
+            prop_assert_eq!(read.modifications.len(), layer_env.modifications.len());
+            for modification in &layer_env.modifications {
+                prop_assert!(read.modifications.contains(modification));
+            }
+        }
+    }
+}
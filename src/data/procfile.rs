@@ -0,0 +1,283 @@
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::data::launch::{Launch, Process, ProcessType, ProcessTypeError};
+
+/// A parsed Heroku-style `Procfile`.
+///
+/// Each non-blank line that is not a comment has the form `<process type>: <command>`. Lines
+/// starting with `#` are comments. Process types are validated with [`ProcessType::from_str`].
+///
+/// # Examples
+/// ```
+/// use libcnb::data::procfile::Procfile;
+/// use std::str::FromStr;
+///
+/// let procfile = Procfile::from_str(
+///     "# Rails app\nweb: bundle exec puma -p $PORT\nworker: bundle exec sidekiq\n",
+/// )
+/// .unwrap();
+///
+/// let launch = procfile.launch();
+/// assert_eq!(launch.processes.len(), 2);
+/// assert!(launch.processes[0].default);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Procfile {
+    pub entries: Vec<ProcfileEntry>,
+}
+
+/// A single process declared in a [`Procfile`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcfileEntry {
+    pub process_type: ProcessType,
+    pub command: String,
+    /// The 1-based line number of this entry in the Procfile.
+    pub line: usize,
+}
+
+impl Procfile {
+    /// Reads and parses the Procfile at the given path.
+    pub fn read(path: impl AsRef<Path>) -> Result<Procfile, ProcfileError> {
+        Procfile::from_str(&fs::read_to_string(path)?)
+    }
+
+    /// Converts all entries to launch processes, in the order they are declared.
+    ///
+    /// Commands that only consist of plain words are executed directly, with the first word as
+    /// the command and the remaining words as arguments. Commands containing quotes, variables
+    /// or any other shell syntax are executed via the shell, just like on Heroku.
+    ///
+    /// The default process is determined as follows:
+    ///
+    /// 1. The `web` process, if declared.
+    /// 2. Otherwise, the only process if the Procfile declares exactly one.
+    /// 3. Otherwise, there is no default process.
+    pub fn processes(&self) -> Vec<Process> {
+        let default_process_type = self.default_process_type();
+
+        self.entries
+            .iter()
+            .map(|entry| {
+                let (command, args, direct) = if is_plain_command(&entry.command) {
+                    let mut words = entry.command.split_whitespace().map(String::from);
+                    let command = words.next().unwrap_or_default();
+                    (command, words.collect(), true)
+                } else {
+                    (entry.command.clone(), Vec::new(), false)
+                };
+
+                Process {
+                    r#type: entry.process_type.clone(),
+                    command,
+                    args,
+                    direct,
+                    default: Some(&entry.process_type) == default_process_type,
+                    working_directory: None,
+                }
+            })
+            .collect()
+    }
+
+    /// Converts this Procfile to [`Launch`] data, see [`Procfile::processes`].
+    pub fn launch(&self) -> Launch {
+        Launch {
+            processes: self.processes(),
+            ..Launch::new()
+        }
+    }
+
+    fn default_process_type(&self) -> Option<&ProcessType> {
+        let web = self
+            .entries
+            .iter()
+            .find(|entry| entry.process_type.as_str() == "web");
+
+        match (web, self.entries.as_slice()) {
+            (Some(web), _) => Some(&web.process_type),
+            (None, [only]) => Some(&only.process_type),
+            (None, _) => None,
+        }
+    }
+}
+
+impl FromStr for Procfile {
+    type Err = ProcfileError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut entries: Vec<ProcfileEntry> = Vec::new();
+
+        for (index, line) in value.lines().enumerate() {
+            let line_number = index + 1;
+            let trimmed = line.trim();
+
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+
+            let (process_type, command) = trimmed
+                .split_once(':')
+                .ok_or(ProcfileError::MissingSeparator(line_number))?;
+
+            let process_type = ProcessType::from_str(process_type.trim())
+                .map_err(|error| ProcfileError::InvalidProcessType(line_number, error))?;
+
+            let command = command.trim();
+            if command.is_empty() {
+                return Err(ProcfileError::MissingCommand(line_number));
+            }
+
+            if let Some(existing) = entries
+                .iter()
+                .find(|entry| entry.process_type == process_type)
+            {
+                return Err(ProcfileError::DuplicateProcessType(
+                    line_number,
+                    String::from(process_type.as_str()),
+                    existing.line,
+                ));
+            }
+
+            entries.push(ProcfileEntry {
+                process_type,
+                command: String::from(command),
+                line: line_number,
+            });
+        }
+
+        Ok(Procfile { entries })
+    }
+}
+
+fn is_plain_command(command: &str) -> bool {
+    command.chars().all(|character| {
+        character.is_alphanumeric()
+            || character.is_whitespace()
+            || matches!(
+                character,
+                '-' | '_' | '.' | '/' | ',' | ':' | '+' | '@' | '%'
+            )
+    })
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ProcfileError {
+    #[error("Line {0}: Expected `<process type>: <command>`")]
+    MissingSeparator(usize),
+
+    #[error("Line {0}: {1}")]
+    InvalidProcessType(usize, ProcessTypeError),
+
+    #[error("Line {0}: Missing command")]
+    MissingCommand(usize),
+
+    #[error("Line {0}: Process type `{1}` was already declared on line {2}")]
+    DuplicateProcessType(usize, String, usize),
+
+    #[error("Could not read Procfile: {0}")]
+    IoError(#[from] std::io::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_entries_with_comments_and_blank_lines() {
+        let procfile = Procfile::from_str(
+            "# comment\n\nweb: bundle exec puma\n  # indented comment\nworker:bundle exec sidekiq  \n",
+        )
+        .unwrap();
+
+        let entries: Vec<(&str, &str, usize)> = procfile
+            .entries
+            .iter()
+            .map(|entry| {
+                (
+                    entry.process_type.as_str(),
+                    entry.command.as_str(),
+                    entry.line,
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            entries,
+            vec![
+                ("web", "bundle exec puma", 3),
+                ("worker", "bundle exec sidekiq", 5)
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_colons_in_commands() {
+        let procfile = Procfile::from_str("web: bin/server --bind 0.0.0.0:$PORT").unwrap();
+        assert_eq!(
+            procfile.entries[0].command,
+            "bin/server --bind 0.0.0.0:$PORT"
+        );
+    }
+
+    #[test]
+    fn reports_line_numbers() {
+        assert_eq!(
+            Procfile::from_str("web: puma\n\nno separator")
+                .unwrap_err()
+                .to_string(),
+            "Line 3: Expected `<process type>: <command>`"
+        );
+        assert!(matches!(
+            Procfile::from_str("w3b!: puma"),
+            Err(ProcfileError::InvalidProcessType(1, _))
+        ));
+        assert!(matches!(
+            Procfile::from_str("web:   "),
+            Err(ProcfileError::MissingCommand(1))
+        ));
+        assert!(matches!(
+            Procfile::from_str("web: puma\nweb: rackup"),
+            Err(ProcfileError::DuplicateProcessType(2, _, 1))
+        ));
+    }
+
+    #[test]
+    fn chooses_direct_or_shell_execution() {
+        let processes = Procfile::from_str(
+            "web: bundle exec puma -C config/puma.rb\nworker: bundle exec sidekiq -c $CONCURRENCY",
+        )
+        .unwrap()
+        .processes();
+
+        assert!(processes[0].direct);
+        assert_eq!(processes[0].command, "bundle");
+        assert_eq!(
+            processes[0].args,
+            vec!["exec", "puma", "-C", "config/puma.rb"]
+        );
+
+        assert!(!processes[1].direct);
+        assert_eq!(processes[1].command, "bundle exec sidekiq -c $CONCURRENCY");
+        assert!(processes[1].args.is_empty());
+    }
+
+    #[test]
+    fn picks_default_process() {
+        let defaults = |procfile: &str| -> Vec<bool> {
+            Procfile::from_str(procfile)
+                .unwrap()
+                .processes()
+                .iter()
+                .map(|process| process.default)
+                .collect()
+        };
+
+        assert_eq!(defaults("worker: sidekiq\nweb: puma"), vec![false, true]);
+        assert_eq!(defaults("worker: sidekiq"), vec![true]);
+        assert_eq!(
+            defaults("worker: sidekiq\nclock: clockwork"),
+            vec![false, false]
+        );
+    }
+}
@@ -0,0 +1,688 @@
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::data::bom;
+
+/// A software bill-of-materials file format.
+///
+/// Serializes to and deserializes from the media types used by the `sbom-formats` key in buildpack.toml.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SbomFormat {
+    #[serde(rename = "application/vnd.cyclonedx+json")]
+    CycloneDxJson,
+    #[serde(rename = "application/spdx+json")]
+    SpdxJson,
+    #[serde(rename = "application/vnd.syft+json")]
+    SyftJson,
+}
+
+impl SbomFormat {
+    pub(crate) const ALL: [SbomFormat; 3] = [
+        SbomFormat::CycloneDxJson,
+        SbomFormat::SpdxJson,
+        SbomFormat::SyftJson,
+    ];
+
+    pub fn media_type(&self) -> &'static str {
+        match self {
+            SbomFormat::CycloneDxJson => "application/vnd.cyclonedx+json",
+            SbomFormat::SpdxJson => "application/spdx+json",
+            SbomFormat::SyftJson => "application/vnd.syft+json",
+        }
+    }
+
+    /// The file extension used for SBOM files in this format, i.e. the `<ext>` in
+    /// `<layer>.sbom.<ext>`.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            SbomFormat::CycloneDxJson => "cdx.json",
+            SbomFormat::SpdxJson => "spdx.json",
+            SbomFormat::SyftJson => "syft.json",
+        }
+    }
+}
+
+impl fmt::Display for SbomFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.media_type())
+    }
+}
+
+/// A software bill-of-materials.
+///
+/// Use the `write_*_sbom` functions of [`BuildContext`](crate::build::BuildContext) to write it
+/// for a layer, for launch or for build.
+///
+/// # Examples
+/// ```
+/// use libcnb::data::sbom::{Component, Sbom, SbomFormat};
+///
+/// let sbom = Sbom::new().component(
+///     Component::new("ruby")
+///         .version("3.0.1")
+///         .license("BSD-2-Clause")
+///         .purl("pkg:generic/ruby@3.0.1"),
+/// );
+///
+/// let cyclonedx = sbom.to_json(SbomFormat::CycloneDxJson, "ruby").unwrap();
+/// assert_eq!(cyclonedx["components"][0]["name"], "ruby");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sbom {
+    pub components: Vec<Component>,
+    /// When the SBOM was created. Defaults to the time [`Sbom::new`] was called.
+    pub created: SystemTime,
+}
+
+/// A single component of a [`Sbom`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Component {
+    pub name: String,
+    pub version: Option<String>,
+    pub licenses: Vec<String>,
+    pub uri: Option<String>,
+    pub sha256: Option<String>,
+    pub cpe: Option<String>,
+    pub purl: Option<String>,
+}
+
+impl Sbom {
+    pub fn new() -> Self {
+        Sbom {
+            components: Vec::new(),
+            created: SystemTime::now(),
+        }
+    }
+
+    pub fn component(mut self, component: Component) -> Self {
+        self.components.push(component);
+        self
+    }
+
+    /// Serializes this SBOM in the given format.
+    ///
+    /// `name` is used as the document name for formats that require one.
+    pub fn to_json(&self, format: SbomFormat, name: &str) -> Result<Value, SbomError> {
+        match format {
+            SbomFormat::CycloneDxJson => Ok(self.to_cyclonedx_json()),
+            SbomFormat::SpdxJson => Ok(self.to_spdx_json(name)),
+            SbomFormat::SyftJson => Ok(self.to_syft_json(name)),
+        }
+    }
+
+    /// Reads an SBOM in the given format, i.e. one written by [`Sbom::to_json`].
+    ///
+    /// The creation time is not read, `created` is set to the time of reading. Syft documents
+    /// carry no component URIs or SHA-256 checksums, these are empty after reading a Syft
+    /// document.
+    ///
+    /// # Examples
+    /// ```
+    /// use libcnb::data::sbom::{Component, Sbom, SbomFormat};
+    ///
+    /// let sbom = Sbom::new().component(Component::new("ruby").version("3.0.1"));
+    /// let spdx = sbom.to_json(SbomFormat::SpdxJson, "ruby").unwrap();
+    ///
+    /// let read = Sbom::from_json(SbomFormat::SpdxJson, &spdx).unwrap();
+    /// assert_eq!(read.components, sbom.components);
+    /// ```
+    pub fn from_json(format: SbomFormat, value: &Value) -> Result<Self, SbomError> {
+        match format {
+            SbomFormat::CycloneDxJson => Ok(Sbom {
+                components: read_components(format, &value["components"], |name, component| {
+                    Component {
+                        name,
+                        version: optional_str(&component["version"]),
+                        licenses: component["licenses"]
+                            .as_array()
+                            .map(|licenses| {
+                                licenses
+                                    .iter()
+                                    .filter_map(|license| {
+                                        optional_str(&license["license"]["name"])
+                                            .or_else(|| optional_str(&license["license"]["id"]))
+                                    })
+                                    .collect()
+                            })
+                            .unwrap_or_default(),
+                        uri: find_str(
+                            &component["externalReferences"],
+                            "type",
+                            "distribution",
+                            "url",
+                        ),
+                        sha256: find_str(&component["hashes"], "alg", "SHA-256", "content"),
+                        cpe: optional_str(&component["cpe"]),
+                        purl: optional_str(&component["purl"]),
+                    }
+                })?,
+                created: SystemTime::now(),
+            }),
+            SbomFormat::SpdxJson => Ok(Sbom {
+                components: read_components(format, &value["packages"], |name, package| {
+                    Component {
+                        name,
+                        version: optional_str(&package["versionInfo"]),
+                        licenses: optional_str(&package["licenseDeclared"])
+                            .filter(|license| license != "NOASSERTION")
+                            .map(|licenses| licenses.split(" AND ").map(String::from).collect())
+                            .unwrap_or_default(),
+                        uri: optional_str(&package["downloadLocation"])
+                            .filter(|uri| uri != "NOASSERTION"),
+                        sha256: find_str(
+                            &package["checksums"],
+                            "algorithm",
+                            "SHA256",
+                            "checksumValue",
+                        ),
+                        cpe: find_str(
+                            &package["externalRefs"],
+                            "referenceType",
+                            "cpe23Type",
+                            "referenceLocator",
+                        ),
+                        purl: find_str(
+                            &package["externalRefs"],
+                            "referenceType",
+                            "purl",
+                            "referenceLocator",
+                        ),
+                    }
+                })?,
+                created: SystemTime::now(),
+            }),
+            SbomFormat::SyftJson => Ok(Sbom {
+                components: read_components(format, &value["artifacts"], |name, artifact| {
+                    Component {
+                        name,
+                        version: optional_str(&artifact["version"])
+                            .filter(|version| !version.is_empty()),
+                        licenses: artifact["licenses"]
+                            .as_array()
+                            .map(|licenses| licenses.iter().filter_map(optional_str).collect())
+                            .unwrap_or_default(),
+                        uri: None,
+                        sha256: None,
+                        cpe: optional_str(&artifact["cpes"][0]),
+                        purl: optional_str(&artifact["purl"]).filter(|purl| !purl.is_empty()),
+                    }
+                })?,
+                created: SystemTime::now(),
+            }),
+        }
+    }
+
+    fn to_cyclonedx_json(&self) -> Value {
+        let components: Vec<Value> = self
+            .components
+            .iter()
+            .map(|component| {
+                let mut value = json!({
+                    "type": "library",
+                    "name": component.name,
+                });
+
+                insert_optional(&mut value, "version", &component.version);
+                insert_optional(&mut value, "purl", &component.purl);
+                insert_optional(&mut value, "cpe", &component.cpe);
+
+                if !component.licenses.is_empty() {
+                    value["licenses"] = component
+                        .licenses
+                        .iter()
+                        .map(|license| json!({ "license": { "name": license } }))
+                        .collect();
+                }
+
+                if let Some(sha256) = &component.sha256 {
+                    value["hashes"] = json!([{ "alg": "SHA-256", "content": sha256 }]);
+                }
+
+                if let Some(uri) = &component.uri {
+                    value["externalReferences"] = json!([{ "type": "distribution", "url": uri }]);
+                }
+
+                value
+            })
+            .collect();
+
+        json!({
+            "bomFormat": "CycloneDX",
+            "specVersion": "1.3",
+            "version": 1,
+            "metadata": { "timestamp": format_timestamp(self.created) },
+            "components": components,
+        })
+    }
+
+    fn to_spdx_json(&self, name: &str) -> Value {
+        let packages: Vec<Value> = self
+            .components
+            .iter()
+            .enumerate()
+            .map(|(index, component)| {
+                let mut value = json!({
+                    "name": component.name,
+                    "SPDXID": format!("SPDXRef-Package-{}", index),
+                    "downloadLocation": component.uri.as_deref().unwrap_or("NOASSERTION"),
+                    "filesAnalyzed": false,
+                    "licenseConcluded": "NOASSERTION",
+                    "licenseDeclared": if component.licenses.is_empty() {
+                        String::from("NOASSERTION")
+                    } else {
+                        component.licenses.join(" AND ")
+                    },
+                    "copyrightText": "NOASSERTION",
+                });
+
+                insert_optional(&mut value, "versionInfo", &component.version);
+
+                if let Some(sha256) = &component.sha256 {
+                    value["checksums"] =
+                        json!([{ "algorithm": "SHA256", "checksumValue": sha256 }]);
+                }
+
+                let mut external_refs = vec![];
+                if let Some(purl) = &component.purl {
+                    external_refs.push(json!({
+                        "referenceCategory": "PACKAGE_MANAGER",
+                        "referenceType": "purl",
+                        "referenceLocator": purl,
+                    }));
+                }
+                if let Some(cpe) = &component.cpe {
+                    external_refs.push(json!({
+                        "referenceCategory": "SECURITY",
+                        "referenceType": "cpe23Type",
+                        "referenceLocator": cpe,
+                    }));
+                }
+                if !external_refs.is_empty() {
+                    value["externalRefs"] = Value::from(external_refs);
+                }
+
+                value
+            })
+            .collect();
+
+        let created = format_timestamp(self.created);
+
+        // SPDX requires a unique namespace per document, derive it from the document contents.
+        let mut hasher = Sha256::new();
+        hasher.update(name.as_bytes());
+        hasher.update(created.as_bytes());
+        hasher.update(Value::from(packages.clone()).to_string().as_bytes());
+
+        json!({
+            "spdxVersion": "SPDX-2.2",
+            "dataLicense": "CC0-1.0",
+            "SPDXID": "SPDXRef-DOCUMENT",
+            "name": name,
+            "documentNamespace": format!("https://buildpacks.io/spdx/{}-{:x}", name, hasher.finalize()),
+            "creationInfo": {
+                "created": created,
+                "creators": [format!("Tool: libcnb-{}", env!("CARGO_PKG_VERSION"))],
+            },
+            "packages": packages,
+        })
+    }
+
+    fn to_syft_json(&self, name: &str) -> Value {
+        let artifacts: Vec<Value> = self
+            .components
+            .iter()
+            .enumerate()
+            .map(|(index, component)| {
+                // Syft requires a unique artifact id, derive it from the package data.
+                let mut hasher = Sha256::new();
+                hasher.update(index.to_le_bytes());
+                hasher.update(component.name.as_bytes());
+                hasher.update(component.version.as_deref().unwrap_or_default().as_bytes());
+
+                json!({
+                    "id": format!("{:x}", hasher.finalize())[..16],
+                    "name": component.name,
+                    "version": component.version.as_deref().unwrap_or_default(),
+                    "type": "UnknownPackage",
+                    "foundBy": format!("libcnb-{}", env!("CARGO_PKG_VERSION")),
+                    "locations": [],
+                    "licenses": component.licenses,
+                    "language": "",
+                    "cpes": component.cpe.iter().collect::<Vec<_>>(),
+                    "purl": component.purl.as_deref().unwrap_or_default(),
+                })
+            })
+            .collect();
+
+        json!({
+            "artifacts": artifacts,
+            "artifactRelationships": [],
+            "source": { "type": "directory", "target": name },
+            "distro": {},
+            "descriptor": { "name": "libcnb", "version": env!("CARGO_PKG_VERSION") },
+            "schema": {
+                "version": "1.1.0",
+                "url": "https://raw.githubusercontent.com/anchore/syft/main/schema/json/schema-1.1.0.json",
+            },
+        })
+    }
+}
+
+impl Default for Sbom {
+    fn default() -> Self {
+        Sbom::new()
+    }
+}
+
+impl Component {
+    pub fn new(name: impl Into<String>) -> Self {
+        Component {
+            name: name.into(),
+            ..Component::default()
+        }
+    }
+
+    pub fn version(mut self, version: impl Into<String>) -> Self {
+        self.version = Some(version.into());
+        self
+    }
+
+    pub fn license(mut self, license: impl Into<String>) -> Self {
+        self.licenses.push(license.into());
+        self
+    }
+
+    pub fn uri(mut self, uri: impl Into<String>) -> Self {
+        self.uri = Some(uri.into());
+        self
+    }
+
+    pub fn sha256(mut self, sha256: impl Into<String>) -> Self {
+        self.sha256 = Some(sha256.into());
+        self
+    }
+
+    pub fn cpe(mut self, cpe: impl Into<String>) -> Self {
+        self.cpe = Some(cpe.into());
+        self
+    }
+
+    pub fn purl(mut self, purl: impl Into<String>) -> Self {
+        self.purl = Some(purl.into());
+        self
+    }
+}
+
+impl From<&bom::Entry> for Component {
+    fn from(entry: &bom::Entry) -> Self {
+        Component {
+            name: entry.name.clone(),
+            version: entry.metadata.version.clone(),
+            licenses: entry.metadata.licenses.clone(),
+            uri: entry.metadata.uri.clone(),
+            sha256: entry.metadata.sha256.clone(),
+            cpe: entry.metadata.cpe.clone(),
+            purl: entry.metadata.purl.clone(),
+        }
+    }
+}
+
+/// Reads all components of `values`, which must be an array of objects with a `name`.
+fn read_components(
+    format: SbomFormat,
+    values: &Value,
+    read_component: impl Fn(String, &Value) -> Component,
+) -> Result<Vec<Component>, SbomError> {
+    values
+        .as_array()
+        .ok_or_else(|| SbomError::InvalidDocument(format, String::from("Missing component list")))?
+        .iter()
+        .map(|value| {
+            optional_str(&value["name"])
+                .map(|name| read_component(name, value))
+                .ok_or_else(|| {
+                    SbomError::InvalidDocument(format, String::from("Component without a name"))
+                })
+        })
+        .collect()
+}
+
+/// Returns `field` of the first object in the array `values` whose `key` equals `expected`.
+fn find_str(values: &Value, key: &str, expected: &str, field: &str) -> Option<String> {
+    values
+        .as_array()?
+        .iter()
+        .find(|value| value[key] == expected)
+        .and_then(|value| optional_str(&value[field]))
+}
+
+fn optional_str(value: &Value) -> Option<String> {
+    value.as_str().map(String::from)
+}
+
+fn insert_optional(value: &mut Value, key: &str, optional: &Option<String>) {
+    if let Some(optional) = optional {
+        value[key] = Value::from(optional.as_str());
+    }
+}
+
+/// Formats the given time as an RFC 3339 UTC timestamp with second precision.
+fn format_timestamp(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+
+    let (year, month, day) = civil_from_days((seconds / 86400) as i64);
+    let seconds_of_day = seconds % 86400;
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day % 3600 / 60,
+        seconds_of_day % 60
+    )
+}
+
+// Converts days since the unix epoch to a (year, month, day) date in the proleptic Gregorian
+// calendar. See http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i64) -> (i64, u64, u64) {
+    let days = days + 719_468;
+    let era = if days >= 0 { days } else { days - 146_096 } / 146_097;
+    let day_of_era = (days - era * 146_097) as u64;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era as i64 + era * 400;
+
+    (if month <= 2 { year + 1 } else { year }, month, day)
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum SbomError {
+    #[error("SBOM format `{0}` is not declared in the sbom-formats of buildpack.toml")]
+    UndeclaredFormat(SbomFormat),
+
+    #[error("Invalid {0} SBOM: {1}")]
+    InvalidDocument(SbomFormat, String),
+
+    #[error("Could not serialize SBOM: {0}")]
+    SerializationError(#[from] serde_json::Error),
+
+    #[error("Could not write SBOM file: {0}")]
+    IoError(#[from] std::io::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer_name;
+    use crate::test_support::build_context;
+    use std::time::Duration;
+
+    fn sbom() -> Sbom {
+        Sbom {
+            created: UNIX_EPOCH + Duration::from_secs(1_625_097_600),
+            ..Sbom::new()
+        }
+        .component(
+            Component::new("ruby")
+                .version("3.0.1")
+                .license("BSD-2-Clause")
+                .license("Ruby")
+                .sha256("abc")
+                .purl("pkg:generic/ruby@3.0.1"),
+        )
+        .component(Component::new("bundler"))
+    }
+
+    #[test]
+    fn serializes_cyclonedx() {
+        let value = sbom().to_json(SbomFormat::CycloneDxJson, "ruby").unwrap();
+
+        assert_eq!(value["bomFormat"], "CycloneDX");
+        assert_eq!(value["metadata"]["timestamp"], "2021-07-01T00:00:00Z");
+        assert_eq!(
+            value["components"][0],
+            json!({
+                "type": "library",
+                "name": "ruby",
+                "version": "3.0.1",
+                "purl": "pkg:generic/ruby@3.0.1",
+                "licenses": [
+                    { "license": { "name": "BSD-2-Clause" } },
+                    { "license": { "name": "Ruby" } }
+                ],
+                "hashes": [{ "alg": "SHA-256", "content": "abc" }]
+            })
+        );
+        assert_eq!(
+            value["components"][1],
+            json!({ "type": "library", "name": "bundler" })
+        );
+    }
+
+    #[test]
+    fn serializes_spdx() {
+        let value = sbom().to_json(SbomFormat::SpdxJson, "ruby").unwrap();
+
+        assert_eq!(value["spdxVersion"], "SPDX-2.2");
+        assert_eq!(value["name"], "ruby");
+        assert_eq!(value["creationInfo"]["created"], "2021-07-01T00:00:00Z");
+        assert_eq!(
+            value["packages"][0]["licenseDeclared"],
+            "BSD-2-Clause AND Ruby"
+        );
+        assert_eq!(
+            value["packages"][0]["externalRefs"][0]["referenceLocator"],
+            "pkg:generic/ruby@3.0.1"
+        );
+        assert_eq!(value["packages"][1]["SPDXID"], "SPDXRef-Package-1");
+        assert_eq!(value["packages"][1]["downloadLocation"], "NOASSERTION");
+    }
+
+    #[test]
+    fn serializes_syft() {
+        let value = sbom().to_json(SbomFormat::SyftJson, "ruby").unwrap();
+
+        assert_eq!(
+            value["source"],
+            json!({ "type": "directory", "target": "ruby" })
+        );
+        assert_eq!(value["schema"]["version"], "1.1.0");
+        assert_eq!(value["artifacts"][0]["name"], "ruby");
+        assert_eq!(value["artifacts"][0]["version"], "3.0.1");
+        assert_eq!(
+            value["artifacts"][0]["licenses"],
+            json!(["BSD-2-Clause", "Ruby"])
+        );
+        assert_eq!(value["artifacts"][0]["purl"], "pkg:generic/ruby@3.0.1");
+        assert_eq!(value["artifacts"][1]["version"], "");
+        assert_ne!(value["artifacts"][0]["id"], value["artifacts"][1]["id"]);
+    }
+
+    #[test]
+    fn formats_timestamps() {
+        assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        assert_eq!(
+            format_timestamp(UNIX_EPOCH + Duration::from_secs(951_825_599)),
+            "2000-02-29T11:59:59Z"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_documents() {
+        assert!(matches!(
+            Sbom::from_json(
+                SbomFormat::CycloneDxJson,
+                &json!({ "bomFormat": "CycloneDX" })
+            ),
+            Err(SbomError::InvalidDocument(SbomFormat::CycloneDxJson, _))
+        ));
+        assert!(matches!(
+            Sbom::from_json(
+                SbomFormat::SyftJson,
+                &json!({ "artifacts": [{ "version": "1" }] })
+            ),
+            Err(SbomError::InvalidDocument(SbomFormat::SyftJson, _))
+        ));
+    }
+
+    #[test]
+    fn writes_sbom_files_for_declared_formats() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut context = build_context(&temp_dir);
+        context.buildpack_descriptor.buildpack.sbom_formats = vec![SbomFormat::CycloneDxJson];
+
+        context
+            .write_layer_sbom(&layer_name!("ruby"), &sbom(), SbomFormat::CycloneDxJson)
+            .unwrap();
+        context
+            .write_launch_sbom(&sbom(), SbomFormat::CycloneDxJson)
+            .unwrap();
+
+        let written: Value = serde_json::from_slice(
+            &std::fs::read(context.layers_dir.join("ruby.sbom.cdx.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(written["components"][0]["name"], "ruby");
+        assert!(context.layers_dir.join("launch.sbom.cdx.json").exists());
+
+        assert!(matches!(
+            context.write_build_sbom(&sbom(), SbomFormat::SpdxJson),
+            Err(SbomError::UndeclaredFormat(SbomFormat::SpdxJson))
+        ));
+        assert!(!context.layers_dir.join("build.sbom.spdx.json").exists());
+    }
+
+    #[test]
+    fn deserializes_sbom_formats_from_media_types() {
+        let formats: Vec<SbomFormat> = serde_json::from_str(
+            r#"["application/vnd.cyclonedx+json", "application/spdx+json", "application/vnd.syft+json"]"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            formats,
+            vec![
+                SbomFormat::CycloneDxJson,
+                SbomFormat::SpdxJson,
+                SbomFormat::SyftJson
+            ]
+        );
+    }
+}
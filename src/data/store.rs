@@ -1,7 +1,8 @@
 use serde::{Deserialize, Serialize};
 use toml::value::Table;
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct Store {
+    #[serde(serialize_with = "toml::ser::tables_last")]
     pub metadata: Table,
 }
@@ -0,0 +1,124 @@
+//! `const fn` helpers to validate newtype values such as [`LayerName`](crate::data::layer::LayerName).
+//!
+//! These are used by `FromStr` implementations at runtime and by macros such as
+//! [`layer_name!`](crate::layer_name) at compile time, which ensures both accept exactly the same
+//! values.
+
+/// Returns whether `value` is not empty and only contains ASCII letters, ASCII numbers and the
+/// given special characters.
+pub const fn is_alphanumeric_or(value: &str, special_characters: &[u8]) -> bool {
+    let bytes = value.as_bytes();
+    if bytes.is_empty() {
+        return false;
+    }
+
+    let mut index = 0;
+    while index < bytes.len() {
+        if !(bytes[index].is_ascii_alphanumeric() || contains(special_characters, bytes[index])) {
+            return false;
+        }
+        index += 1;
+    }
+
+    true
+}
+
+/// Returns whether `value` equals any of the given values.
+pub const fn is_any_of(value: &str, values: &[&str]) -> bool {
+    let mut index = 0;
+    while index < values.len() {
+        if str_eq(value, values[index]) {
+            return true;
+        }
+        index += 1;
+    }
+
+    false
+}
+
+/// Returns whether `value` ends with `suffix`.
+pub const fn ends_with(value: &str, suffix: &str) -> bool {
+    let value = value.as_bytes();
+    let suffix = suffix.as_bytes();
+
+    value.len() >= suffix.len() && bytes_eq_at(value, value.len() - suffix.len(), suffix)
+}
+
+/// Returns whether `value` contains `needle`.
+pub const fn contains_str(value: &str, needle: &str) -> bool {
+    let value = value.as_bytes();
+    let needle = needle.as_bytes();
+
+    let mut offset = 0;
+    while offset + needle.len() <= value.len() {
+        if bytes_eq_at(value, offset, needle) {
+            return true;
+        }
+        offset += 1;
+    }
+
+    false
+}
+
+const fn contains(bytes: &[u8], byte: u8) -> bool {
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == byte {
+            return true;
+        }
+        index += 1;
+    }
+
+    false
+}
+
+const fn str_eq(left: &str, right: &str) -> bool {
+    left.len() == right.len() && bytes_eq_at(left.as_bytes(), 0, right.as_bytes())
+}
+
+/// Returns whether `bytes` contains `expected` starting at `offset`. The caller ensures that
+/// `expected` fits into `bytes` at that offset.
+const fn bytes_eq_at(bytes: &[u8], offset: usize, expected: &[u8]) -> bool {
+    let mut index = 0;
+    while index < expected.len() {
+        if bytes[offset + index] != expected[index] {
+            return false;
+        }
+        index += 1;
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_characters() {
+        assert!(is_alphanumeric_or("foo-Bar_9", b"-_"));
+        assert!(!is_alphanumeric_or("foo/bar", b"-_"));
+        assert!(!is_alphanumeric_or("", b"-_"));
+        assert!(!is_alphanumeric_or("föö", b"-_"));
+    }
+
+    #[test]
+    fn checks_values() {
+        assert!(is_any_of("app", &["app", "config"]));
+        assert!(!is_any_of("apps", &["app", "config"]));
+        assert!(!is_any_of("", &["app", "config"]));
+    }
+
+    #[test]
+    fn checks_substrings() {
+        assert!(ends_with("ruby.toml", ".toml"));
+        assert!(ends_with(".toml", ".toml"));
+        assert!(!ends_with("ruby.tomlx", ".toml"));
+        assert!(!ends_with("toml", ".toml"));
+
+        assert!(contains_str("ruby.libcnb-backup", ".libcnb-"));
+        assert!(contains_str("abc", ""));
+        assert!(!contains_str("ruby-libcnb", ".libcnb-"));
+        assert!(!contains_str("", "a"));
+    }
+}
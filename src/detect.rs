@@ -1,8 +1,11 @@
-use std::fmt::Debug;
 use std::path::PathBuf;
 
 use crate::{data::build_plan::BuildPlan, data::buildpack::BuildpackToml, platform::Platform};
 
+pub use rules::*;
+
+mod rules;
+
 /// Context for a buildpack's detect phase execution.
 pub struct DetectContext<P: Platform, BM> {
     pub app_dir: PathBuf,
@@ -12,9 +15,95 @@ pub struct DetectContext<P: Platform, BM> {
     pub buildpack_descriptor: BuildpackToml<BM>,
 }
 
+impl<P: Platform, BM> DetectContext<P, BM> {
+    /// Evaluates the given rule against the app directory and the platform environment.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use libcnb::data::build_plan::BuildPlan;
+    /// use libcnb::{DetectOutcome, DetectRule, GenericDetectContext, Result};
+    ///
+    /// fn detect(context: GenericDetectContext) -> Result<DetectOutcome, std::io::Error> {
+    ///     let rule = DetectRule::file_exists("Gemfile.lock")
+    ///         .or(DetectRule::any_glob(&["*.gemspec"]));
+    ///
+    ///     Ok(context.detect(&rule).into_detect_outcome(BuildPlan::new()))
+    /// }
+    /// ```
+    pub fn detect(&self, rule: &DetectRule) -> RuleOutcome {
+        rule.evaluate(&self.app_dir, self.platform.env())
+    }
+}
+
 /// Describes the outcome of the buildpack's detect phase.
+///
+/// # Examples
+/// ```
+/// use libcnb::data::build_plan::BuildPlan;
+/// use libcnb::DetectOutcome;
+/// use std::path::PathBuf;
+///
+/// let pass = DetectOutcome::PassWithTriggers(BuildPlan::new(), vec![PathBuf::from("Gemfile.lock")]);
+/// let fail = DetectOutcome::Fail(Some(String::from("No Gemfile.lock found")));
+/// let error = DetectOutcome::Error(String::from("Gemfile.lock is not readable"));
+/// ```
 #[derive(Debug)]
 pub enum DetectOutcome {
+    /// The buildpack applies to the app.
     Pass(BuildPlan),
-    Fail,
+
+    /// The buildpack applies to the app because of the given files. The runtime logs the files,
+    /// relative to the app directory if possible.
+    PassWithTriggers(BuildPlan, Vec<PathBuf>),
+
+    /// The buildpack does not apply to the app. The runtime logs the reason, if given.
+    Fail(Option<String>),
+
+    /// Detection could not be completed, for example because of an unreadable file. Unlike
+    /// [`DetectOutcome::Fail`], this is reported as an error to the lifecycle.
+    Error(String),
+}
+
+/// The exit code for a passed detection.
+pub const DETECT_PASS_EXIT_CODE: i32 = 0;
+
+/// The exit code for a failed detection, i.e. the buildpack does not apply.
+pub const DETECT_FAIL_EXIT_CODE: i32 = 100;
+
+/// The exit code for [`DetectOutcome::Error`].
+pub const DETECT_ERROR_EXIT_CODE: i32 = 1;
+
+impl DetectOutcome {
+    /// The exit code of the detect executable for this outcome.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            DetectOutcome::Pass(_) | DetectOutcome::PassWithTriggers(_, _) => DETECT_PASS_EXIT_CODE,
+            DetectOutcome::Fail(_) => DETECT_FAIL_EXIT_CODE,
+            DetectOutcome::Error(_) => DETECT_ERROR_EXIT_CODE,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_outcomes_to_exit_codes() {
+        assert_eq!(DetectOutcome::Pass(BuildPlan::new()).exit_code(), 0);
+        assert_eq!(
+            DetectOutcome::PassWithTriggers(BuildPlan::new(), vec![PathBuf::from("Gemfile")])
+                .exit_code(),
+            0
+        );
+        assert_eq!(DetectOutcome::Fail(None).exit_code(), 100);
+        assert_eq!(
+            DetectOutcome::Fail(Some(String::from("No Gemfile"))).exit_code(),
+            100
+        );
+        assert_ne!(
+            DetectOutcome::Error(String::from("Unreadable Gemfile")).exit_code(),
+            100
+        );
+    }
 }
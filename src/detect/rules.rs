@@ -0,0 +1,576 @@
+use std::fs;
+use std::io;
+use std::ops::Not;
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+
+use crate::data::build_plan::BuildPlan;
+use crate::detect::DetectOutcome;
+use crate::platform::PlatformEnv;
+
+/// A composable rule for the detect phase.
+///
+/// Rules are evaluated against an app directory and the platform environment, either directly via
+/// [`DetectRule::evaluate`] or via [`DetectContext::detect`](crate::detect::DetectContext::detect).
+/// All paths and glob patterns are relative to the app directory.
+///
+/// # Examples
+/// ```
+/// use libcnb::DetectRule;
+///
+/// let rule = DetectRule::file_exists("Gemfile.lock")
+///     .or(DetectRule::any_glob(&["*.gemspec"]))
+///     .and(!DetectRule::env_flag("BP_DISABLE_RUBY"));
+/// ```
+#[derive(Debug, Clone)]
+pub enum DetectRule {
+    /// The file exists in the app directory.
+    FileExists(PathBuf),
+
+    /// At least one file matches at least one of the glob patterns.
+    AnyGlob(Vec<String>),
+
+    /// Each of the glob patterns matches at least one file.
+    AllGlobs(Vec<String>),
+
+    /// The contents of the file match the regular expression.
+    FileMatches(PathBuf, Regex),
+
+    /// The JSON file contains the dotted key, i.e. `engines.node`.
+    JsonKey(PathBuf, String),
+
+    /// The TOML file contains the dotted key, i.e. `package.name`.
+    TomlKey(PathBuf, String),
+
+    /// The platform environment variable is set to a value other than an empty string, `0` or
+    /// `false`.
+    EnvFlag(String),
+
+    /// All of the rules pass.
+    All(Vec<DetectRule>),
+
+    /// At least one of the rules passes.
+    Any(Vec<DetectRule>),
+
+    /// The rule fails.
+    Not(Box<DetectRule>),
+}
+
+impl DetectRule {
+    pub fn file_exists(path: impl Into<PathBuf>) -> Self {
+        DetectRule::FileExists(path.into())
+    }
+
+    pub fn any_glob(patterns: &[impl AsRef<str>]) -> Self {
+        DetectRule::AnyGlob(patterns.iter().map(|p| String::from(p.as_ref())).collect())
+    }
+
+    pub fn all_globs(patterns: &[impl AsRef<str>]) -> Self {
+        DetectRule::AllGlobs(patterns.iter().map(|p| String::from(p.as_ref())).collect())
+    }
+
+    pub fn file_matches(path: impl Into<PathBuf>, regex: Regex) -> Self {
+        DetectRule::FileMatches(path.into(), regex)
+    }
+
+    pub fn json_key(path: impl Into<PathBuf>, key: impl Into<String>) -> Self {
+        DetectRule::JsonKey(path.into(), key.into())
+    }
+
+    pub fn toml_key(path: impl Into<PathBuf>, key: impl Into<String>) -> Self {
+        DetectRule::TomlKey(path.into(), key.into())
+    }
+
+    pub fn env_flag(name: impl Into<String>) -> Self {
+        DetectRule::EnvFlag(name.into())
+    }
+
+    pub fn all(rules: Vec<DetectRule>) -> Self {
+        DetectRule::All(rules)
+    }
+
+    pub fn any(rules: Vec<DetectRule>) -> Self {
+        DetectRule::Any(rules)
+    }
+
+    /// Combines this rule with another rule that must pass as well.
+    pub fn and(self, other: DetectRule) -> Self {
+        match self {
+            DetectRule::All(mut rules) => {
+                rules.push(other);
+                DetectRule::All(rules)
+            }
+            rule => DetectRule::All(vec![rule, other]),
+        }
+    }
+
+    /// Combines this rule with an alternative rule.
+    pub fn or(self, other: DetectRule) -> Self {
+        match self {
+            DetectRule::Any(mut rules) => {
+                rules.push(other);
+                DetectRule::Any(rules)
+            }
+            rule => DetectRule::Any(vec![rule, other]),
+        }
+    }
+
+    /// Evaluates this rule against the given app directory and platform environment.
+    pub fn evaluate(&self, app_dir: impl AsRef<Path>, env: &PlatformEnv) -> RuleOutcome {
+        let app_dir = app_dir.as_ref();
+
+        match self {
+            DetectRule::FileExists(path) => {
+                if app_dir.join(path).exists() {
+                    RuleOutcome::pass(
+                        format!("Found {}", path.display()),
+                        vec![app_dir.join(path)],
+                    )
+                } else {
+                    RuleOutcome::fail(format!("{} does not exist", path.display()))
+                }
+            }
+            DetectRule::AnyGlob(patterns) => {
+                let mut triggers = Vec::new();
+                for pattern in patterns {
+                    match glob_in(app_dir, pattern) {
+                        Ok(mut paths) => triggers.append(&mut paths),
+                        Err(outcome) => return outcome,
+                    }
+                }
+
+                if triggers.is_empty() {
+                    RuleOutcome::fail(format!("No files match {}", patterns.join(", ")))
+                } else {
+                    RuleOutcome::pass(
+                        format!("Found files matching {}", patterns.join(", ")),
+                        triggers,
+                    )
+                }
+            }
+            DetectRule::AllGlobs(patterns) => {
+                let mut triggers = Vec::new();
+                for pattern in patterns {
+                    match glob_in(app_dir, pattern) {
+                        Ok(paths) if paths.is_empty() => {
+                            return RuleOutcome::fail(format!("No files match {}", pattern))
+                        }
+                        Ok(mut paths) => triggers.append(&mut paths),
+                        Err(outcome) => return outcome,
+                    }
+                }
+
+                RuleOutcome::pass(
+                    format!("Found files matching {}", patterns.join(", ")),
+                    triggers,
+                )
+            }
+            DetectRule::FileMatches(path, regex) => match read_file(app_dir, path) {
+                Ok(contents) if regex.is_match(&contents) => RuleOutcome::pass(
+                    format!("{} matches `{}`", path.display(), regex),
+                    vec![app_dir.join(path)],
+                ),
+                Ok(_) => {
+                    RuleOutcome::fail(format!("{} does not match `{}`", path.display(), regex))
+                }
+                Err(outcome) => outcome,
+            },
+            DetectRule::JsonKey(path, key) => match read_file(app_dir, path) {
+                Ok(contents) => match serde_json::from_str::<serde_json::Value>(&contents) {
+                    Ok(value) => key_outcome(
+                        app_dir,
+                        path,
+                        key,
+                        key.split('.')
+                            .try_fold(&value, |value, segment| value.get(segment))
+                            .is_some(),
+                    ),
+                    Err(error) => RuleOutcome::error(format!(
+                        "{} is not valid JSON: {}",
+                        path.display(),
+                        error
+                    )),
+                },
+                Err(outcome) => outcome,
+            },
+            DetectRule::TomlKey(path, key) => match read_file(app_dir, path) {
+                Ok(contents) => match toml::from_str::<toml::Value>(&contents) {
+                    Ok(value) => key_outcome(
+                        app_dir,
+                        path,
+                        key,
+                        key.split('.')
+                            .try_fold(&value, |value, segment| value.get(segment))
+                            .is_some(),
+                    ),
+                    Err(error) => RuleOutcome::error(format!(
+                        "{} is not valid TOML: {}",
+                        path.display(),
+                        error
+                    )),
+                },
+                Err(outcome) => outcome,
+            },
+            DetectRule::EnvFlag(name) => match env.var(name) {
+                Ok(value) if !matches!(value.trim(), "" | "0" | "false") => {
+                    RuleOutcome::pass(format!("{} is set", name), vec![])
+                }
+                _ => RuleOutcome::fail(format!("{} is not set", name)),
+            },
+            DetectRule::All(rules) => {
+                let mut explanations = Vec::new();
+                let mut triggers = Vec::new();
+
+                for rule in rules {
+                    match rule.evaluate(app_dir, env) {
+                        RuleOutcome::Pass {
+                            explanation,
+                            triggers: mut rule_triggers,
+                        } => {
+                            explanations.push(explanation);
+                            triggers.append(&mut rule_triggers);
+                        }
+                        outcome => return outcome,
+                    }
+                }
+
+                RuleOutcome::pass(explanations.join(" and "), triggers)
+            }
+            DetectRule::Any(rules) => {
+                let mut explanations = Vec::new();
+                let mut error = None;
+
+                for rule in rules {
+                    match rule.evaluate(app_dir, env) {
+                        outcome @ RuleOutcome::Pass { .. } => return outcome,
+                        RuleOutcome::Fail { explanation } => explanations.push(explanation),
+                        outcome @ RuleOutcome::Error { .. } => {
+                            error.get_or_insert(outcome);
+                        }
+                    }
+                }
+
+                error.unwrap_or_else(|| RuleOutcome::fail(explanations.join(" and ")))
+            }
+            DetectRule::Not(rule) => match rule.evaluate(app_dir, env) {
+                RuleOutcome::Pass { explanation, .. } => {
+                    RuleOutcome::fail(format!("not ({})", explanation))
+                }
+                RuleOutcome::Fail { explanation } => {
+                    RuleOutcome::pass(format!("not ({})", explanation), vec![])
+                }
+                outcome @ RuleOutcome::Error { .. } => outcome,
+            },
+        }
+    }
+}
+
+/// Negates the rule.
+impl Not for DetectRule {
+    type Output = DetectRule;
+
+    fn not(self) -> Self::Output {
+        DetectRule::Not(Box::new(self))
+    }
+}
+
+/// The outcome of evaluating a [`DetectRule`], with a human-readable explanation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleOutcome {
+    /// The rule passed because of the given files, if any.
+    Pass {
+        explanation: String,
+        triggers: Vec<PathBuf>,
+    },
+
+    /// The rule did not pass.
+    Fail { explanation: String },
+
+    /// The rule could not be evaluated, for example because a file could not be parsed.
+    Error { explanation: String },
+}
+
+impl RuleOutcome {
+    fn pass(explanation: String, triggers: Vec<PathBuf>) -> Self {
+        RuleOutcome::Pass {
+            explanation,
+            triggers,
+        }
+    }
+
+    fn fail(explanation: String) -> Self {
+        RuleOutcome::Fail { explanation }
+    }
+
+    fn error(explanation: String) -> Self {
+        RuleOutcome::Error { explanation }
+    }
+
+    pub fn passed(&self) -> bool {
+        matches!(self, RuleOutcome::Pass { .. })
+    }
+
+    pub fn explanation(&self) -> &str {
+        match self {
+            RuleOutcome::Pass { explanation, .. }
+            | RuleOutcome::Fail { explanation }
+            | RuleOutcome::Error { explanation } => explanation,
+        }
+    }
+
+    /// Converts this outcome to a [`DetectOutcome`], using the given build plan if the rule passed.
+    pub fn into_detect_outcome(self, build_plan: BuildPlan) -> DetectOutcome {
+        match self {
+            RuleOutcome::Pass { triggers, .. } if triggers.is_empty() => {
+                DetectOutcome::Pass(build_plan)
+            }
+            RuleOutcome::Pass { triggers, .. } => {
+                DetectOutcome::PassWithTriggers(build_plan, triggers)
+            }
+            RuleOutcome::Fail { explanation } => DetectOutcome::Fail(Some(explanation)),
+            RuleOutcome::Error { explanation } => DetectOutcome::Error(explanation),
+        }
+    }
+}
+
+fn glob_in(app_dir: &Path, pattern: &str) -> Result<Vec<PathBuf>, RuleOutcome> {
+    let full_pattern = format!(
+        "{}/{}",
+        glob::Pattern::escape(&app_dir.to_string_lossy()),
+        pattern
+    );
+
+    glob::glob(&full_pattern)
+        .map_err(|error| RuleOutcome::error(format!("Invalid glob {}: {}", pattern, error)))?
+        .collect::<Result<Vec<PathBuf>, glob::GlobError>>()
+        .map_err(|error| {
+            RuleOutcome::error(format!("Could not evaluate glob {}: {}", pattern, error))
+        })
+}
+
+fn read_file(app_dir: &Path, path: &Path) -> Result<String, RuleOutcome> {
+    fs::read_to_string(app_dir.join(path)).map_err(|error| match error.kind() {
+        io::ErrorKind::NotFound => RuleOutcome::fail(format!("{} does not exist", path.display())),
+        _ => RuleOutcome::error(format!("Could not read {}: {}", path.display(), error)),
+    })
+}
+
+fn key_outcome(app_dir: &Path, path: &Path, key: &str, present: bool) -> RuleOutcome {
+    if present {
+        RuleOutcome::pass(
+            format!("{} contains {}", path.display(), key),
+            vec![app_dir.join(path)],
+        )
+    } else {
+        RuleOutcome::fail(format!("{} does not contain {}", path.display(), key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    struct Fixture {
+        temp_dir: TempDir,
+        env: PlatformEnv,
+    }
+
+    impl Fixture {
+        fn new(files: &[(&str, &str)], env: &[(&str, &str)]) -> Self {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let app_dir = temp_dir.path().join("app");
+            let env_dir = temp_dir.path().join("platform").join("env");
+            fs::create_dir_all(&app_dir).unwrap();
+            fs::create_dir_all(&env_dir).unwrap();
+
+            for (path, contents) in files {
+                let path = app_dir.join(path);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, contents).unwrap();
+            }
+
+            for (name, value) in env {
+                fs::write(env_dir.join(name), value).unwrap();
+            }
+
+            let env = PlatformEnv::from_path(temp_dir.path().join("platform")).unwrap();
+            Fixture { temp_dir, env }
+        }
+
+        fn app_dir(&self) -> PathBuf {
+            self.temp_dir.path().join("app")
+        }
+
+        fn evaluate(&self, rule: &DetectRule) -> RuleOutcome {
+            rule.evaluate(self.app_dir(), &self.env)
+        }
+    }
+
+    #[test]
+    fn file_exists() {
+        let fixture = Fixture::new(&[("Gemfile.lock", "")], &[]);
+
+        assert_eq!(
+            fixture.evaluate(&DetectRule::file_exists("Gemfile.lock")),
+            RuleOutcome::Pass {
+                explanation: String::from("Found Gemfile.lock"),
+                triggers: vec![fixture.app_dir().join("Gemfile.lock")]
+            }
+        );
+        assert_eq!(
+            fixture
+                .evaluate(&DetectRule::file_exists("package.json"))
+                .explanation(),
+            "package.json does not exist"
+        );
+    }
+
+    #[test]
+    fn globs() {
+        let fixture = Fixture::new(&[("foo.gemspec", ""), ("lib/foo.rb", "")], &[]);
+
+        let outcome = fixture.evaluate(&DetectRule::any_glob(&["*.gemspec", "*.nimble"]));
+        assert_eq!(
+            outcome,
+            RuleOutcome::Pass {
+                explanation: String::from("Found files matching *.gemspec, *.nimble"),
+                triggers: vec![fixture.app_dir().join("foo.gemspec")]
+            }
+        );
+
+        assert!(fixture
+            .evaluate(&DetectRule::all_globs(&["*.gemspec", "lib/*.rb"]))
+            .passed());
+        assert_eq!(
+            fixture
+                .evaluate(&DetectRule::all_globs(&["*.gemspec", "*.nimble"]))
+                .explanation(),
+            "No files match *.nimble"
+        );
+        assert!(matches!(
+            fixture.evaluate(&DetectRule::any_glob(&["[*"])),
+            RuleOutcome::Error { .. }
+        ));
+    }
+
+    #[test]
+    fn file_matches() {
+        let fixture = Fixture::new(&[("Gemfile", "gem 'rails', '~> 6.1'\n")], &[]);
+
+        assert!(fixture
+            .evaluate(&DetectRule::file_matches(
+                "Gemfile",
+                Regex::new(r"gem 'rails'").unwrap()
+            ))
+            .passed());
+        assert_eq!(
+            fixture
+                .evaluate(&DetectRule::file_matches(
+                    "Gemfile",
+                    Regex::new(r"gem 'sinatra'").unwrap()
+                ))
+                .explanation(),
+            "Gemfile does not match `gem 'sinatra'`"
+        );
+    }
+
+    #[test]
+    fn json_and_toml_keys() {
+        let fixture = Fixture::new(
+            &[
+                ("package.json", r#"{"engines": {"node": "16.x"}}"#),
+                ("Cargo.toml", "[package]\nname = \"foo\"\n"),
+                ("broken.json", "{"),
+            ],
+            &[],
+        );
+
+        assert!(fixture
+            .evaluate(&DetectRule::json_key("package.json", "engines.node"))
+            .passed());
+        assert_eq!(
+            fixture
+                .evaluate(&DetectRule::json_key("package.json", "engines.npm"))
+                .explanation(),
+            "package.json does not contain engines.npm"
+        );
+        assert!(fixture
+            .evaluate(&DetectRule::toml_key("Cargo.toml", "package.name"))
+            .passed());
+        assert!(!fixture
+            .evaluate(&DetectRule::toml_key("Cargo.toml", "workspace"))
+            .passed());
+        assert!(matches!(
+            fixture.evaluate(&DetectRule::json_key("broken.json", "engines")),
+            RuleOutcome::Error { .. }
+        ));
+    }
+
+    #[test]
+    fn env_flag() {
+        let fixture = Fixture::new(&[], &[("BP_ENABLED", "true"), ("BP_DISABLED", "false")]);
+
+        assert!(fixture
+            .evaluate(&DetectRule::env_flag("BP_ENABLED"))
+            .passed());
+        assert!(!fixture
+            .evaluate(&DetectRule::env_flag("BP_DISABLED"))
+            .passed());
+        assert!(!fixture
+            .evaluate(&DetectRule::env_flag("BP_MISSING"))
+            .passed());
+    }
+
+    #[test]
+    fn composition() {
+        let fixture = Fixture::new(&[("Gemfile", ""), ("Gemfile.lock", "")], &[]);
+
+        let outcome = fixture.evaluate(
+            &DetectRule::file_exists("Gemfile")
+                .and(DetectRule::file_exists("Gemfile.lock"))
+                .and(!DetectRule::env_flag("BP_DISABLE_RUBY")),
+        );
+        assert_eq!(
+            outcome,
+            RuleOutcome::Pass {
+                explanation: String::from(
+                    "Found Gemfile and Found Gemfile.lock and not (BP_DISABLE_RUBY is not set)"
+                ),
+                triggers: vec![
+                    fixture.app_dir().join("Gemfile"),
+                    fixture.app_dir().join("Gemfile.lock")
+                ]
+            }
+        );
+
+        assert_eq!(
+            fixture
+                .evaluate(
+                    &DetectRule::file_exists("package.json")
+                        .or(DetectRule::file_exists("yarn.lock"))
+                )
+                .explanation(),
+            "package.json does not exist and yarn.lock does not exist"
+        );
+    }
+
+    #[test]
+    fn converts_to_detect_outcome() {
+        let fixture = Fixture::new(&[("Gemfile", "")], &[]);
+
+        assert!(matches!(
+            fixture
+                .evaluate(&DetectRule::file_exists("Gemfile"))
+                .into_detect_outcome(BuildPlan::new()),
+            DetectOutcome::PassWithTriggers(_, triggers) if triggers.len() == 1
+        ));
+        assert!(matches!(
+            fixture
+                .evaluate(&!DetectRule::file_exists("Gemfile"))
+                .into_detect_outcome(BuildPlan::new()),
+            DetectOutcome::Fail(Some(reason)) if reason == "not (Found Gemfile)"
+        ));
+    }
+}
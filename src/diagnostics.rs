@@ -0,0 +1,209 @@
+//! Collect warnings and non-fatal errors during a buildpack's build phase.
+
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// The severity of a [`Diagnostic`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "Warning"),
+            Severity::Error => write!(f, "Error"),
+        }
+    }
+}
+
+/// A single message recorded during the build.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.severity, self.message)
+    }
+}
+
+/// Collects [`Diagnostic`]s during the build.
+///
+/// Clones share the same underlying storage, which allows the runtime to print a summary of all
+/// recorded diagnostics after the build function returned. By default, recorded errors do not
+/// fail the build, use [`Diagnostics::fail_build_on_error`] to change that.
+///
+/// # Examples
+/// ```
+/// use libcnb::diagnostics::Diagnostics;
+///
+/// let diagnostics = Diagnostics::new();
+/// diagnostics.warning("Ruby version is not pinned, using default.");
+/// diagnostics.error("Gemfile.lock references an unsupported platform.");
+///
+/// assert_eq!(diagnostics.warning_count(), 1);
+/// assert!(diagnostics.has_errors());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics {
+    state: Arc<Mutex<DiagnosticsState>>,
+}
+
+#[derive(Debug, Default)]
+struct DiagnosticsState {
+    diagnostics: Vec<Diagnostic>,
+    fail_build_on_error: bool,
+}
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Diagnostics::default()
+    }
+
+    /// Records a warning, for example about deprecated configuration or unpinned versions.
+    pub fn warning(&self, message: impl Into<String>) {
+        self.push(Diagnostic {
+            severity: Severity::Warning,
+            message: message.into(),
+        });
+    }
+
+    /// Records a non-fatal error. The build continues, but see [`Diagnostics::fail_build_on_error`].
+    pub fn error(&self, message: impl Into<String>) {
+        self.push(Diagnostic {
+            severity: Severity::Error,
+            message: message.into(),
+        });
+    }
+
+    pub fn push(&self, diagnostic: Diagnostic) {
+        self.lock().diagnostics.push(diagnostic);
+    }
+
+    /// Controls whether the build should fail after the build function returned if at least one
+    /// error was recorded.
+    pub fn fail_build_on_error(&self, fail_build_on_error: bool) {
+        self.lock().fail_build_on_error = fail_build_on_error;
+    }
+
+    /// Returns all recorded diagnostics in the order they were recorded.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.lock().diagnostics.clone()
+    }
+
+    pub fn warning_count(&self) -> usize {
+        self.count(Severity::Warning)
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.count(Severity::Error)
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.error_count() > 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().diagnostics.is_empty()
+    }
+
+    pub(crate) fn should_fail_build(&self) -> bool {
+        self.lock().fail_build_on_error && self.has_errors()
+    }
+
+    fn count(&self, severity: Severity) -> usize {
+        self.lock()
+            .diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == severity)
+            .count()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, DiagnosticsState> {
+        // A panic while holding the lock cannot leave the state inconsistent, pushing to a Vec
+        // and setting a bool are the only mutations.
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Renders a summary of all recorded diagnostics, one per line.
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Build finished with {} error(s) and {} warning(s):",
+            self.error_count(),
+            self.warning_count()
+        )?;
+
+        for diagnostic in self.diagnostics() {
+            write!(f, "\n{}", diagnostic)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clones_share_diagnostics() {
+        let diagnostics = Diagnostics::new();
+        let clone = diagnostics.clone();
+
+        clone.warning("foo");
+        clone.error("bar");
+
+        assert_eq!(
+            diagnostics.diagnostics(),
+            vec![
+                Diagnostic {
+                    severity: Severity::Warning,
+                    message: String::from("foo")
+                },
+                Diagnostic {
+                    severity: Severity::Error,
+                    message: String::from("bar")
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn errors_only_fail_build_when_enabled() {
+        let diagnostics = Diagnostics::new();
+        diagnostics.error("foo");
+        assert!(!diagnostics.should_fail_build());
+
+        diagnostics.fail_build_on_error(true);
+        assert!(diagnostics.should_fail_build());
+    }
+
+    #[test]
+    fn warnings_do_not_fail_build() {
+        let diagnostics = Diagnostics::new();
+        diagnostics.fail_build_on_error(true);
+        diagnostics.warning("foo");
+
+        assert!(!diagnostics.should_fail_build());
+    }
+
+    #[test]
+    fn renders_summary() {
+        let diagnostics = Diagnostics::new();
+        diagnostics.warning("Deprecated config");
+        diagnostics.error("Unsupported platform");
+
+        assert_eq!(
+            diagnostics.to_string(),
+            "Build finished with 1 error(s) and 1 warning(s):\n[Warning] Deprecated config\n[Error] Unsupported platform"
+        );
+    }
+}
@@ -1,5 +1,7 @@
 use crate::data::launch::ProcessTypeError;
+use crate::data::layer::LayerName;
 use crate::layer_lifecycle::LayerLifecycleError;
+use crate::platform::PlatformError;
 use crate::toml_file::TomlFileError;
 use std::fmt::{Debug, Display};
 
@@ -19,6 +21,9 @@ pub enum Error<E: Debug + Display> {
     #[error("Layer lifecycle error: {0}")]
     LayerLifecycleError(#[from] LayerLifecycleError),
 
+    #[error("Layer lifecycle for layer `{0}` failed: {1}")]
+    LayerLifecycleFailed(LayerName, Box<Error<E>>),
+
     #[error("Process type error: {0}")]
     ProcessTypeError(#[from] ProcessTypeError),
 
@@ -32,7 +37,7 @@ pub enum Error<E: Debug + Display> {
     CannotDetermineStackId(std::env::VarError),
 
     #[error("Cannot create platform from platform path: {0}")]
-    CannotCreatePlatformFromPath(std::io::Error),
+    CannotCreatePlatformFromPath(PlatformError),
 
     #[error("Cannot read buildpack plan: {0}")]
     CannotReadBuildpackPlan(TomlFileError),
@@ -43,6 +48,21 @@ pub enum Error<E: Debug + Display> {
     #[error("Cannot write build plan: {0}")]
     CannotWriteBuildPlan(TomlFileError),
 
+    #[error("Cannot read launch.toml: {0}")]
+    CannotReadLaunch(TomlFileError),
+
+    #[error("Cannot write launch.toml: {0}")]
+    CannotWriteLaunch(TomlFileError),
+
+    #[error("Cannot read build.toml: {0}")]
+    CannotReadBuild(TomlFileError),
+
+    #[error("Cannot write build.toml: {0}")]
+    CannotWriteBuild(TomlFileError),
+
+    #[error("Build failed because {0} error(s) were recorded")]
+    DiagnosticErrorsRecorded(usize),
+
     #[error("Buildpack error: {0}")]
     BuildpackError(E),
 }
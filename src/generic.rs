@@ -1,9 +1,7 @@
-use std::path::Path;
-
 use crate::build::BuildContext;
 use crate::detect::DetectContext;
 use crate::error::{Error, ErrorHandler};
-use crate::platform::{Platform, PlatformEnv};
+use crate::platform::{Platform, PlatformContext, PlatformError};
 use std::fmt::{Debug, Display};
 
 /// Generic TOML metadata.
@@ -18,20 +16,18 @@ pub type GenericDetectContext = DetectContext<GenericPlatform, GenericMetadata>;
 /// Generic output type for layer lifecycles.
 pub type GenericLayerLifecycleOutput = ();
 
-/// A generic platform that only provides access to environment variables.
+/// A generic platform that provides the platform contract as-is, without further validation.
 pub struct GenericPlatform {
-    env: PlatformEnv,
+    context: PlatformContext,
 }
 
 impl Platform for GenericPlatform {
-    fn env(&self) -> &PlatformEnv {
-        &self.env
+    fn context(&self) -> &PlatformContext {
+        &self.context
     }
 
-    fn from_path(platform_dir: impl AsRef<Path>) -> std::io::Result<Self> {
-        Ok(GenericPlatform {
-            env: PlatformEnv::from_path(platform_dir)?,
-        })
+    fn from_context(context: PlatformContext) -> Result<Self, PlatformError> {
+        Ok(GenericPlatform { context })
     }
 }
 
@@ -1,26 +1,67 @@
 //! Manage layer lifecycles in a declarative way.
 
 use std::fmt::{Debug, Display};
+use std::fs;
 use std::path::{Path, PathBuf};
 
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 
 use crate::build::BuildContext;
+use crate::data::bom::Bom;
+use crate::data::layer::LayerName;
 use crate::data::layer_content_metadata::LayerContentMetadata;
 use crate::error::Error;
+use crate::generic::GenericMetadata;
 use crate::platform::Platform;
-use crate::toml_file::TomlFileError;
+use crate::toml_file::{write_toml_file, TomlFileError};
+
+pub use cache_key::*;
+pub use cleanup::*;
+#[cfg(feature = "download")]
+pub use download::*;
+pub use graph::*;
+pub use layer::*;
+pub use migrations::*;
+
+mod cache_key;
+mod cleanup;
+#[cfg(feature = "download")]
+mod download;
+mod graph;
+mod layer;
+mod migrations;
 
 /// A lifecycle of a Cloud Native Buildpack layer
 ///
 /// Use [`execute_layer_lifecycle`] to execute a layer lifecycle.
+///
+/// # Staged layers
+///
+/// [`execute_layer_lifecycle`] does not pass the layer directory itself to
+/// [`LayerLifecycle::create`] and [`LayerLifecycle::update`] but a staging directory
+/// `<layer>.libcnb-staging/layer` next to it, which is moved into place after the lifecycle
+/// succeeded. For [`LayerLifecycle::update`], the staging directory is a full copy of the
+/// existing layer, so updating a layer takes time and disk space proportional to its size even
+/// if the update itself only touches a few files. Prefer [`ValidateResult::KeepLayer`] over a
+/// no-op update for large layers.
+///
+/// Lifecycles written for earlier versions of libcnb received the final layer directory. Paths
+/// that are used after the build, for example in environment variables or in configuration files
+/// written outside of the layer, must now be built from [`BuildContext::layer_path`] instead of
+/// `layer_path`, as the `configure_bundler` step of the Ruby example buildpack does. Paths that
+/// are only used while `create` or `update` run, like the destination of a download, can still
+/// use `layer_path`.
 pub trait LayerLifecycle<P: Platform, BM, LM, O: Default, E: Debug + Display> {
     /// Creates the layer from scratch
     ///
-    /// When used with [`execute_layer_lifecycle`], `path` will be created and empty. The
-    /// returned [`LayerContentMetadata`] will be automatically written to disk. Implementations
-    /// only need to care about putting files into `path`.
+    /// When used with [`execute_layer_lifecycle`], `layer_path` will be an empty staging directory
+    /// next to the layer. The returned [`LayerContentMetadata`] will be automatically written to
+    /// disk and the staging directory is moved into place afterwards. Implementations only need
+    /// to care about putting files into `layer_path`. Paths that must stay valid once the layer
+    /// is in place, i.e. in configuration written outside of the layer, should be based on
+    /// [`BuildContext::layer_path`] instead. If this function returns an error, the previous
+    /// layer, if any, is left untouched.
     fn create(
         &self,
         layer_path: &Path,
@@ -45,6 +86,21 @@ pub trait LayerLifecycle<P: Platform, BM, LM, O: Default, E: Debug + Display> {
         Ok(MetadataRecoveryStrategy::DeleteLayer)
     }
 
+    /// Migrations for older versions of this layer's metadata
+    ///
+    /// When migrations are returned, [`execute_layer_lifecycle`] stores the metadata version next
+    /// to the layer metadata. Metadata of an existing layer with a different version is migrated
+    /// to `LM` and written back to disk before [`LayerLifecycle::validate`] is called. If there is
+    /// no migration path, the layer will be deleted just like with
+    /// [`MetadataRecoveryStrategy::DeleteLayer`] and
+    /// [`recover_from_invalid_metadata`](LayerLifecycle::recover_from_invalid_metadata) will not
+    /// be called.
+    ///
+    /// The default implementation returns `None` to disable metadata versioning.
+    fn metadata_migrations(&self) -> Option<MetadataMigrations<LM>> {
+        None
+    }
+
     /// Based on the current state of the layer, determines how the layer will be processed
     ///
     /// This will be called by [`execute_layer_lifecycle`] when the layer for this lifecycle already
@@ -71,6 +127,10 @@ pub trait LayerLifecycle<P: Platform, BM, LM, O: Default, E: Debug + Display> {
     }
 
     /// Updates an existing layer
+    ///
+    /// When used with [`execute_layer_lifecycle`], `layer_path` will be a staging copy of the
+    /// existing layer, see [`LayerLifecycle::create`]. If this function returns an error, the
+    /// existing layer is left untouched.
     fn update(
         &self,
         #[allow(unused_variables)] layer_path: &Path,
@@ -89,6 +149,20 @@ pub trait LayerLifecycle<P: Platform, BM, LM, O: Default, E: Debug + Display> {
         Ok(O::default())
     }
 
+    /// Bill-of-materials entries describing the layer contents
+    ///
+    /// When used with [`execute_layer_lifecycle`], the entries are collected after the layer was
+    /// created, updated or kept. They are merged into `launch.toml` if the layer is a `launch`
+    /// layer and into `build.toml` if it is a `build` layer.
+    fn bom(
+        &self,
+        #[allow(unused_variables)] layer_path: &Path,
+        #[allow(unused_variables)] layer_content_metadata: &LayerContentMetadata<LM>,
+        #[allow(unused_variables)] build_context: &BuildContext<P, BM>,
+    ) -> Bom {
+        Bom::new()
+    }
+
     fn on_lifecycle_start(&self) {}
     fn on_keep(&self) {}
     fn on_update(&self) {}
@@ -144,14 +218,50 @@ pub enum LayerLifecycleError {
     #[error("Could not delete layer: {0}")]
     CannotDeleteLayer(std::io::Error),
 
+    #[error("Could not copy layer to its staging directory before update: {0}")]
+    CannotStageLayer(std::io::Error),
+
+    #[error("Could not move staged layer into place: {0}")]
+    CannotMoveStagedLayer(std::io::Error),
+
+    #[error("Could not back up layer before replacing it: {0}")]
+    CannotBackupLayer(std::io::Error),
+
+    #[error("Could not restore layer from backup: {0}")]
+    CannotRestoreLayer(std::io::Error),
+
     #[error("Layer content metadata is missing after lifecycle")]
     CannotFindLayerMetadataAfterLifecycle(),
 
     #[error("Could not read layer content metadata: {0}")]
     CannotReadLayerContentMetadata(TomlFileError),
+
+    #[error("Layer `{0}` was added more than once")]
+    DuplicateLayer(LayerName),
+
+    #[error("Layer `{0}` depends on unknown layer `{1}`")]
+    UnknownLayerDependency(LayerName, LayerName),
+
+    #[error(
+        "Layers have cyclic dependencies: {}",
+        .0.iter().map(LayerName::as_str).collect::<Vec<_>>().join(", ")
+    )]
+    CyclicLayerDependencies(Vec<LayerName>),
 }
 
 /// Executes a layer lifecycle for a given layer name and [`BuildContext`]
+///
+/// [`LayerLifecycle::create`] and [`LayerLifecycle::update`] work in a temporary sibling
+/// directory of the layer. Once they succeeded and the layer content metadata was written, the
+/// staged layer is renamed into place. Failures therefore never leave a partially written layer
+/// behind:
+///
+/// - If [`LayerLifecycle::create`] fails for a new layer, no layer is created.
+/// - If [`LayerLifecycle::create`] fails while recreating an existing layer, the previous layer
+///   is left intact.
+/// - If [`LayerLifecycle::update`] fails, the previous layer is left intact since the update
+///   works on a copy of it.
+///
 /// See [`LayerLifecycle`]
 pub fn execute_layer_lifecycle<
     P: Platform,
@@ -160,55 +270,84 @@ pub fn execute_layer_lifecycle<
     O: Default,
     E: Debug + Display,
 >(
-    layer_name: impl AsRef<str>,
+    layer_name: &LayerName,
     layer_lifecycle: impl LayerLifecycle<P, BM, LM, O, E>,
     context: &BuildContext<P, BM>,
 ) -> Result<O, Error<E>> {
     layer_lifecycle.on_lifecycle_start();
+    context.layer_tracker.touch(layer_name);
 
-    let layer_path = context.layer_path(&layer_name);
-    let layer_content_metadata = match context.read_layer_content_metadata(&layer_name) {
-        Ok(value) => value,
-        Err(_) => {
-            // If we cannot read the metadata due to a TOML file error, it's very likely that the
-            // metadata could not be parsed into `LM` due to field/type mismatch(es). Regardless
-            // of the actual error, we run the metadata recovery process here.
-            metadata_recovery(&layer_name, &layer_lifecycle, &context)?
-        }
+    // A backup or staged layer only exists at this point when an earlier build was interrupted
+    // while moving a staged layer into place. Restoring the backup ensures the lifecycle starts
+    // from the last known good state.
+    let backup = LayerBackup::new(layer_name, context);
+    if backup.exists() {
+        backup
+            .restore()
+            .map_err(LayerLifecycleError::CannotRestoreLayer)?;
+    }
+
+    StagedLayer::new(layer_name, context)
+        .discard()
+        .map_err(LayerLifecycleError::CannotDeleteLayer)?;
+
+    let layer_path = context.layer_path(layer_name);
+    let migrations = layer_lifecycle.metadata_migrations();
+    let layer_content_metadata = match &migrations {
+        Some(migrations) => metadata_migration(layer_name, migrations, context)?,
+        None => match context.read_layer_content_metadata(layer_name) {
+            Ok(value) => value,
+            Err(_) => {
+                // If we cannot read the metadata due to a TOML file error, it's very likely that
+                // the metadata could not be parsed into `LM` due to field/type mismatch(es).
+                // Regardless of the actual error, we run the metadata recovery process here.
+                metadata_recovery(layer_name, &layer_lifecycle, context)?
+            }
+        },
     };
 
     match layer_content_metadata {
         Some(layer_content_metadata) => {
             let handler =
-                match layer_lifecycle.validate(&layer_path, &layer_content_metadata, &context) {
+                match layer_lifecycle.validate(&layer_path, &layer_content_metadata, context) {
                     ValidateResult::KeepLayer => handle_layer_keep,
                     ValidateResult::RecreateLayer => handle_layer_recreate,
                     ValidateResult::UpdateLayer => handle_layer_update,
                 };
 
             handler(
-                &layer_name,
-                &layer_path,
+                layer_name,
                 layer_content_metadata,
                 &layer_lifecycle,
-                &context,
+                migrations.as_ref(),
+                context,
             )?;
         }
-        None => handle_layer_create(&layer_name, &layer_path, &layer_lifecycle, &context)?,
+        None => handle_layer_create(layer_name, &layer_lifecycle, migrations.as_ref(), context)?,
     };
 
     layer_lifecycle.on_lifecycle_end();
 
-    match context.read_layer_content_metadata(&layer_name) {
+    match context.read_layer_content_metadata(layer_name) {
         Err(toml_file_error) => Err(Error::LayerLifecycleError(
             LayerLifecycleError::CannotReadLayerContentMetadata(toml_file_error),
         )),
         Ok(None) => Err(Error::LayerLifecycleError(
             LayerLifecycleError::CannotFindLayerMetadataAfterLifecycle(),
         )),
-        Ok(Some(metadata)) => layer_lifecycle
-            .layer_lifecycle_data(&layer_path, metadata)
-            .map_err(Error::BuildpackError),
+        Ok(Some(metadata)) => {
+            let bom = layer_lifecycle.bom(&layer_path, &metadata, context);
+            if metadata.launch {
+                context.bom.add_launch_entries(bom.iter().cloned());
+            }
+            if metadata.build {
+                context.bom.add_build_entries(bom);
+            }
+
+            layer_lifecycle
+                .layer_lifecycle_data(&layer_path, metadata)
+                .map_err(Error::BuildpackError)
+        }
     }
 }
 
@@ -219,10 +358,10 @@ fn handle_layer_keep<
     O: Default,
     E: Debug + Display,
 >(
-    _layer_name: impl AsRef<str>,
-    _layer_path: &PathBuf,
+    _layer_name: &LayerName,
     _layer_content_metadata: LayerContentMetadata<LM>,
     layer_lifecycle: &impl LayerLifecycle<P, BM, LM, O, E>,
+    _migrations: Option<&MetadataMigrations<LM>>,
     _context: &BuildContext<P, BM>,
 ) -> Result<(), Error<E>> {
     layer_lifecycle.on_keep();
@@ -236,24 +375,26 @@ fn handle_layer_create<
     O: Default,
     E: Debug + Display,
 >(
-    layer_name: impl AsRef<str>,
-    layer_path: &PathBuf,
+    layer_name: &LayerName,
     layer_lifecycle: &impl LayerLifecycle<P, BM, LM, O, E>,
+    migrations: Option<&MetadataMigrations<LM>>,
     context: &BuildContext<P, BM>,
 ) -> Result<(), Error<E>> {
-    std::fs::create_dir_all(&layer_path)
+    let staged_layer = StagedLayer::new(layer_name, context);
+    staged_layer
+        .prepare_empty()
         .map_err(LayerLifecycleError::CannotCreateLayerDirectoryBeforeCreate)?;
 
     layer_lifecycle.on_create();
 
-    let layer_content_metadata = layer_lifecycle
-        .create(&layer_path, &context)
-        .map_err(Error::BuildpackError)?;
+    let result = layer_lifecycle
+        .create(&staged_layer.layer_path(), context)
+        .map_err(Error::BuildpackError)
+        .and_then(|layer_content_metadata| {
+            staged_layer.commit(&layer_content_metadata, migrations, context)
+        });
 
-    context
-        .write_layer_content_metadata(&layer_name, &layer_content_metadata)
-        .map_err(LayerLifecycleError::CannotWriteLayerMetadata)?;
-    Ok(())
+    discard_on_error(result, &staged_layer)
 }
 
 fn handle_layer_recreate<
@@ -263,29 +404,14 @@ fn handle_layer_recreate<
     O: Default,
     E: Debug + Display,
 >(
-    layer_name: impl AsRef<str>,
-    layer_path: &PathBuf,
+    layer_name: &LayerName,
     _layer_content_metadata: LayerContentMetadata<LM>,
     layer_lifecycle: &impl LayerLifecycle<P, BM, LM, O, E>,
+    migrations: Option<&MetadataMigrations<LM>>,
     context: &BuildContext<P, BM>,
 ) -> Result<(), Error<E>> {
-    context
-        .delete_layer(&layer_name)
-        .map_err(LayerLifecycleError::CannotDeleteLayer)?;
-
-    std::fs::create_dir_all(&layer_path).map_err(LayerLifecycleError::CannotDeleteLayer)?;
-
-    layer_lifecycle.on_create();
-
-    let content_metadata = layer_lifecycle
-        .create(&layer_path, &context)
-        .map_err(Error::BuildpackError)?;
-
-    context
-        .write_layer_content_metadata(&layer_name, &content_metadata)
-        .map_err(|toml_error| {
-            Error::LayerLifecycleError(LayerLifecycleError::CannotWriteLayerMetadata(toml_error))
-        })
+    // The previous layer is only replaced once the staged layer was created successfully.
+    handle_layer_create(layer_name, layer_lifecycle, migrations, context)
 }
 
 fn handle_layer_update<
@@ -295,23 +421,311 @@ fn handle_layer_update<
     O: Default,
     E: Debug + Display,
 >(
-    layer_name: impl AsRef<str>,
-    layer_path: &PathBuf,
+    layer_name: &LayerName,
     layer_content_metadata: LayerContentMetadata<LM>,
     layer_lifecycle: &impl LayerLifecycle<P, BM, LM, O, E>,
+    migrations: Option<&MetadataMigrations<LM>>,
     context: &BuildContext<P, BM>,
 ) -> Result<(), Error<E>> {
-    layer_lifecycle.on_update();
-
-    let content_metadata = layer_lifecycle
-        .update(&layer_path, layer_content_metadata, &context)
-        .map_err(Error::BuildpackError)?;
+    let staged_layer = StagedLayer::new(layer_name, context);
+    let result = staged_layer
+        .prepare_copy()
+        .map_err(|io_error| {
+            Error::LayerLifecycleError(LayerLifecycleError::CannotStageLayer(io_error))
+        })
+        .and_then(|_| {
+            layer_lifecycle.on_update();
 
-    context
-        .write_layer_content_metadata(&layer_name, &content_metadata)
-        .map_err(|toml_error| {
-            Error::LayerLifecycleError(LayerLifecycleError::CannotWriteLayerMetadata(toml_error))
+            layer_lifecycle
+                .update(&staged_layer.layer_path(), layer_content_metadata, context)
+                .map_err(Error::BuildpackError)
         })
+        .and_then(|layer_content_metadata| {
+            staged_layer.commit(&layer_content_metadata, migrations, context)
+        });
+
+    discard_on_error(result, &staged_layer)
+}
+
+/// Discards the staged layer if `result` is an error.
+///
+/// Cleanup is best-effort, the original error is more relevant to the user.
+fn discard_on_error<T, E>(result: Result<T, E>, staged_layer: &StagedLayer) -> Result<T, E> {
+    if result.is_err() {
+        let _ = staged_layer.discard();
+    }
+
+    result
+}
+
+/// Where [`write_layer_content_metadata`] writes to.
+enum MetadataDestination<'a> {
+    /// The `<layer>.toml` of the layer itself.
+    Layer,
+    /// The metadata file of a staged layer, see [`StagedLayer`].
+    Staged(&'a Path),
+}
+
+/// Writes layer content metadata, including the metadata version if the layer lifecycle uses
+/// metadata migrations.
+fn write_layer_content_metadata<P: Platform, BM, LM: Serialize + DeserializeOwned>(
+    layer_name: &LayerName,
+    destination: MetadataDestination,
+    layer_content_metadata: &LayerContentMetadata<LM>,
+    migrations: Option<&MetadataMigrations<LM>>,
+    context: &BuildContext<P, BM>,
+) -> Result<(), TomlFileError> {
+    match migrations {
+        None => write_to(layer_name, destination, layer_content_metadata, context),
+        Some(migrations) => {
+            let versioned_metadata = migrations
+                .versioned_table(&layer_content_metadata.metadata)
+                .map_err(|migration_error| match migration_error {
+                    MigrationError::SerializationError(toml_error) => toml_error,
+                    other => serde::ser::Error::custom(other),
+                })?;
+
+            write_to(
+                layer_name,
+                destination,
+                &LayerContentMetadata {
+                    launch: layer_content_metadata.launch,
+                    build: layer_content_metadata.build,
+                    cache: layer_content_metadata.cache,
+                    metadata: versioned_metadata,
+                },
+                context,
+            )
+        }
+    }
+}
+
+fn write_to<P: Platform, BM, M: Serialize>(
+    layer_name: &LayerName,
+    destination: MetadataDestination,
+    layer_content_metadata: &LayerContentMetadata<M>,
+    context: &BuildContext<P, BM>,
+) -> Result<(), TomlFileError> {
+    match destination {
+        MetadataDestination::Layer => {
+            context.write_layer_content_metadata(layer_name, layer_content_metadata)
+        }
+        MetadataDestination::Staged(path) => write_toml_file(
+            &layer_content_metadata.for_api(&context.buildpack_descriptor.api),
+            path,
+        ),
+    }
+}
+
+/// A layer that is being created or updated in a temporary sibling directory of the layer.
+///
+/// The staging directory contains the layer directory and its metadata file, but no
+/// `<name>.toml` of its own, so the lifecycle will never pick it up as a layer.
+struct StagedLayer {
+    layer_name: LayerName,
+    staging_path: PathBuf,
+    layer_path: PathBuf,
+    layer_content_metadata_path: PathBuf,
+}
+
+impl StagedLayer {
+    fn new<P: Platform, BM>(layer_name: &LayerName, context: &BuildContext<P, BM>) -> Self {
+        StagedLayer {
+            layer_name: layer_name.clone(),
+            staging_path: context
+                .layers_dir
+                .join(format!("{}.libcnb-staging", layer_name.as_str())),
+            layer_path: context.layer_path(layer_name),
+            layer_content_metadata_path: context.layer_content_metadata_path(layer_name),
+        }
+    }
+
+    /// The directory passed to [`LayerLifecycle::create`] and [`LayerLifecycle::update`].
+    fn layer_path(&self) -> PathBuf {
+        self.staging_path.join("layer")
+    }
+
+    fn staged_content_metadata_path(&self) -> PathBuf {
+        self.staging_path.join("layer.toml")
+    }
+
+    fn prepare_empty(&self) -> std::io::Result<()> {
+        self.discard()?;
+        fs::create_dir_all(self.layer_path())
+    }
+
+    /// Stages a copy of the existing layer, leaving the layer itself untouched.
+    ///
+    /// The layer directory itself might not exist, the lifecycle only restores the metadata of
+    /// layers that are not cached. An empty directory is staged in that case.
+    fn prepare_copy(&self) -> std::io::Result<()> {
+        if !self.layer_path.exists() {
+            return self.prepare_empty();
+        }
+
+        self.discard()?;
+        fs::create_dir_all(&self.staging_path)?;
+        copy_dir(&self.layer_path, &self.layer_path())
+    }
+
+    /// Writes the layer content metadata and moves the staged layer into place, replacing the
+    /// existing layer.
+    fn commit<P: Platform, BM, LM: Serialize + DeserializeOwned, E: Debug + Display>(
+        &self,
+        layer_content_metadata: &LayerContentMetadata<LM>,
+        migrations: Option<&MetadataMigrations<LM>>,
+        context: &BuildContext<P, BM>,
+    ) -> Result<(), Error<E>> {
+        write_layer_content_metadata(
+            &self.layer_name,
+            MetadataDestination::Staged(&self.staged_content_metadata_path()),
+            layer_content_metadata,
+            migrations,
+            context,
+        )
+        .map_err(LayerLifecycleError::CannotWriteLayerMetadata)?;
+
+        let backup = LayerBackup::new(&self.layer_name, context);
+        backup
+            .create()
+            .map_err(LayerLifecycleError::CannotBackupLayer)?;
+
+        // The metadata is moved last. Should this process be interrupted, the backup is restored
+        // by the next execution of the lifecycle.
+        let moved = fs::rename(self.layer_path(), &self.layer_path).and_then(|_| {
+            fs::rename(
+                self.staged_content_metadata_path(),
+                &self.layer_content_metadata_path,
+            )
+        });
+
+        match moved {
+            Ok(()) => backup
+                .discard()
+                .and_then(|_| self.discard())
+                .map_err(|io_error| {
+                    Error::LayerLifecycleError(LayerLifecycleError::CannotDeleteLayer(io_error))
+                }),
+            Err(io_error) => {
+                backup
+                    .restore()
+                    .map_err(LayerLifecycleError::CannotRestoreLayer)?;
+
+                Err(Error::LayerLifecycleError(
+                    LayerLifecycleError::CannotMoveStagedLayer(io_error),
+                ))
+            }
+        }
+    }
+
+    fn discard(&self) -> std::io::Result<()> {
+        remove_dir_if_exists(&self.staging_path)
+    }
+}
+
+/// A backup of an existing layer, kept while a staged layer is moved into place.
+///
+/// The layer directory and its metadata file are moved into a single sibling directory without
+/// a `<name>.toml` of its own, so the lifecycle will never pick it up as a layer.
+struct LayerBackup {
+    backup_path: PathBuf,
+    layer_path: PathBuf,
+    layer_content_metadata_path: PathBuf,
+}
+
+impl LayerBackup {
+    fn new<P: Platform, BM>(layer_name: &LayerName, context: &BuildContext<P, BM>) -> Self {
+        LayerBackup {
+            backup_path: context
+                .layers_dir
+                .join(format!("{}.libcnb-backup", layer_name.as_ref())),
+            layer_path: context.layer_path(layer_name),
+            layer_content_metadata_path: context.layer_content_metadata_path(layer_name),
+        }
+    }
+
+    fn exists(&self) -> bool {
+        self.backup_path.exists()
+    }
+
+    fn create(&self) -> std::io::Result<()> {
+        fs::create_dir_all(&self.backup_path)?;
+
+        // The metadata is moved first. Should this process be interrupted, a layer directory
+        // without metadata remains which is never considered a valid layer.
+        rename_if_exists(
+            &self.layer_content_metadata_path,
+            self.backup_path.join("layer.toml"),
+        )?;
+        rename_if_exists(&self.layer_path, self.backup_path.join("layer"))
+    }
+
+    fn restore(&self) -> std::io::Result<()> {
+        remove_file_if_exists(&self.layer_content_metadata_path)?;
+        remove_dir_if_exists(&self.layer_path)?;
+
+        rename_if_exists(self.backup_path.join("layer"), &self.layer_path)?;
+        rename_if_exists(
+            self.backup_path.join("layer.toml"),
+            &self.layer_content_metadata_path,
+        )?;
+
+        self.discard()
+    }
+
+    fn discard(&self) -> std::io::Result<()> {
+        remove_dir_if_exists(&self.backup_path)
+    }
+}
+
+/// Recursively copies a directory, preserving symlinks and file permissions.
+fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(to)?;
+
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let target = to.join(entry.file_name());
+
+        if file_type.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else if file_type.is_symlink() {
+            copy_symlink(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_symlink(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(from)?, to)
+}
+
+#[cfg(not(unix))]
+fn copy_symlink(from: &Path, to: &Path) -> std::io::Result<()> {
+    fs::copy(from, to).map(|_| ())
+}
+
+fn rename_if_exists(from: impl AsRef<Path>, to: impl AsRef<Path>) -> std::io::Result<()> {
+    ignore_not_found(fs::rename(from, to))
+}
+
+fn remove_file_if_exists(path: impl AsRef<Path>) -> std::io::Result<()> {
+    ignore_not_found(fs::remove_file(path))
+}
+
+fn remove_dir_if_exists(path: impl AsRef<Path>) -> std::io::Result<()> {
+    ignore_not_found(fs::remove_dir_all(path))
+}
+
+fn ignore_not_found(result: std::io::Result<()>) -> std::io::Result<()> {
+    match result {
+        Err(io_error) if io_error.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        other => other,
+    }
 }
 
 fn metadata_recovery<
@@ -321,7 +735,7 @@ fn metadata_recovery<
     O: Default,
     E: Debug + Display,
 >(
-    layer_name: impl AsRef<str>,
+    layer_name: &LayerName,
     layer_lifecycle: &impl LayerLifecycle<P, BM, LM, O, E>,
     context: &BuildContext<P, BM>,
 ) -> Result<Option<LayerContentMetadata<LM>>, Error<E>> {
@@ -329,7 +743,7 @@ fn metadata_recovery<
     // non-existent metadata so subsequent steps don't have to deal with either.
     let mut layer_content_metadata = {
         let maybe_layer_content_metadata = context
-            .read_layer_content_metadata(&layer_name)
+            .read_layer_content_metadata(layer_name)
             .map_err(|toml_file_error| {
                 Error::LayerLifecycleError(LayerLifecycleError::CannotNotReadUntypedLayerMetadata(
                     toml_file_error,
@@ -343,13 +757,13 @@ fn metadata_recovery<
     };
 
     let metadata_recovery_strategy = layer_lifecycle
-        .recover_from_invalid_metadata(&layer_content_metadata.metadata, &context)
+        .recover_from_invalid_metadata(&layer_content_metadata.metadata, context)
         .map_err(Error::BuildpackError)?;
 
     match metadata_recovery_strategy {
         MetadataRecoveryStrategy::DeleteLayer => {
             context
-                .delete_layer(&layer_name)
+                .delete_layer(layer_name)
                 .map_err(LayerLifecycleError::CannotDeleteLayer)?;
 
             Ok(None)
@@ -357,11 +771,718 @@ fn metadata_recovery<
         MetadataRecoveryStrategy::ReplaceMetadata(replacement_metadata) => {
             let updated_metadata = layer_content_metadata.metadata(replacement_metadata);
 
-            context
-                .write_layer_content_metadata(&layer_name, &updated_metadata)
-                .map_err(LayerLifecycleError::CannotReplaceLayerMetadata)?;
+            write_layer_content_metadata(
+                layer_name,
+                MetadataDestination::Layer,
+                &updated_metadata,
+                None,
+                context,
+            )
+            .map_err(LayerLifecycleError::CannotReplaceLayerMetadata)?;
 
             Ok(Some(updated_metadata))
         }
     }
 }
+
+fn metadata_migration<P: Platform, BM, LM: Serialize + DeserializeOwned, E: Debug + Display>(
+    layer_name: &LayerName,
+    migrations: &MetadataMigrations<LM>,
+    context: &BuildContext<P, BM>,
+) -> Result<Option<LayerContentMetadata<LM>>, Error<E>> {
+    let untyped_layer_content_metadata: LayerContentMetadata<GenericMetadata> = match context
+        .read_layer_content_metadata(layer_name)
+        .map_err(LayerLifecycleError::CannotNotReadUntypedLayerMetadata)?
+    {
+        None => return Ok(None),
+        Some(value) => value,
+    };
+
+    let untyped_metadata = untyped_layer_content_metadata.metadata.unwrap_or_default();
+
+    match migrations.migrate(&untyped_metadata) {
+        Ok(metadata) => {
+            let layer_content_metadata = LayerContentMetadata {
+                launch: untyped_layer_content_metadata.launch,
+                build: untyped_layer_content_metadata.build,
+                cache: untyped_layer_content_metadata.cache,
+                metadata,
+            };
+
+            if stored_version(&untyped_metadata).ok() != Some(migrations.version()) {
+                write_layer_content_metadata(
+                    layer_name,
+                    MetadataDestination::Layer,
+                    &layer_content_metadata,
+                    Some(migrations),
+                    context,
+                )
+                .map_err(LayerLifecycleError::CannotReplaceLayerMetadata)?;
+            }
+
+            Ok(Some(layer_content_metadata))
+        }
+        Err(_) => {
+            context
+                .delete_layer(layer_name)
+                .map_err(LayerLifecycleError::CannotDeleteLayer)?;
+
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::bom::Entry;
+    use crate::data::launch::Launch;
+    use crate::generic::{GenericBuildContext, GenericMetadata, GenericPlatform};
+    use crate::layer_name;
+    use crate::test_support::build_context;
+    use serde::ser::{Error as _, SerializeStruct};
+    use serde::{Deserialize, Serializer};
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[derive(Deserialize, Debug)]
+    struct TestLayerMetadata {
+        value: String,
+    }
+
+    // Serialization fails for the value `unserializable` to simulate a failure while writing
+    // the layer content metadata.
+    impl Serialize for TestLayerMetadata {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            if self.value == "unserializable" {
+                return Err(S::Error::custom("unserializable"));
+            }
+
+            let mut state = serializer.serialize_struct("TestLayerMetadata", 1)?;
+            state.serialize_field("value", &self.value)?;
+            state.end()
+        }
+    }
+
+    impl VersionedMetadata for TestLayerMetadata {
+        const VERSION: u32 = 1;
+    }
+
+    #[derive(Deserialize, Serialize)]
+    struct TestLayerMetadataV0 {
+        old_value: String,
+    }
+
+    impl VersionedMetadata for TestLayerMetadataV0 {
+        const VERSION: u32 = 0;
+    }
+
+    impl From<TestLayerMetadataV0> for TestLayerMetadata {
+        fn from(v0: TestLayerMetadataV0) -> Self {
+            TestLayerMetadata {
+                value: v0.old_value,
+            }
+        }
+    }
+
+    struct TestLayerLifecycle {
+        validate_result: fn() -> ValidateResult,
+        create_result: Result<&'static str, &'static str>,
+        update_result: Result<&'static str, &'static str>,
+        migrations: bool,
+        migrations_calls: Rc<Cell<usize>>,
+        before_create: Option<Box<dyn Fn()>>,
+    }
+
+    impl TestLayerLifecycle {
+        fn new() -> Self {
+            TestLayerLifecycle {
+                validate_result: || ValidateResult::RecreateLayer,
+                create_result: Ok("created"),
+                update_result: Ok("updated"),
+                migrations: false,
+                migrations_calls: Rc::new(Cell::new(0)),
+                before_create: None,
+            }
+        }
+    }
+
+    impl LayerLifecycle<GenericPlatform, GenericMetadata, TestLayerMetadata, (), String>
+        for TestLayerLifecycle
+    {
+        fn create(
+            &self,
+            layer_path: &Path,
+            _build_context: &GenericBuildContext,
+        ) -> Result<LayerContentMetadata<TestLayerMetadata>, String> {
+            if let Some(before_create) = &self.before_create {
+                before_create();
+            }
+
+            fs::write(layer_path.join("contents"), "new").unwrap();
+
+            self.create_result
+                .map(test_layer_content_metadata)
+                .map_err(String::from)
+        }
+
+        fn metadata_migrations(&self) -> Option<MetadataMigrations<TestLayerMetadata>> {
+            self.migrations_calls.set(self.migrations_calls.get() + 1);
+
+            if self.migrations {
+                Some(
+                    MetadataMigrations::new().migration::<TestLayerMetadataV0, TestLayerMetadata>(),
+                )
+            } else {
+                None
+            }
+        }
+
+        fn validate(
+            &self,
+            _layer_path: &Path,
+            _layer_content_metadata: &LayerContentMetadata<TestLayerMetadata>,
+            _build_context: &GenericBuildContext,
+        ) -> ValidateResult {
+            (self.validate_result)()
+        }
+
+        fn update(
+            &self,
+            layer_path: &Path,
+            _layer_content_metadata: LayerContentMetadata<TestLayerMetadata>,
+            _build_context: &GenericBuildContext,
+        ) -> Result<LayerContentMetadata<TestLayerMetadata>, String> {
+            fs::write(layer_path.join("contents"), "updated").unwrap();
+
+            self.update_result
+                .map(test_layer_content_metadata)
+                .map_err(String::from)
+        }
+
+        fn bom(
+            &self,
+            _layer_path: &Path,
+            layer_content_metadata: &LayerContentMetadata<TestLayerMetadata>,
+            _build_context: &GenericBuildContext,
+        ) -> Bom {
+            vec![Entry::new(&layer_content_metadata.metadata.value)]
+        }
+    }
+
+    fn test_layer_content_metadata(value: &str) -> LayerContentMetadata<TestLayerMetadata> {
+        LayerContentMetadata::new(TestLayerMetadata {
+            value: String::from(value),
+        })
+    }
+
+    fn write_existing_layer(context: &GenericBuildContext) {
+        fs::create_dir_all(context.layer_path(&layer_name!("test"))).unwrap();
+        fs::write(
+            context.layer_path(&layer_name!("test")).join("contents"),
+            "previous",
+        )
+        .unwrap();
+        context
+            .write_layer_content_metadata(
+                &layer_name!("test"),
+                &test_layer_content_metadata("previous"),
+            )
+            .unwrap();
+    }
+
+    fn layer_contents(context: &GenericBuildContext) -> Option<String> {
+        fs::read_to_string(context.layer_path(&layer_name!("test")).join("contents")).ok()
+    }
+
+    fn layer_metadata_value(context: &GenericBuildContext) -> Option<String> {
+        context
+            .read_layer_content_metadata::<TestLayerMetadata>(&layer_name!("test"))
+            .unwrap()
+            .map(|layer_content_metadata| layer_content_metadata.metadata.value)
+    }
+
+    fn layers_dir_entries(context: &GenericBuildContext) -> Vec<String> {
+        let mut entries: Vec<String> = fs::read_dir(&context.layers_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+
+        entries.sort();
+        entries
+    }
+
+    #[test]
+    fn create_failure_removes_new_layer() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+
+        let mut layer_lifecycle = TestLayerLifecycle::new();
+        layer_lifecycle.create_result = Err("create failed");
+
+        let result = execute_layer_lifecycle(&layer_name!("test"), layer_lifecycle, &context);
+
+        assert!(matches!(result, Err(Error::BuildpackError(_))));
+        assert!(layers_dir_entries(&context).is_empty());
+    }
+
+    #[test]
+    fn metadata_write_failure_removes_new_layer() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+
+        let mut layer_lifecycle = TestLayerLifecycle::new();
+        layer_lifecycle.create_result = Ok("unserializable");
+
+        let result = execute_layer_lifecycle(&layer_name!("test"), layer_lifecycle, &context);
+
+        assert!(matches!(
+            result,
+            Err(Error::LayerLifecycleError(
+                LayerLifecycleError::CannotWriteLayerMetadata(_)
+            ))
+        ));
+        assert!(layers_dir_entries(&context).is_empty());
+    }
+
+    #[test]
+    fn create_removes_leftover_layer_directory() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+
+        fs::create_dir_all(context.layer_path(&layer_name!("test"))).unwrap();
+        fs::write(
+            context.layer_path(&layer_name!("test")).join("leftover"),
+            "",
+        )
+        .unwrap();
+
+        execute_layer_lifecycle(&layer_name!("test"), TestLayerLifecycle::new(), &context).unwrap();
+
+        assert!(!context
+            .layer_path(&layer_name!("test"))
+            .join("leftover")
+            .exists());
+        assert_eq!(layer_contents(&context), Some(String::from("new")));
+        assert_eq!(
+            layer_metadata_value(&context),
+            Some(String::from("created"))
+        );
+    }
+
+    #[test]
+    fn recreate_replaces_previous_layer() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+        write_existing_layer(&context);
+
+        execute_layer_lifecycle(&layer_name!("test"), TestLayerLifecycle::new(), &context).unwrap();
+
+        assert_eq!(layer_contents(&context), Some(String::from("new")));
+        assert_eq!(
+            layer_metadata_value(&context),
+            Some(String::from("created"))
+        );
+        assert_eq!(layers_dir_entries(&context), vec!["test", "test.toml"]);
+    }
+
+    #[test]
+    fn recreate_create_failure_restores_previous_layer() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+        write_existing_layer(&context);
+
+        let mut layer_lifecycle = TestLayerLifecycle::new();
+        layer_lifecycle.create_result = Err("create failed");
+
+        let result = execute_layer_lifecycle(&layer_name!("test"), layer_lifecycle, &context);
+
+        assert!(matches!(result, Err(Error::BuildpackError(_))));
+        assert_eq!(layer_contents(&context), Some(String::from("previous")));
+        assert_eq!(
+            layer_metadata_value(&context),
+            Some(String::from("previous"))
+        );
+        assert_eq!(layers_dir_entries(&context), vec!["test", "test.toml"]);
+    }
+
+    #[test]
+    fn recreate_metadata_write_failure_restores_previous_layer() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+        write_existing_layer(&context);
+
+        let mut layer_lifecycle = TestLayerLifecycle::new();
+        layer_lifecycle.create_result = Ok("unserializable");
+
+        let result = execute_layer_lifecycle(&layer_name!("test"), layer_lifecycle, &context);
+
+        assert!(matches!(
+            result,
+            Err(Error::LayerLifecycleError(
+                LayerLifecycleError::CannotWriteLayerMetadata(_)
+            ))
+        ));
+        assert_eq!(layer_contents(&context), Some(String::from("previous")));
+        assert_eq!(
+            layer_metadata_value(&context),
+            Some(String::from("previous"))
+        );
+        assert_eq!(layers_dir_entries(&context), vec!["test", "test.toml"]);
+    }
+
+    #[test]
+    fn update_failure_keeps_previous_layer() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+        write_existing_layer(&context);
+
+        let mut layer_lifecycle = TestLayerLifecycle::new();
+        layer_lifecycle.validate_result = || ValidateResult::UpdateLayer;
+        layer_lifecycle.update_result = Err("update failed");
+
+        let result = execute_layer_lifecycle(&layer_name!("test"), layer_lifecycle, &context);
+
+        assert!(matches!(result, Err(Error::BuildpackError(_))));
+        assert_eq!(layer_contents(&context), Some(String::from("previous")));
+        assert_eq!(
+            layer_metadata_value(&context),
+            Some(String::from("previous"))
+        );
+        assert_eq!(layers_dir_entries(&context), vec!["test", "test.toml"]);
+    }
+
+    #[test]
+    fn update_metadata_write_failure_keeps_previous_layer() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+        write_existing_layer(&context);
+
+        let mut layer_lifecycle = TestLayerLifecycle::new();
+        layer_lifecycle.validate_result = || ValidateResult::UpdateLayer;
+        layer_lifecycle.update_result = Ok("unserializable");
+
+        let result = execute_layer_lifecycle(&layer_name!("test"), layer_lifecycle, &context);
+
+        assert!(matches!(
+            result,
+            Err(Error::LayerLifecycleError(
+                LayerLifecycleError::CannotWriteLayerMetadata(_)
+            ))
+        ));
+        assert_eq!(layer_contents(&context), Some(String::from("previous")));
+        assert_eq!(
+            layer_metadata_value(&context),
+            Some(String::from("previous"))
+        );
+        assert_eq!(layers_dir_entries(&context), vec!["test", "test.toml"]);
+    }
+
+    #[test]
+    fn update_replaces_previous_layer() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+        write_existing_layer(&context);
+        fs::write(
+            context.layer_path(&layer_name!("test")).join("unchanged"),
+            "",
+        )
+        .unwrap();
+
+        let mut layer_lifecycle = TestLayerLifecycle::new();
+        layer_lifecycle.validate_result = || ValidateResult::UpdateLayer;
+
+        execute_layer_lifecycle(&layer_name!("test"), layer_lifecycle, &context).unwrap();
+
+        assert_eq!(layer_contents(&context), Some(String::from("updated")));
+        assert!(context
+            .layer_path(&layer_name!("test"))
+            .join("unchanged")
+            .exists());
+        assert_eq!(
+            layer_metadata_value(&context),
+            Some(String::from("updated"))
+        );
+        assert_eq!(layers_dir_entries(&context), vec!["test", "test.toml"]);
+    }
+
+    #[test]
+    fn update_without_layer_directory_stages_empty_layer() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+        context
+            .write_layer_content_metadata(
+                &layer_name!("test"),
+                &test_layer_content_metadata("previous"),
+            )
+            .unwrap();
+
+        let mut layer_lifecycle = TestLayerLifecycle::new();
+        layer_lifecycle.validate_result = || ValidateResult::UpdateLayer;
+
+        execute_layer_lifecycle(&layer_name!("test"), layer_lifecycle, &context).unwrap();
+
+        assert_eq!(layer_contents(&context), Some(String::from("updated")));
+        assert_eq!(
+            layer_metadata_value(&context),
+            Some(String::from("updated"))
+        );
+        assert_eq!(layers_dir_entries(&context), vec!["test", "test.toml"]);
+    }
+
+    #[test]
+    fn backup_failure_keeps_previous_layer() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+        write_existing_layer(&context);
+
+        // A file at the backup location prevents the previous layer from being moved aside.
+        let backup_path = context.layers_dir.join("test.libcnb-backup");
+        let mut layer_lifecycle = TestLayerLifecycle::new();
+        layer_lifecycle.before_create =
+            Some(Box::new(move || fs::write(&backup_path, "").unwrap()));
+
+        let result = execute_layer_lifecycle(&layer_name!("test"), layer_lifecycle, &context);
+
+        assert!(matches!(
+            result,
+            Err(Error::LayerLifecycleError(
+                LayerLifecycleError::CannotBackupLayer(_)
+            ))
+        ));
+        assert_eq!(layer_contents(&context), Some(String::from("previous")));
+        assert_eq!(
+            layer_metadata_value(&context),
+            Some(String::from("previous"))
+        );
+        assert_eq!(
+            layers_dir_entries(&context),
+            vec!["test", "test.libcnb-backup", "test.toml"]
+        );
+    }
+
+    #[test]
+    fn create_works_in_staging_directory() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+        write_existing_layer(&context);
+
+        let layer_path = context.layer_path(&layer_name!("test"));
+        let mut layer_lifecycle = TestLayerLifecycle::new();
+        layer_lifecycle.before_create = Some(Box::new(move || {
+            // The previous layer is still in place while the new one is created.
+            assert_eq!(
+                fs::read_to_string(layer_path.join("contents")).unwrap(),
+                "previous"
+            );
+        }));
+
+        execute_layer_lifecycle(&layer_name!("test"), layer_lifecycle, &context).unwrap();
+
+        assert_eq!(layer_contents(&context), Some(String::from("new")));
+    }
+
+    #[test]
+    fn interrupted_recreate_is_restored_on_next_execution() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+        write_existing_layer(&context);
+
+        // Simulate a build that was interrupted after the backup was created and `create` already
+        // wrote some files into the new layer.
+        LayerBackup::new(&layer_name!("test"), &context)
+            .create()
+            .unwrap();
+        fs::create_dir_all(context.layer_path(&layer_name!("test"))).unwrap();
+        fs::write(
+            context.layer_path(&layer_name!("test")).join("contents"),
+            "partial",
+        )
+        .unwrap();
+
+        let mut layer_lifecycle = TestLayerLifecycle::new();
+        layer_lifecycle.validate_result = || ValidateResult::KeepLayer;
+
+        execute_layer_lifecycle(&layer_name!("test"), layer_lifecycle, &context).unwrap();
+
+        assert_eq!(layer_contents(&context), Some(String::from("previous")));
+        assert_eq!(
+            layer_metadata_value(&context),
+            Some(String::from("previous"))
+        );
+        assert_eq!(layers_dir_entries(&context), vec!["test", "test.toml"]);
+    }
+
+    #[test]
+    fn interrupted_commit_is_restored_on_next_execution() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+        write_existing_layer(&context);
+
+        // Simulate a build that was interrupted after the previous layer was moved aside and the
+        // staged layer was moved into place, but before its metadata was.
+        LayerBackup::new(&layer_name!("test"), &context)
+            .create()
+            .unwrap();
+        fs::create_dir_all(context.layer_path(&layer_name!("test"))).unwrap();
+        fs::write(
+            context.layer_path(&layer_name!("test")).join("contents"),
+            "partial",
+        )
+        .unwrap();
+        fs::create_dir_all(context.layers_dir.join("test.libcnb-staging")).unwrap();
+
+        let mut layer_lifecycle = TestLayerLifecycle::new();
+        layer_lifecycle.validate_result = || ValidateResult::KeepLayer;
+
+        execute_layer_lifecycle(&layer_name!("test"), layer_lifecycle, &context).unwrap();
+
+        assert_eq!(layer_contents(&context), Some(String::from("previous")));
+        assert_eq!(
+            layer_metadata_value(&context),
+            Some(String::from("previous"))
+        );
+        assert_eq!(layers_dir_entries(&context), vec!["test", "test.toml"]);
+    }
+
+    fn untyped_layer_metadata(context: &GenericBuildContext) -> toml::value::Table {
+        context
+            .read_layer_content_metadata::<GenericMetadata>(&layer_name!("test"))
+            .unwrap()
+            .and_then(|layer_content_metadata| layer_content_metadata.metadata)
+            .unwrap()
+    }
+
+    #[test]
+    fn create_writes_metadata_version() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+
+        let mut layer_lifecycle = TestLayerLifecycle::new();
+        layer_lifecycle.migrations = true;
+
+        execute_layer_lifecycle(&layer_name!("test"), layer_lifecycle, &context).unwrap();
+
+        assert_eq!(
+            untyped_layer_metadata(&context).get(METADATA_VERSION_KEY),
+            Some(&toml::Value::Integer(1))
+        );
+    }
+
+    #[test]
+    fn metadata_migrations_are_built_once_per_execution() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+        context
+            .write_layer_content_metadata(
+                &layer_name!("test"),
+                &LayerContentMetadata::new(TestLayerMetadataV0 {
+                    old_value: String::from("previous"),
+                }),
+            )
+            .unwrap();
+
+        let mut layer_lifecycle = TestLayerLifecycle::new();
+        layer_lifecycle.migrations = true;
+        layer_lifecycle.validate_result = || ValidateResult::UpdateLayer;
+        let migrations_calls = Rc::clone(&layer_lifecycle.migrations_calls);
+
+        execute_layer_lifecycle(&layer_name!("test"), layer_lifecycle, &context).unwrap();
+
+        assert_eq!(migrations_calls.get(), 1);
+    }
+
+    #[test]
+    fn migrates_outdated_metadata_before_validate() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+
+        fs::create_dir_all(context.layer_path(&layer_name!("test"))).unwrap();
+        fs::write(
+            context.layer_path(&layer_name!("test")).join("contents"),
+            "previous",
+        )
+        .unwrap();
+        context
+            .write_layer_content_metadata(
+                &layer_name!("test"),
+                &LayerContentMetadata::new(TestLayerMetadataV0 {
+                    old_value: String::from("previous"),
+                }),
+            )
+            .unwrap();
+
+        let mut layer_lifecycle = TestLayerLifecycle::new();
+        layer_lifecycle.migrations = true;
+        layer_lifecycle.validate_result = || ValidateResult::KeepLayer;
+
+        execute_layer_lifecycle(&layer_name!("test"), layer_lifecycle, &context).unwrap();
+
+        assert_eq!(layer_contents(&context), Some(String::from("previous")));
+        assert_eq!(
+            layer_metadata_value(&context),
+            Some(String::from("previous"))
+        );
+        assert_eq!(
+            untyped_layer_metadata(&context).get(METADATA_VERSION_KEY),
+            Some(&toml::Value::Integer(1))
+        );
+    }
+
+    #[test]
+    fn deletes_layer_without_migration_path() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+
+        fs::create_dir_all(context.layer_path(&layer_name!("test"))).unwrap();
+        fs::write(
+            context.layer_path(&layer_name!("test")).join("contents"),
+            "previous",
+        )
+        .unwrap();
+        fs::write(
+            context.layer_content_metadata_path(&layer_name!("test")),
+            "[metadata]\nmetadata-version = 5\nvalue = \"previous\"\n",
+        )
+        .unwrap();
+
+        let mut layer_lifecycle = TestLayerLifecycle::new();
+        layer_lifecycle.migrations = true;
+        layer_lifecycle.validate_result = || ValidateResult::KeepLayer;
+
+        execute_layer_lifecycle(&layer_name!("test"), layer_lifecycle, &context).unwrap();
+
+        assert_eq!(layer_contents(&context), Some(String::from("new")));
+        assert_eq!(
+            layer_metadata_value(&context),
+            Some(String::from("created"))
+        );
+    }
+
+    #[test]
+    fn contributes_bom_entries_of_launch_layers_to_launch_toml() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+
+        fs::create_dir_all(context.layer_path(&layer_name!("test"))).unwrap();
+        context
+            .write_layer_content_metadata(
+                &layer_name!("test"),
+                &test_layer_content_metadata("previous").launch(true),
+            )
+            .unwrap();
+
+        let mut layer_lifecycle = TestLayerLifecycle::new();
+        layer_lifecycle.validate_result = || ValidateResult::KeepLayer;
+        execute_layer_lifecycle(&layer_name!("test"), layer_lifecycle, &context).unwrap();
+
+        assert_eq!(context.bom.launch_entries(), vec![Entry::new("previous")]);
+        assert!(context.bom.build_entries().is_empty());
+
+        context.write_launch(Launch::new()).unwrap();
+        let launch_toml: toml::Value =
+            toml::from_str(&fs::read_to_string(context.layers_dir.join("launch.toml")).unwrap())
+                .unwrap();
+        assert_eq!(launch_toml["bom"][0]["name"].as_str(), Some("previous"));
+    }
+}
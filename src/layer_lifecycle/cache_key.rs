@@ -0,0 +1,413 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::build::BuildContext;
+use crate::data::layer_content_metadata::LayerContentMetadata;
+use crate::layer_lifecycle::ValidateResult;
+use crate::platform::{Platform, PlatformEnv};
+
+/// A key that identifies the inputs a layer was created from.
+///
+/// Cache keys are hex-encoded SHA-256 digests. Use [`CacheKeyBuilder`] to create them.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct CacheKey(String);
+
+impl CacheKey {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for CacheKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Builds a [`CacheKey`] from app files, globs, directories, environment variables and arbitrary
+/// values.
+///
+/// Relative paths and glob patterns are resolved against the base directory, usually the app
+/// directory. Files are only read when [`CacheKeyBuilder::build`] is called. Missing files and
+/// directories are part of the key, they do not cause an error.
+///
+/// # Examples
+/// ```no_run
+/// use libcnb::layer_lifecycle::CacheKeyBuilder;
+/// use libcnb::{GenericBuildContext, Platform};
+///
+/// fn bundler_cache_key(context: &GenericBuildContext) {
+///     let cache_key = CacheKeyBuilder::from_context(context)
+///         .file("Gemfile.lock")
+///         .glob("vendor/cache/*.gem")
+///         .env_var(context.platform.env(), "BUNDLE_WITHOUT")
+///         .build();
+/// }
+/// ```
+pub struct CacheKeyBuilder {
+    base_dir: PathBuf,
+    inputs: Vec<CacheKeyInput>,
+}
+
+enum CacheKeyInput {
+    File(PathBuf),
+    Glob(String),
+    Directory(PathBuf),
+    Value(String, Option<String>),
+}
+
+impl CacheKeyBuilder {
+    /// Creates a new builder that resolves relative paths against `base_dir`.
+    pub fn new(base_dir: impl Into<PathBuf>) -> Self {
+        CacheKeyBuilder {
+            base_dir: base_dir.into(),
+            inputs: vec![],
+        }
+    }
+
+    /// Creates a new builder that resolves relative paths against the app directory and already
+    /// contains the stack id and buildpack version.
+    pub fn from_context<P: Platform, BM>(context: &BuildContext<P, BM>) -> Self {
+        CacheKeyBuilder::new(&context.app_dir)
+            .stack_id(&context.stack_id)
+            .buildpack_version(context.buildpack_descriptor.buildpack.version.to_string())
+    }
+
+    /// Adds the contents of a single file.
+    pub fn file(mut self, path: impl AsRef<Path>) -> Self {
+        self.inputs
+            .push(CacheKeyInput::File(path.as_ref().to_path_buf()));
+        self
+    }
+
+    /// Adds the contents of all files matching the given glob pattern.
+    pub fn glob(mut self, pattern: impl Into<String>) -> Self {
+        self.inputs.push(CacheKeyInput::Glob(pattern.into()));
+        self
+    }
+
+    /// Adds the contents of all files in the given directory, recursively.
+    pub fn directory(mut self, path: impl AsRef<Path>) -> Self {
+        self.inputs
+            .push(CacheKeyInput::Directory(path.as_ref().to_path_buf()));
+        self
+    }
+
+    /// Adds the value of a platform environment variable. An unset variable is part of the key.
+    pub fn env_var(self, env: &PlatformEnv, key: impl AsRef<str>) -> Self {
+        let value = env.var(key.as_ref()).ok();
+        self.value_of(format!("env:{}", key.as_ref()), value)
+    }
+
+    pub fn stack_id(self, stack_id: impl Into<String>) -> Self {
+        self.value("stack-id", stack_id)
+    }
+
+    pub fn buildpack_version(self, buildpack_version: impl Into<String>) -> Self {
+        self.value("buildpack-version", buildpack_version)
+    }
+
+    /// Adds an arbitrary named value, for example the version of a downloaded dependency.
+    pub fn value(self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.value_of(name, Some(value.into()))
+    }
+
+    fn value_of(mut self, name: impl Into<String>, value: Option<String>) -> Self {
+        self.inputs.push(CacheKeyInput::Value(name.into(), value));
+        self
+    }
+
+    /// Reads all inputs and computes the cache key.
+    pub fn build(&self) -> Result<CacheKey, CacheKeyError> {
+        let mut hasher = Sha256::new();
+
+        for input in &self.inputs {
+            match input {
+                CacheKeyInput::File(path) => {
+                    update(&mut hasher, "file", path.to_string_lossy().as_bytes());
+                    self.hash_file(&mut hasher, &self.base_dir.join(path))?;
+                }
+                CacheKeyInput::Glob(pattern) => {
+                    update(&mut hasher, "glob", pattern.as_bytes());
+
+                    let absolute_pattern = format!(
+                        "{}/{}",
+                        glob::Pattern::escape(&self.base_dir.to_string_lossy()),
+                        pattern
+                    );
+
+                    let mut paths = glob::glob(&absolute_pattern)?
+                        .collect::<Result<Vec<PathBuf>, glob::GlobError>>()?;
+                    paths.sort();
+
+                    for path in paths.iter().filter(|path| path.is_file()) {
+                        self.hash_relative_path(&mut hasher, path);
+                        self.hash_file(&mut hasher, path)?;
+                    }
+                }
+                CacheKeyInput::Directory(path) => {
+                    update(&mut hasher, "directory", path.to_string_lossy().as_bytes());
+                    self.hash_directory(&mut hasher, &self.base_dir.join(path))?;
+                }
+                CacheKeyInput::Value(name, value) => {
+                    update(&mut hasher, "value", name.as_bytes());
+                    match value {
+                        Some(value) => update(&mut hasher, "set", value.as_bytes()),
+                        None => update(&mut hasher, "unset", &[]),
+                    }
+                }
+            }
+        }
+
+        Ok(CacheKey(format!("{:x}", hasher.finalize())))
+    }
+
+    fn hash_file(&self, hasher: &mut Sha256, path: &Path) -> Result<(), CacheKeyError> {
+        match fs::read(path) {
+            Ok(contents) => update(hasher, "contents", &contents),
+            Err(io_error) if io_error.kind() == std::io::ErrorKind::NotFound => {
+                update(hasher, "missing", &[])
+            }
+            Err(io_error) => return Err(CacheKeyError::IoError(path.to_path_buf(), io_error)),
+        }
+
+        Ok(())
+    }
+
+    fn hash_directory(&self, hasher: &mut Sha256, path: &Path) -> Result<(), CacheKeyError> {
+        if !path.is_dir() {
+            update(hasher, "missing", &[]);
+            return Ok(());
+        }
+
+        let mut entries = fs::read_dir(path)
+            .and_then(|read_dir| read_dir.collect::<Result<Vec<_>, _>>())
+            .map_err(|io_error| CacheKeyError::IoError(path.to_path_buf(), io_error))?;
+
+        // Directory iteration order is platform dependent.
+        entries.sort_by_key(|entry| entry.file_name());
+
+        for entry in entries {
+            let entry_path = entry.path();
+            let file_type = entry
+                .file_type()
+                .map_err(|io_error| CacheKeyError::IoError(entry_path.clone(), io_error))?;
+
+            self.hash_relative_path(hasher, &entry_path);
+
+            if file_type.is_symlink() {
+                let target = fs::read_link(&entry_path)
+                    .map_err(|io_error| CacheKeyError::IoError(entry_path.clone(), io_error))?;
+                update(hasher, "symlink", target.to_string_lossy().as_bytes());
+            } else if file_type.is_dir() {
+                self.hash_directory(hasher, &entry_path)?;
+            } else {
+                self.hash_file(hasher, &entry_path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn hash_relative_path(&self, hasher: &mut Sha256, path: &Path) {
+        let relative_path = path.strip_prefix(&self.base_dir).unwrap_or(path);
+        update(hasher, "path", relative_path.to_string_lossy().as_bytes());
+    }
+}
+
+// Every input is tagged and length-prefixed so that different combinations of inputs never
+// result in the same byte stream.
+fn update(hasher: &mut Sha256, tag: &str, bytes: &[u8]) {
+    hasher.update(tag.as_bytes());
+    hasher.update((bytes.len() as u64).to_be_bytes());
+    hasher.update(bytes);
+}
+
+/// An error that occurred while computing a [`CacheKey`].
+#[derive(thiserror::Error, Debug)]
+pub enum CacheKeyError {
+    #[error("Could not read `{0}` for cache key: {1}")]
+    IoError(PathBuf, std::io::Error),
+
+    #[error("Invalid glob pattern for cache key: {0}")]
+    InvalidGlobPattern(#[from] glob::PatternError),
+
+    #[error("Could not read glob match for cache key: {0}")]
+    GlobError(#[from] glob::GlobError),
+}
+
+/// Layer metadata that contains a [`CacheKey`].
+///
+/// Implement this for custom layer metadata types to use them with [`validate_cache_key`].
+pub trait HasCacheKey {
+    fn cache_key(&self) -> &CacheKey;
+}
+
+/// Layer metadata that only consists of a [`CacheKey`].
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CacheKeyMetadata {
+    pub cache_key: CacheKey,
+}
+
+impl HasCacheKey for CacheKeyMetadata {
+    fn cache_key(&self) -> &CacheKey {
+        &self.cache_key
+    }
+}
+
+/// A ready-made [`LayerLifecycle::validate`](crate::layer_lifecycle::LayerLifecycle::validate)
+/// strategy that keeps the layer if its stored cache key matches the expected one and returns
+/// `on_mismatch` otherwise.
+///
+/// Pass [`ValidateResult::UpdateLayer`] as `on_mismatch` if a stale layer can be brought up to
+/// date incrementally, [`ValidateResult::RecreateLayer`] if it has to be rebuilt from scratch.
+///
+/// # Examples
+/// ```
+/// use libcnb::data::layer_content_metadata::LayerContentMetadata;
+/// use libcnb::layer_lifecycle::{validate_cache_key, CacheKeyBuilder, CacheKeyMetadata, ValidateResult};
+///
+/// let cache_key = CacheKeyBuilder::new("/workspace").value("ruby", "3.0.1").build().unwrap();
+/// let layer_content_metadata = LayerContentMetadata::new(CacheKeyMetadata {
+///     cache_key: cache_key.clone(),
+/// });
+///
+/// assert!(matches!(
+///     validate_cache_key(&cache_key, &layer_content_metadata, ValidateResult::RecreateLayer),
+///     ValidateResult::KeepLayer
+/// ));
+/// ```
+pub fn validate_cache_key<M: HasCacheKey>(
+    expected_cache_key: &CacheKey,
+    layer_content_metadata: &LayerContentMetadata<M>,
+    on_mismatch: ValidateResult,
+) -> ValidateResult {
+    if layer_content_metadata.metadata.cache_key() == expected_cache_key {
+        ValidateResult::KeepLayer
+    } else {
+        on_mismatch
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(base_dir: &Path, path: &str, contents: &str) {
+        let path = base_dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn file_contents_change_key() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let builder = CacheKeyBuilder::new(temp_dir.path()).file("Gemfile.lock");
+
+        let missing = builder.build().unwrap();
+        write(temp_dir.path(), "Gemfile.lock", "foo");
+        let foo = builder.build().unwrap();
+        write(temp_dir.path(), "Gemfile.lock", "bar");
+        let bar = builder.build().unwrap();
+
+        assert_ne!(missing, foo);
+        assert_ne!(foo, bar);
+        assert_eq!(bar, builder.build().unwrap());
+    }
+
+    #[test]
+    fn glob_includes_matching_files_only() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let builder = CacheKeyBuilder::new(temp_dir.path()).glob("vendor/*.gem");
+
+        write(temp_dir.path(), "vendor/a.gem", "a");
+        let before = builder.build().unwrap();
+
+        write(temp_dir.path(), "vendor/README", "ignored");
+        assert_eq!(before, builder.build().unwrap());
+
+        write(temp_dir.path(), "vendor/b.gem", "b");
+        assert_ne!(before, builder.build().unwrap());
+    }
+
+    #[test]
+    fn directory_includes_nested_files_and_names() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let builder = CacheKeyBuilder::new(temp_dir.path()).directory("config");
+
+        write(temp_dir.path(), "config/nested/a", "a");
+        let before = builder.build().unwrap();
+
+        fs::rename(
+            temp_dir.path().join("config/nested/a"),
+            temp_dir.path().join("config/nested/b"),
+        )
+        .unwrap();
+        let renamed = builder.build().unwrap();
+
+        write(temp_dir.path(), "config/nested/b", "changed");
+        let changed = builder.build().unwrap();
+
+        assert_ne!(before, renamed);
+        assert_ne!(renamed, changed);
+    }
+
+    #[test]
+    fn values_are_unambiguous() {
+        let a = CacheKeyBuilder::new("/").value("a", "bc").build().unwrap();
+        let b = CacheKeyBuilder::new("/").value("ab", "c").build().unwrap();
+
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn env_var_unset_differs_from_empty() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(temp_dir.path().join("env")).unwrap();
+        let env = PlatformEnv::from_path(temp_dir.path()).unwrap();
+        let unset = CacheKeyBuilder::new("/")
+            .env_var(&env, "FOO")
+            .build()
+            .unwrap();
+
+        write(temp_dir.path(), "env/FOO", "");
+        let env = PlatformEnv::from_path(temp_dir.path()).unwrap();
+        let empty = CacheKeyBuilder::new("/")
+            .env_var(&env, "FOO")
+            .build()
+            .unwrap();
+
+        assert_ne!(unset, empty);
+    }
+
+    #[test]
+    fn validate_cache_key_returns_given_result_on_mismatch() {
+        let layer_content_metadata = LayerContentMetadata::new(CacheKeyMetadata {
+            cache_key: CacheKeyBuilder::new("/").value("a", "1").build().unwrap(),
+        });
+
+        let expected_cache_key = CacheKeyBuilder::new("/").value("a", "2").build().unwrap();
+
+        assert!(matches!(
+            validate_cache_key(
+                &expected_cache_key,
+                &layer_content_metadata,
+                ValidateResult::RecreateLayer
+            ),
+            ValidateResult::RecreateLayer
+        ));
+        assert!(matches!(
+            validate_cache_key(
+                &expected_cache_key,
+                &layer_content_metadata,
+                ValidateResult::UpdateLayer
+            ),
+            ValidateResult::UpdateLayer
+        ));
+    }
+}
@@ -0,0 +1,387 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use serde::de::DeserializeOwned;
+
+use crate::build::BuildContext;
+use crate::data::layer::LayerName;
+use crate::data::layer_content_metadata::LayerContentMetadata;
+use crate::data::sbom::SbomFormat;
+use crate::platform::Platform;
+use crate::toml_file::{read_toml_file, TomlFileError};
+
+/// Determines what happens to layers that were not touched during the build.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LayerCleanupPolicy {
+    /// Untouched layers are kept, i.e. they are restored from the cache in the next build.
+    #[default]
+    KeepUntouched,
+
+    /// Untouched layers are deleted after a successful build, including their SBOM files,
+    /// orphaned layer directories and `<layer>.toml` files.
+    DeleteUntouched,
+}
+
+/// Tracks which layers were touched during the build.
+///
+/// Layers are touched by [`execute_layer_lifecycle`](crate::layer_lifecycle::execute_layer_lifecycle),
+/// [`execute_layer`](crate::layer_lifecycle::execute_layer),
+/// [`BuildContext::write_layer_content_metadata`] and [`BuildContext::write_layer_env`]. Clones share the same underlying storage,
+/// which allows the runtime to apply the [`LayerCleanupPolicy`] after the build function
+/// returned.
+///
+/// # Examples
+/// ```
+/// use libcnb::layer_lifecycle::{LayerCleanupPolicy, LayerTracker};
+/// use libcnb::layer_name;
+///
+/// let tracker = LayerTracker::new();
+/// tracker.set_cleanup_policy(LayerCleanupPolicy::DeleteUntouched);
+/// tracker.touch(&layer_name!("ruby"));
+///
+/// assert!(tracker.is_touched(&layer_name!("ruby")));
+/// assert!(!tracker.is_touched(&layer_name!("bundler")));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct LayerTracker {
+    state: Arc<Mutex<LayerTrackerState>>,
+}
+
+#[derive(Debug, Default)]
+struct LayerTrackerState {
+    touched: BTreeSet<LayerName>,
+    cleanup_policy: LayerCleanupPolicy,
+}
+
+impl LayerTracker {
+    pub fn new() -> Self {
+        LayerTracker::default()
+    }
+
+    /// Marks the layer as touched, which keeps it regardless of the [`LayerCleanupPolicy`].
+    pub fn touch(&self, layer_name: &LayerName) {
+        self.lock().touched.insert(layer_name.clone());
+    }
+
+    pub fn is_touched(&self, layer_name: &LayerName) -> bool {
+        self.lock().touched.contains(layer_name)
+    }
+
+    /// Returns the names of all touched layers, sorted by name.
+    pub fn touched(&self) -> Vec<LayerName> {
+        self.lock().touched.iter().cloned().collect()
+    }
+
+    pub fn set_cleanup_policy(&self, cleanup_policy: LayerCleanupPolicy) {
+        self.lock().cleanup_policy = cleanup_policy;
+    }
+
+    pub fn cleanup_policy(&self) -> LayerCleanupPolicy {
+        self.lock().cleanup_policy
+    }
+
+    fn lock(&self) -> MutexGuard<'_, LayerTrackerState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// The contents of the layers directory, see [`BuildContext::layers`].
+#[derive(Debug)]
+pub struct LayerListing<M> {
+    /// Layers with both a directory and a `<layer>.toml` file, sorted by name.
+    pub layers: Vec<ListedLayer<M>>,
+
+    /// Layers with both a directory and a `<layer>.toml` file that could not be read, for
+    /// example because it does not match the metadata type `M`, sorted by name.
+    pub unreadable_metadata: Vec<(LayerName, TomlFileError)>,
+
+    /// Names of `<layer>.toml` files without a layer directory or with an invalid
+    /// [`LayerName`].
+    pub orphaned_metadata: Vec<String>,
+
+    /// Names of layer directories without a `<layer>.toml` file or with an invalid
+    /// [`LayerName`].
+    pub orphaned_directories: Vec<String>,
+}
+
+/// A layer found in the layers directory, see [`BuildContext::layers`].
+#[derive(Debug)]
+pub struct ListedLayer<M> {
+    pub name: LayerName,
+    pub path: PathBuf,
+    pub content_metadata: LayerContentMetadata<M>,
+}
+
+impl<P: Platform, BM> BuildContext<P, BM> {
+    /// Lists all layers in the layers directory, including layers restored from previous builds.
+    ///
+    /// `launch.toml`, `build.toml`, `store.toml` and files managed by libcnb are not considered
+    /// layers. Use [`GenericMetadata`](crate::GenericMetadata) if layers use different metadata
+    /// types.
+    pub fn layers<M: DeserializeOwned>(&self) -> Result<LayerListing<M>, TomlFileError> {
+        list_layers(&self.layers_dir)
+    }
+
+    /// Deletes all layers that were not touched during the build, see [`LayerTracker`]. Returns
+    /// the names of the deleted layers, sorted by name.
+    ///
+    /// The names are file names rather than [`LayerName`]s because orphaned layer directories and
+    /// `<layer>.toml` files are deleted as well, and those may have been written by other tools
+    /// with names that are not valid layer names.
+    pub fn delete_untouched_layers(&self) -> Result<Vec<String>, TomlFileError> {
+        delete_untouched_layers(&self.layers_dir, &self.layer_tracker)
+    }
+}
+
+/// Whether the given file name in the layers directory belongs to a temporary file or directory
+/// of libcnb, like a staged layer or a layer backup, rather than to a layer.
+pub(crate) fn is_work_path(file_name: &str) -> bool {
+    file_name.contains(".libcnb-")
+}
+
+pub(crate) fn list_layers<M: DeserializeOwned>(
+    layers_dir: &Path,
+) -> Result<LayerListing<M>, TomlFileError> {
+    let mut directories = BTreeSet::new();
+    let mut metadata_files = BTreeSet::new();
+
+    for entry in fs::read_dir(layers_dir)? {
+        let path = entry?.path();
+        let file_name = match path.file_name().and_then(|name| name.to_str()) {
+            Some(file_name) if !is_work_path(file_name) => String::from(file_name),
+            _ => continue,
+        };
+
+        if path.is_dir() {
+            directories.insert(file_name);
+        } else if let Some(name) = file_name.strip_suffix(".toml") {
+            if !matches!(name, "launch" | "build" | "store") {
+                metadata_files.insert(String::from(name));
+            }
+        }
+    }
+
+    let mut layers = Vec::new();
+    let mut unreadable_metadata = Vec::new();
+    for name in directories.intersection(&metadata_files) {
+        if let Ok(layer_name) = LayerName::from_str(name) {
+            match read_toml_file(layers_dir.join(format!("{}.toml", name))) {
+                Ok(content_metadata) => layers.push(ListedLayer {
+                    name: layer_name,
+                    path: layers_dir.join(name),
+                    content_metadata,
+                }),
+                Err(error) => unreadable_metadata.push((layer_name, error)),
+            }
+        }
+    }
+
+    let is_listed = |name: &String| {
+        layers.iter().any(|layer| layer.name.as_str() == name)
+            || unreadable_metadata
+                .iter()
+                .any(|(layer_name, _)| layer_name.as_str() == name)
+    };
+
+    Ok(LayerListing {
+        orphaned_metadata: metadata_files
+            .iter()
+            .filter(|name| !is_listed(name))
+            .cloned()
+            .collect(),
+        orphaned_directories: directories
+            .iter()
+            .filter(|name| !is_listed(name))
+            .cloned()
+            .collect(),
+        layers,
+        unreadable_metadata,
+    })
+}
+
+pub(crate) fn delete_untouched_layers(
+    layers_dir: &Path,
+    layer_tracker: &LayerTracker,
+) -> Result<Vec<String>, TomlFileError> {
+    let listing = list_layers::<Option<toml::Value>>(layers_dir)?;
+
+    let mut deleted = Vec::new();
+    for name in listing
+        .layers
+        .into_iter()
+        .map(|layer| String::from(layer.name.as_str()))
+        .chain(
+            listing
+                .unreadable_metadata
+                .into_iter()
+                .map(|(layer_name, _)| String::from(layer_name.as_str())),
+        )
+        .chain(listing.orphaned_metadata)
+        .chain(listing.orphaned_directories)
+    {
+        let is_touched = LayerName::from_str(&name)
+            .is_ok_and(|layer_name| layer_tracker.is_touched(&layer_name));
+
+        if !is_touched {
+            remove_if_exists(fs::remove_file(layers_dir.join(format!("{}.toml", name))))?;
+            remove_if_exists(fs::remove_dir_all(layers_dir.join(&name)))?;
+
+            for sbom_format in &SbomFormat::ALL {
+                remove_if_exists(fs::remove_file(layers_dir.join(format!(
+                    "{}.sbom.{}",
+                    name,
+                    sbom_format.extension()
+                ))))?;
+            }
+
+            deleted.push(name);
+        }
+    }
+
+    deleted.sort();
+    Ok(deleted)
+}
+
+fn remove_if_exists(result: io::Result<()>) -> io::Result<()> {
+    match result {
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::layer_env::{LayerEnv, ModificationBehavior, Scope};
+    use crate::generic::GenericMetadata;
+    use crate::layer_name;
+    use crate::test_support::build_context;
+
+    #[test]
+    fn lists_layers_and_orphans() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+
+        for name in &[layer_name!("ruby"), layer_name!("bundler")] {
+            fs::create_dir(context.layer_path(name)).unwrap();
+            context
+                .write_layer_content_metadata(name, &LayerContentMetadata::new(1).launch(true))
+                .unwrap();
+        }
+        fs::create_dir(context.layer_path(&layer_name!("stale"))).unwrap();
+        fs::write(
+            context.layer_content_metadata_path(&layer_name!("gone")),
+            "",
+        )
+        .unwrap();
+        fs::create_dir(context.layer_path(&layer_name!("node"))).unwrap();
+        fs::write(
+            context.layer_content_metadata_path(&layer_name!("node")),
+            "metadata = \"not a number\"",
+        )
+        .unwrap();
+        fs::write(context.launch_path(), "").unwrap();
+        fs::create_dir(context.layers_dir.join("ruby.libcnb-backup")).unwrap();
+
+        let listing = context.layers::<i32>().unwrap();
+
+        let names: Vec<&str> = listing
+            .layers
+            .iter()
+            .map(|layer| layer.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["bundler", "ruby"]);
+        assert!(listing.layers[0].content_metadata.launch);
+        assert_eq!(listing.layers[1].content_metadata.metadata, 1);
+        assert_eq!(
+            listing.layers[1].path,
+            context.layer_path(&layer_name!("ruby"))
+        );
+        assert_eq!(listing.unreadable_metadata.len(), 1);
+        assert_eq!(listing.unreadable_metadata[0].0, layer_name!("node"));
+        assert_eq!(listing.orphaned_metadata, vec!["gone"]);
+        assert_eq!(listing.orphaned_directories, vec!["stale"]);
+    }
+
+    #[test]
+    fn deletes_untouched_layers() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+
+        for name in &[layer_name!("ruby"), layer_name!("old-ruby")] {
+            fs::create_dir(context.layer_path(name)).unwrap();
+            fs::write(context.layer_content_metadata_path(name), "").unwrap();
+        }
+        fs::create_dir(context.layer_path(&layer_name!("stale"))).unwrap();
+        fs::create_dir(context.layer_path(&layer_name!("broken"))).unwrap();
+        fs::write(
+            context.layer_content_metadata_path(&layer_name!("broken")),
+            "not toml",
+        )
+        .unwrap();
+        for name in &[layer_name!("ruby"), layer_name!("old-ruby")] {
+            fs::write(context.sbom_path(name, SbomFormat::CycloneDxJson), "{}").unwrap();
+        }
+        context.layer_tracker.touch(&layer_name!("ruby"));
+
+        assert_eq!(
+            context.delete_untouched_layers().unwrap(),
+            vec!["broken", "old-ruby", "stale"]
+        );
+
+        let listing = context.layers::<GenericMetadata>().unwrap();
+        assert_eq!(listing.layers.len(), 1);
+        assert_eq!(listing.layers[0].name, layer_name!("ruby"));
+        assert!(listing.unreadable_metadata.is_empty());
+        assert!(listing.orphaned_directories.is_empty());
+        assert!(context
+            .sbom_path(&layer_name!("ruby"), SbomFormat::CycloneDxJson)
+            .exists());
+        assert!(!context
+            .sbom_path(&layer_name!("old-ruby"), SbomFormat::CycloneDxJson)
+            .exists());
+    }
+
+    #[test]
+    fn writing_metadata_touches_layer() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+
+        context
+            .write_layer_content_metadata(&layer_name!("ruby"), &LayerContentMetadata::new(1))
+            .unwrap();
+
+        assert_eq!(context.layer_tracker.touched(), vec![layer_name!("ruby")]);
+    }
+
+    #[test]
+    fn writing_layer_env_keeps_layer() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+        context
+            .layer_tracker
+            .set_cleanup_policy(LayerCleanupPolicy::DeleteUntouched);
+
+        context
+            .write_layer_env(
+                &layer_name!("ruby"),
+                &LayerEnv::new().chainable_insert(
+                    Scope::All,
+                    ModificationBehavior::Override,
+                    "BUNDLE_PATH",
+                    "/layers/ruby",
+                ),
+            )
+            .unwrap();
+
+        assert!(context.delete_untouched_layers().unwrap().is_empty());
+        assert!(context
+            .layer_path(&layer_name!("ruby"))
+            .join("env")
+            .exists());
+    }
+}
@@ -0,0 +1,976 @@
+use std::fmt::{Debug, Display};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::build::BuildContext;
+use crate::data::layer_content_metadata::LayerContentMetadata;
+use crate::layer_lifecycle::{LayerLifecycle, ValidateResult};
+use crate::platform::Platform;
+
+/// The format of an archive downloaded by [`DownloadLayerLifecycle`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    TarGz,
+    TarXz,
+    Zip,
+}
+
+/// A layer lifecycle that downloads an archive, verifies its SHA-256 checksum and extracts it
+/// into the layer.
+///
+/// The URL and checksum are stored in the layer metadata. The layer is kept as long as both
+/// match and recreated otherwise. In addition to `http://` and `https://` URLs, `file://` URLs
+/// are supported. Responses with a non-successful HTTP status are treated as errors. Archive
+/// entries, symlinks and hard links that would resolve outside of the layer are rejected.
+///
+/// This lifecycle is only available with the `download` feature.
+///
+/// # Examples
+/// ```no_run
+/// use libcnb::layer_lifecycle::{
+///     execute_layer_lifecycle, ArchiveFormat, DownloadError, DownloadLayerLifecycle,
+/// };
+/// use libcnb::{layer_name, GenericBuildContext};
+///
+/// fn build(context: GenericBuildContext) -> libcnb::Result<(), DownloadError> {
+///     let layer_lifecycle = DownloadLayerLifecycle::new(
+///         "https://example.com/ruby-3.0.1.tar.gz",
+///         "5f5bd0e6d8e5d8a3a0c3a0d1c9a1a0f9e5b4c3d2e1f0a9b8c7d6e5f4a3b2c1d0",
+///         ArchiveFormat::TarGz,
+///     )
+///     .strip_components(1)
+///     .launch(true)
+///     .cache(true);
+///
+///     execute_layer_lifecycle(&layer_name!("ruby"), layer_lifecycle, &context)?;
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct DownloadLayerLifecycle {
+    url: String,
+    sha256: String,
+    format: ArchiveFormat,
+    strip_components: usize,
+    launch: bool,
+    build: bool,
+    cache: bool,
+}
+
+impl DownloadLayerLifecycle {
+    pub fn new(url: impl Into<String>, sha256: impl Into<String>, format: ArchiveFormat) -> Self {
+        DownloadLayerLifecycle {
+            url: url.into(),
+            sha256: sha256.into().to_lowercase(),
+            format,
+            strip_components: 0,
+            launch: false,
+            build: false,
+            cache: false,
+        }
+    }
+
+    /// Strips the given number of leading path components from archive entries, like
+    /// `tar --strip-components`. Entries with fewer components are skipped.
+    pub fn strip_components(mut self, strip_components: usize) -> Self {
+        self.strip_components = strip_components;
+        self
+    }
+
+    pub fn launch(mut self, launch: bool) -> Self {
+        self.launch = launch;
+        self
+    }
+
+    pub fn build(mut self, build: bool) -> Self {
+        self.build = build;
+        self
+    }
+
+    pub fn cache(mut self, cache: bool) -> Self {
+        self.cache = cache;
+        self
+    }
+}
+
+/// Layer metadata of [`DownloadLayerLifecycle`].
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct DownloadLayerMetadata {
+    pub url: String,
+    pub sha256: String,
+}
+
+impl<P: Platform, BM, E: From<DownloadError> + Debug + Display>
+    LayerLifecycle<P, BM, DownloadLayerMetadata, (), E> for DownloadLayerLifecycle
+{
+    fn create(
+        &self,
+        layer_path: &Path,
+        _build_context: &BuildContext<P, BM>,
+    ) -> Result<LayerContentMetadata<DownloadLayerMetadata>, E> {
+        let download_path = download_path(layer_path);
+
+        let result = download(&self.url, &download_path)
+            .and_then(|actual_sha256| {
+                if actual_sha256 == self.sha256 {
+                    Ok(())
+                } else {
+                    Err(DownloadError::ChecksumMismatch {
+                        url: self.url.clone(),
+                        expected: self.sha256.clone(),
+                        actual: actual_sha256,
+                    })
+                }
+            })
+            .and_then(|_| {
+                extract(
+                    &download_path,
+                    layer_path,
+                    self.format,
+                    self.strip_components,
+                )
+            });
+
+        // The downloaded archive is not needed anymore, regardless of the outcome.
+        let _ = fs::remove_file(&download_path);
+        result?;
+
+        Ok(LayerContentMetadata {
+            launch: self.launch,
+            build: self.build,
+            cache: self.cache,
+            metadata: DownloadLayerMetadata {
+                url: self.url.clone(),
+                sha256: self.sha256.clone(),
+            },
+        })
+    }
+
+    fn validate(
+        &self,
+        _layer_path: &Path,
+        layer_content_metadata: &LayerContentMetadata<DownloadLayerMetadata>,
+        _build_context: &BuildContext<P, BM>,
+    ) -> ValidateResult {
+        if layer_content_metadata.metadata.url == self.url
+            && layer_content_metadata.metadata.sha256 == self.sha256
+        {
+            ValidateResult::KeepLayer
+        } else {
+            ValidateResult::RecreateLayer
+        }
+    }
+}
+
+// The archive is downloaded next to the layer directory to not end up in the layer itself.
+fn download_path(layer_path: &Path) -> PathBuf {
+    let file_name = layer_path
+        .file_name()
+        .map(|file_name| file_name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    layer_path.with_file_name(format!("{}.libcnb-download", file_name))
+}
+
+/// Downloads the given URL to `destination`, returning the SHA-256 checksum of the contents.
+fn download(url: &str, destination: &Path) -> Result<String, DownloadError> {
+    let mut reader: Box<dyn Read> = match file_url_path(url)? {
+        Some(path) => Box::new(fs::File::open(path)?),
+        None => Box::new(
+            ureq::get(url)
+                .call()
+                .map_err(|error| DownloadError::HttpError(url.to_string(), Box::new(error)))?
+                .into_reader(),
+        ),
+    };
+
+    let mut file = fs::File::create(destination)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0; 64 * 1024];
+
+    loop {
+        let bytes_read = reader.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+
+        hasher.update(&buffer[..bytes_read]);
+        file.write_all(&buffer[..bytes_read])?;
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Returns the local path of a `file://` URL, percent-decoded, and `None` for other URLs.
+fn file_url_path(url: &str) -> Result<Option<PathBuf>, DownloadError> {
+    if !url.starts_with("file://") {
+        return Ok(None);
+    }
+
+    url::Url::parse(url)
+        .ok()
+        .and_then(|parsed_url| parsed_url.to_file_path().ok())
+        .map(Some)
+        .ok_or_else(|| DownloadError::InvalidFileUrl(url.to_string()))
+}
+
+fn extract(
+    archive_path: &Path,
+    destination: &Path,
+    format: ArchiveFormat,
+    strip_components: usize,
+) -> Result<(), DownloadError> {
+    let file = fs::File::open(archive_path)?;
+    // Links are resolved against the canonical path to detect entries that escape it.
+    let destination = &fs::canonicalize(destination)?;
+
+    match format {
+        ArchiveFormat::TarGz => extract_tar(
+            flate2::read::GzDecoder::new(file),
+            destination,
+            strip_components,
+        ),
+        ArchiveFormat::TarXz => extract_tar(
+            xz2::read::XzDecoder::new(file),
+            destination,
+            strip_components,
+        ),
+        ArchiveFormat::Zip => extract_zip(file, destination, strip_components),
+    }
+}
+
+fn extract_tar(
+    reader: impl Read,
+    destination: &Path,
+    strip_components: usize,
+) -> Result<(), DownloadError> {
+    let mut archive = tar::Archive::new(reader);
+    archive.set_preserve_permissions(true);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let entry_path = entry.path()?.into_owned();
+
+        let relative_path = match stripped_path(&entry_path, strip_components)? {
+            Some(relative_path) => relative_path,
+            None => continue,
+        };
+
+        let target_path = prepare_target_path(destination, &relative_path, &entry_path)?;
+        let entry_type = entry.header().entry_type();
+        let link_name = entry.link_name()?.map(|link_name| link_name.into_owned());
+
+        match link_name {
+            Some(link_target) if entry_type.is_symlink() => {
+                ensure_link_target_within(&relative_path, &link_target, &entry_path)?;
+                entry.unpack(&target_path)?;
+                ensure_within(destination, &target_path, &entry_path)?;
+            }
+            // Hard link names are archive paths, `unpack` would resolve them against the current
+            // working directory instead.
+            Some(link_name) if entry_type.is_hard_link() => {
+                let source_path = stripped_path(&link_name, strip_components)?
+                    .map(|source_path| destination.join(source_path))
+                    .ok_or_else(|| {
+                        DownloadError::UnsafeLinkTarget(entry_path.clone(), link_name.clone())
+                    })?;
+
+                ensure_within(destination, &source_path, &entry_path)?;
+                fs::hard_link(&source_path, &target_path)?;
+            }
+            _ => {
+                entry.unpack(&target_path)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn extract_zip(
+    file: fs::File,
+    destination: &Path,
+    strip_components: usize,
+) -> Result<(), DownloadError> {
+    let mut archive = zip::ZipArchive::new(file)?;
+
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index)?;
+        let entry_path = PathBuf::from(entry.name());
+
+        let relative_path = match stripped_path(&entry_path, strip_components)? {
+            Some(relative_path) => relative_path,
+            None => continue,
+        };
+
+        let target_path = prepare_target_path(destination, &relative_path, &entry_path)?;
+
+        if entry.is_dir() {
+            fs::create_dir_all(&target_path)?;
+            continue;
+        }
+
+        // Zip archives store the target of a symlink as its contents.
+        if is_zip_symlink(entry.unix_mode()) {
+            let mut link_target = String::new();
+            entry.read_to_string(&mut link_target)?;
+            let link_target = PathBuf::from(link_target);
+
+            ensure_link_target_within(&relative_path, &link_target, &entry_path)?;
+            create_symlink(&link_target, &target_path)?;
+            ensure_within(destination, &target_path, &entry_path)?;
+            continue;
+        }
+
+        io::copy(&mut entry, &mut fs::File::create(&target_path)?)?;
+
+        #[cfg(target_family = "unix")]
+        if let Some(mode) = entry.unix_mode() {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&target_path, fs::Permissions::from_mode(mode))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn is_zip_symlink(unix_mode: Option<u32>) -> bool {
+    const S_IFMT: u32 = 0o170000;
+    const S_IFLNK: u32 = 0o120000;
+
+    matches!(unix_mode, Some(mode) if mode & S_IFMT == S_IFLNK)
+}
+
+#[cfg(target_family = "unix")]
+fn create_symlink(link_target: &Path, path: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(link_target, path)
+}
+
+#[cfg(not(target_family = "unix"))]
+fn create_symlink(link_target: &Path, path: &Path) -> io::Result<()> {
+    fs::write(path, link_target.to_string_lossy().as_bytes())
+}
+
+/// Creates the parent directories of an archive entry and returns the path to extract it to.
+///
+/// Each existing parent is checked to resolve within `destination`, so entries can't be written
+/// through symlinks extracted earlier. A symlink at the entry path itself is removed to be
+/// replaced instead of written through.
+fn prepare_target_path(
+    destination: &Path,
+    relative_path: &Path,
+    entry_path: &Path,
+) -> Result<PathBuf, DownloadError> {
+    let mut target_path = destination.to_path_buf();
+    let mut components = relative_path.components().peekable();
+
+    while let Some(component) = components.next() {
+        target_path.push(component);
+
+        let metadata = match fs::symlink_metadata(&target_path) {
+            Ok(metadata) => metadata,
+            Err(io_error) if io_error.kind() == io::ErrorKind::NotFound => {
+                if components.peek().is_some() {
+                    fs::create_dir(&target_path)?;
+                }
+                continue;
+            }
+            Err(io_error) => return Err(io_error.into()),
+        };
+
+        if components.peek().is_some() {
+            ensure_within(destination, &target_path, entry_path)?;
+        } else if metadata.file_type().is_symlink() {
+            fs::remove_file(&target_path)?;
+        }
+    }
+
+    Ok(target_path)
+}
+
+/// Checks that `path` resolves to a location within `destination`, following symlinks.
+///
+/// Paths that do not exist, like dangling symlinks, are accepted since nothing can be written
+/// through them without passing this check again.
+fn ensure_within(destination: &Path, path: &Path, entry_path: &Path) -> Result<(), DownloadError> {
+    match fs::canonicalize(path) {
+        Ok(resolved_path) if !resolved_path.starts_with(destination) => {
+            // Do not leave a link behind that points outside of the layer.
+            if fs::symlink_metadata(path)?.file_type().is_symlink() {
+                fs::remove_file(path)?;
+            }
+
+            Err(DownloadError::UnsafeLinkTarget(
+                entry_path.to_path_buf(),
+                resolved_path,
+            ))
+        }
+        Ok(_) => Ok(()),
+        Err(io_error) if io_error.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(io_error) => Err(io_error.into()),
+    }
+}
+
+/// Checks that a symlink at `relative_path` pointing to `link_target` stays within the
+/// destination, without resolving any of the links involved.
+fn ensure_link_target_within(
+    relative_path: &Path,
+    link_target: &Path,
+    entry_path: &Path,
+) -> Result<(), DownloadError> {
+    let mut depth = relative_path.components().count().saturating_sub(1);
+
+    for component in link_target.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir if depth > 0 => depth -= 1,
+            _ => {
+                return Err(DownloadError::UnsafeLinkTarget(
+                    entry_path.to_path_buf(),
+                    link_target.to_path_buf(),
+                ))
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Strips leading components from an archive entry path.
+///
+/// Returns `None` for entries that are stripped entirely and an error for entries that would
+/// escape the destination directory.
+fn stripped_path(path: &Path, strip_components: usize) -> Result<Option<PathBuf>, DownloadError> {
+    let mut components = vec![];
+
+    for component in path.components() {
+        match component {
+            Component::Normal(component) => components.push(component),
+            Component::CurDir => {}
+            _ => return Err(DownloadError::UnsafeArchiveEntry(path.to_path_buf())),
+        }
+    }
+
+    if components.len() <= strip_components {
+        Ok(None)
+    } else {
+        Ok(Some(components[strip_components..].iter().collect()))
+    }
+}
+
+/// An error that occurred while downloading or extracting an archive.
+#[derive(thiserror::Error, Debug)]
+pub enum DownloadError {
+    #[error("Could not download `{0}`: {1}")]
+    HttpError(String, Box<ureq::Error>),
+
+    #[error("Checksum mismatch for `{url}`: expected {expected}, but got {actual}")]
+    ChecksumMismatch {
+        url: String,
+        expected: String,
+        actual: String,
+    },
+
+    #[error("Archive entry `{}` would be extracted outside of the layer", .0.display())]
+    UnsafeArchiveEntry(PathBuf),
+
+    #[error("Archive entry `{}` links to `{}` outside of the layer", .0.display(), .1.display())]
+    UnsafeLinkTarget(PathBuf, PathBuf),
+
+    #[error("Invalid file URL: `{0}`")]
+    InvalidFileUrl(String),
+
+    #[error("Could not read zip archive: {0}")]
+    ZipError(#[from] zip::result::ZipError),
+
+    #[error("IO error while downloading or extracting: {0}")]
+    IoError(#[from] io::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use crate::generic::GenericBuildContext;
+    use crate::layer_lifecycle::execute_layer_lifecycle;
+    use crate::layer_name;
+    use crate::test_support::build_context;
+
+    fn write_tar_gz(path: &Path) -> String {
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            fs::File::create(path).unwrap(),
+            flate2::Compression::default(),
+        ));
+
+        for (entry_path, contents) in &[
+            ("ruby-3.0.1/bin/ruby", "#!/bin/sh"),
+            ("ruby-3.0.1/lib/ruby.so", "lib"),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o755);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, entry_path, contents.as_bytes())
+                .unwrap();
+        }
+
+        builder.into_inner().unwrap().finish().unwrap();
+        sha256(path)
+    }
+
+    fn write_zip(path: &Path) -> String {
+        let mut writer = zip::ZipWriter::new(fs::File::create(path).unwrap());
+        writer
+            .start_file("node-v16/bin/node", Default::default())
+            .unwrap();
+        writer.write_all(b"node").unwrap();
+        writer.finish().unwrap();
+        sha256(path)
+    }
+
+    enum TarEntry {
+        File(&'static str, &'static str),
+        Symlink(&'static str, String),
+        HardLink(&'static str, &'static str),
+        /// A file whose path is written as-is, `tar::Builder` would reject paths containing `..`.
+        UncheckedFile(&'static str, &'static str),
+    }
+
+    fn write_tar_gz_entries(path: &Path, entries: Vec<TarEntry>) -> String {
+        let encoder = flate2::write::GzEncoder::new(
+            fs::File::create(path).unwrap(),
+            flate2::Compression::default(),
+        );
+        append_tar_entries(encoder, entries).finish().unwrap();
+        sha256(path)
+    }
+
+    fn write_tar_xz_entries(path: &Path, entries: Vec<TarEntry>) -> String {
+        let encoder = xz2::write::XzEncoder::new(fs::File::create(path).unwrap(), 6);
+        append_tar_entries(encoder, entries).finish().unwrap();
+        sha256(path)
+    }
+
+    fn append_tar_entries<W: Write>(writer: W, entries: Vec<TarEntry>) -> W {
+        let mut builder = tar::Builder::new(writer);
+
+        for entry in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_mode(0o644);
+            header.set_size(0);
+
+            match entry {
+                TarEntry::File(entry_path, contents) => {
+                    header.set_size(contents.len() as u64);
+                    header.set_cksum();
+                    builder
+                        .append_data(&mut header, entry_path, contents.as_bytes())
+                        .unwrap();
+                }
+                TarEntry::Symlink(entry_path, link_target) => {
+                    header.set_entry_type(tar::EntryType::Symlink);
+                    builder
+                        .append_link(&mut header, entry_path, link_target)
+                        .unwrap();
+                }
+                TarEntry::HardLink(entry_path, link_name) => {
+                    header.set_entry_type(tar::EntryType::Link);
+                    builder
+                        .append_link(&mut header, entry_path, link_name)
+                        .unwrap();
+                }
+                TarEntry::UncheckedFile(entry_path, contents) => {
+                    header.as_gnu_mut().unwrap().name[..entry_path.len()]
+                        .copy_from_slice(entry_path.as_bytes());
+                    header.set_size(contents.len() as u64);
+                    header.set_cksum();
+                    builder.append(&header, contents.as_bytes()).unwrap();
+                }
+            }
+        }
+
+        builder.into_inner().unwrap()
+    }
+
+    fn execute_archive(
+        context: &GenericBuildContext,
+        archive_path: &Path,
+        sha256: String,
+        format: ArchiveFormat,
+    ) -> crate::Result<(), DownloadError> {
+        execute(
+            context,
+            DownloadLayerLifecycle::new(
+                format!("file://{}", archive_path.display()),
+                sha256,
+                format,
+            ),
+        )
+    }
+
+    fn sha256(path: &Path) -> String {
+        format!("{:x}", Sha256::digest(&fs::read(path).unwrap()))
+    }
+
+    fn execute(
+        context: &GenericBuildContext,
+        layer_lifecycle: DownloadLayerLifecycle,
+    ) -> crate::Result<(), DownloadError> {
+        execute_layer_lifecycle(&layer_name!("download"), layer_lifecycle, context)
+    }
+
+    #[test]
+    fn downloads_and_extracts_tar_gz() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+        let archive_path = temp_dir.path().join("ruby.tar.gz");
+        let sha256 = write_tar_gz(&archive_path);
+        let url = format!("file://{}", archive_path.display());
+
+        execute(
+            &context,
+            DownloadLayerLifecycle::new(&url, &sha256, ArchiveFormat::TarGz)
+                .strip_components(1)
+                .launch(true),
+        )
+        .unwrap();
+
+        let layer_path = context.layer_path(&layer_name!("download"));
+        assert_eq!(
+            fs::read_to_string(layer_path.join("bin/ruby")).unwrap(),
+            "#!/bin/sh"
+        );
+        assert!(layer_path.join("lib/ruby.so").exists());
+        assert!(!download_path(&layer_path).exists());
+
+        let layer_content_metadata = context
+            .read_layer_content_metadata::<DownloadLayerMetadata>(&layer_name!("download"))
+            .unwrap()
+            .unwrap();
+        assert!(layer_content_metadata.launch);
+        assert_eq!(
+            layer_content_metadata.metadata,
+            DownloadLayerMetadata { url, sha256 }
+        );
+    }
+
+    #[test]
+    fn downloads_and_extracts_zip() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+        let archive_path = temp_dir.path().join("node.zip");
+        let sha256 = write_zip(&archive_path);
+
+        execute(
+            &context,
+            DownloadLayerLifecycle::new(
+                format!("file://{}", archive_path.display()),
+                sha256,
+                ArchiveFormat::Zip,
+            )
+            .strip_components(1),
+        )
+        .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(
+                context
+                    .layer_path(&layer_name!("download"))
+                    .join("bin/node")
+            )
+            .unwrap(),
+            "node"
+        );
+    }
+
+    #[test]
+    fn downloads_and_extracts_tar_xz() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+        let archive_path = temp_dir.path().join("ruby.tar.xz");
+        let sha256 = write_tar_xz_entries(
+            &archive_path,
+            vec![
+                TarEntry::File("README", "stripped"),
+                TarEntry::File("ruby-3.0.1/bin/ruby", "#!/bin/sh"),
+                TarEntry::File("ruby-3.0.1/lib/ruby.so", "lib"),
+            ],
+        );
+
+        execute(
+            &context,
+            DownloadLayerLifecycle::new(
+                format!("file://{}", archive_path.display()),
+                sha256,
+                ArchiveFormat::TarXz,
+            )
+            .strip_components(1),
+        )
+        .unwrap();
+
+        let layer_path = context.layer_path(&layer_name!("download"));
+        assert_eq!(
+            fs::read_to_string(layer_path.join("bin/ruby")).unwrap(),
+            "#!/bin/sh"
+        );
+        assert!(layer_path.join("lib/ruby.so").exists());
+        assert!(!layer_path.join("README").exists());
+        assert!(!layer_path.join("ruby-3.0.1").exists());
+    }
+
+    #[test]
+    fn rejects_tar_xz_entries_outside_of_layer() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+        let archive_path = temp_dir.path().join("evil.tar.xz");
+        let sha256 = write_tar_xz_entries(
+            &archive_path,
+            vec![
+                TarEntry::File("ruby-3.0.1/bin/ruby", "#!/bin/sh"),
+                TarEntry::UncheckedFile("ruby-3.0.1/../../escape", "evil"),
+            ],
+        );
+
+        let result = execute_archive(&context, &archive_path, sha256, ArchiveFormat::TarXz);
+
+        assert!(matches!(
+            result,
+            Err(Error::BuildpackError(DownloadError::UnsafeArchiveEntry(..)))
+        ));
+        assert!(!context.layers_dir.join("escape").exists());
+        assert!(!context.layer_path(&layer_name!("download")).exists());
+    }
+
+    #[test]
+    fn rejects_checksum_mismatch() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+        let archive_path = temp_dir.path().join("ruby.tar.gz");
+        write_tar_gz(&archive_path);
+
+        let result = execute(
+            &context,
+            DownloadLayerLifecycle::new(
+                format!("file://{}", archive_path.display()),
+                "0000",
+                ArchiveFormat::TarGz,
+            ),
+        );
+
+        assert!(matches!(
+            result,
+            Err(Error::BuildpackError(
+                DownloadError::ChecksumMismatch { .. }
+            ))
+        ));
+        assert!(!context.layer_path(&layer_name!("download")).exists());
+    }
+
+    #[test]
+    fn rejects_entries_outside_of_layer() {
+        assert!(matches!(
+            stripped_path(Path::new("../etc/passwd"), 0),
+            Err(DownloadError::UnsafeArchiveEntry(_))
+        ));
+        assert_eq!(stripped_path(Path::new("ruby/bin"), 2).unwrap(), None);
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn extracts_tar_links_within_layer() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+        let archive_path = temp_dir.path().join("ruby.tar.gz");
+        let sha256 = write_tar_gz_entries(
+            &archive_path,
+            vec![
+                TarEntry::File("ruby-3.0.1/bin/ruby", "ruby"),
+                TarEntry::Symlink("ruby-3.0.1/bin/ruby3", String::from("ruby")),
+                TarEntry::Symlink("ruby-3.0.1/lib/bin", String::from("../bin")),
+                TarEntry::HardLink("ruby-3.0.1/bin/ruby-hard", "ruby-3.0.1/bin/ruby"),
+            ],
+        );
+
+        execute(
+            &context,
+            DownloadLayerLifecycle::new(
+                format!("file://{}", archive_path.display()),
+                sha256,
+                ArchiveFormat::TarGz,
+            )
+            .strip_components(1),
+        )
+        .unwrap();
+
+        let layer_path = context.layer_path(&layer_name!("download"));
+        for path in &["bin/ruby3", "lib/bin/ruby", "bin/ruby-hard"] {
+            assert_eq!(fs::read_to_string(layer_path.join(path)).unwrap(), "ruby");
+        }
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn rejects_tar_symlink_outside_of_layer() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+        let outside_dir = tempfile::tempdir().unwrap();
+        let archive_path = temp_dir.path().join("evil.tar.gz");
+        let sha256 = write_tar_gz_entries(
+            &archive_path,
+            vec![
+                TarEntry::Symlink("a", outside_dir.path().display().to_string()),
+                TarEntry::File("a/passwd", "evil"),
+            ],
+        );
+
+        let result = execute_archive(&context, &archive_path, sha256, ArchiveFormat::TarGz);
+
+        assert!(matches!(
+            result,
+            Err(Error::BuildpackError(DownloadError::UnsafeLinkTarget(..)))
+        ));
+        assert!(!outside_dir.path().join("passwd").exists());
+        assert!(!context.layer_path(&layer_name!("download")).exists());
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn rejects_tar_symlink_escaping_through_other_symlink() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+        let archive_path = temp_dir.path().join("evil.tar.gz");
+        // `up` looks like it stays within the layer, but `root/..` resolves to its parent.
+        let sha256 = write_tar_gz_entries(
+            &archive_path,
+            vec![
+                TarEntry::Symlink("root", String::from(".")),
+                TarEntry::Symlink("up", String::from("root/..")),
+                TarEntry::File("up/evil", "evil"),
+            ],
+        );
+
+        let result = execute_archive(&context, &archive_path, sha256, ArchiveFormat::TarGz);
+
+        assert!(matches!(
+            result,
+            Err(Error::BuildpackError(DownloadError::UnsafeLinkTarget(..)))
+        ));
+        assert!(!context.layers_dir.join("evil").exists());
+    }
+
+    #[test]
+    fn rejects_tar_hard_link_outside_of_layer() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+        let archive_path = temp_dir.path().join("evil.tar.gz");
+        let sha256 = write_tar_gz_entries(
+            &archive_path,
+            vec![TarEntry::HardLink("passwd", "../../etc/passwd")],
+        );
+
+        let result = execute_archive(&context, &archive_path, sha256, ArchiveFormat::TarGz);
+
+        assert!(matches!(
+            result,
+            Err(Error::BuildpackError(DownloadError::UnsafeArchiveEntry(..)))
+        ));
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn extracts_zip_symlinks_within_layer() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+        let archive_path = temp_dir.path().join("node.zip");
+
+        let mut writer = zip::ZipWriter::new(fs::File::create(&archive_path).unwrap());
+        writer.start_file("bin/node", Default::default()).unwrap();
+        writer.write_all(b"node").unwrap();
+        writer
+            .add_symlink("bin/nodejs", "node", Default::default())
+            .unwrap();
+        writer.finish().unwrap();
+        let sha256 = sha256(&archive_path);
+
+        execute_archive(&context, &archive_path, sha256, ArchiveFormat::Zip).unwrap();
+
+        let link_path = context
+            .layer_path(&layer_name!("download"))
+            .join("bin/nodejs");
+        assert!(fs::symlink_metadata(&link_path)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(fs::read_to_string(link_path).unwrap(), "node");
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn rejects_zip_symlink_outside_of_layer() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+        let outside_dir = tempfile::tempdir().unwrap();
+        let archive_path = temp_dir.path().join("evil.zip");
+
+        let mut writer = zip::ZipWriter::new(fs::File::create(&archive_path).unwrap());
+        writer
+            .add_symlink(
+                "a",
+                outside_dir.path().display().to_string(),
+                Default::default(),
+            )
+            .unwrap();
+        writer.start_file("a/passwd", Default::default()).unwrap();
+        writer.write_all(b"evil").unwrap();
+        writer.finish().unwrap();
+        let sha256 = sha256(&archive_path);
+
+        let result = execute_archive(&context, &archive_path, sha256, ArchiveFormat::Zip);
+
+        assert!(matches!(
+            result,
+            Err(Error::BuildpackError(DownloadError::UnsafeLinkTarget(..)))
+        ));
+        assert!(!outside_dir.path().join("passwd").exists());
+        assert!(!context.layer_path(&layer_name!("download")).exists());
+    }
+
+    #[test]
+    fn decodes_file_urls() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+        let archive_dir = temp_dir.path().join("with space");
+        fs::create_dir_all(&archive_dir).unwrap();
+        let archive_path = archive_dir.join("ruby.tar.gz");
+        let sha256 = write_tar_gz(&archive_path);
+
+        execute(
+            &context,
+            DownloadLayerLifecycle::new(
+                format!(
+                    "file://{}",
+                    archive_path.display().to_string().replace(' ', "%20")
+                ),
+                sha256,
+                ArchiveFormat::TarGz,
+            ),
+        )
+        .unwrap();
+
+        assert!(context
+            .layer_path(&layer_name!("download"))
+            .join("ruby-3.0.1/bin/ruby")
+            .exists());
+        assert!(matches!(
+            file_url_path("file://remote-host/ruby.tar.gz"),
+            Err(DownloadError::InvalidFileUrl(_))
+        ));
+        assert_eq!(file_url_path("https://example.com").unwrap(), None);
+    }
+}
@@ -0,0 +1,542 @@
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Debug, Display};
+use std::marker::PhantomData;
+use std::num::NonZeroUsize;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc;
+use std::thread;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::build::BuildContext;
+use crate::data::layer::LayerName;
+use crate::error::Error;
+use crate::layer_lifecycle::{execute_layer_lifecycle, LayerLifecycle, LayerLifecycleError};
+use crate::platform::Platform;
+
+type LayerJob<'a, P, BM, E> =
+    Box<dyn FnOnce(&BuildContext<P, BM>) -> Result<Box<dyn Any + Send>, Error<E>> + Send + 'a>;
+
+struct LayerNode<'a, P: Platform, BM, E: Debug + Display> {
+    layer_name: LayerName,
+    dependencies: Vec<LayerName>,
+    job: LayerJob<'a, P, BM, E>,
+}
+
+/// A set of named layer lifecycles with dependencies between them.
+///
+/// Layer lifecycles that do not depend on each other are executed in parallel on a bounded number
+/// of threads. A layer lifecycle is only started after all of its dependencies finished
+/// successfully. Dependencies only define the execution order, a lifecycle that needs the output
+/// of another lifecycle has to be executed separately after the graph.
+///
+/// # Examples
+/// ```no_run
+/// use libcnb::layer_lifecycle::{LayerLifecycle, LayerLifecycleGraph};
+/// use libcnb::layer_name;
+/// use libcnb::data::layer_content_metadata::LayerContentMetadata;
+/// use libcnb::{GenericBuildContext, GenericMetadata, GenericPlatform};
+/// use std::path::Path;
+///
+/// struct DownloadLayerLifecycle;
+///
+/// impl LayerLifecycle<GenericPlatform, GenericMetadata, GenericMetadata, (), std::io::Error>
+///     for DownloadLayerLifecycle
+/// {
+///     fn create(
+///         &self,
+///         layer_path: &Path,
+///         build_context: &GenericBuildContext,
+///     ) -> Result<LayerContentMetadata<GenericMetadata>, std::io::Error> {
+///         // ...
+///         Ok(LayerContentMetadata::default())
+///     }
+/// }
+///
+/// fn build(context: GenericBuildContext) -> libcnb::Result<(), std::io::Error> {
+///     let mut graph = LayerLifecycleGraph::new();
+///     let runtime = graph.add(&layer_name!("runtime"), &[], DownloadLayerLifecycle);
+///     graph.add(&layer_name!("tooling"), &[], DownloadLayerLifecycle);
+///     graph.add(&layer_name!("cache"), &[layer_name!("runtime")], DownloadLayerLifecycle);
+///
+///     let mut outputs = graph.execute(&context)?;
+///     let runtime_output: Option<()> = outputs.take(&runtime);
+///     Ok(())
+/// }
+/// ```
+pub struct LayerLifecycleGraph<'a, P: Platform, BM, E: Debug + Display> {
+    nodes: Vec<LayerNode<'a, P, BM, E>>,
+    max_threads: Option<NonZeroUsize>,
+}
+
+impl<'a, P: Platform + Sync, BM: Sync, E: Debug + Display + Send>
+    LayerLifecycleGraph<'a, P, BM, E>
+{
+    pub fn new() -> Self {
+        LayerLifecycleGraph {
+            nodes: vec![],
+            max_threads: None,
+        }
+    }
+
+    /// Limits the number of layer lifecycles that are executed at the same time.
+    ///
+    /// Defaults to the available parallelism of the machine the buildpack runs on.
+    pub fn max_threads(mut self, max_threads: NonZeroUsize) -> Self {
+        self.max_threads = Some(max_threads);
+        self
+    }
+
+    /// Adds a layer lifecycle for the given layer name that will only be executed after all
+    /// layers in `dependencies` were processed successfully.
+    ///
+    /// The returned [`LayerHandle`] can be used to retrieve the lifecycle's output from the
+    /// [`LayerLifecycleOutputs`] after execution.
+    pub fn add<LM, O, L>(
+        &mut self,
+        layer_name: &LayerName,
+        dependencies: &[LayerName],
+        layer_lifecycle: L,
+    ) -> LayerHandle<O>
+    where
+        LM: Serialize + DeserializeOwned,
+        O: Default + Send + 'static,
+        L: LayerLifecycle<P, BM, LM, O, E> + Send + 'a,
+    {
+        let job_layer_name = layer_name.clone();
+
+        self.nodes.push(LayerNode {
+            layer_name: layer_name.clone(),
+            dependencies: dependencies.to_vec(),
+            job: Box::new(move |context| {
+                execute_layer_lifecycle(&job_layer_name, layer_lifecycle, context)
+                    .map(|output| Box::new(output) as Box<dyn Any + Send>)
+            }),
+        });
+
+        LayerHandle {
+            layer_name: layer_name.clone(),
+            output: PhantomData,
+        }
+    }
+
+    /// Executes all layer lifecycles of this graph.
+    ///
+    /// After the first failure, no new layer lifecycles will be started. Lifecycles that are
+    /// already running will finish before the error is returned. The returned error is an
+    /// [`Error::LayerLifecycleFailed`] that contains the name of the failed layer.
+    pub fn execute(self, context: &BuildContext<P, BM>) -> Result<LayerLifecycleOutputs, Error<E>> {
+        self.validate()?;
+
+        let max_threads = self
+            .max_threads
+            .or_else(|| thread::available_parallelism().ok())
+            .map_or(1, NonZeroUsize::get);
+
+        let mut pending = self.nodes;
+
+        thread::scope(|scope| {
+            let (sender, receiver) = mpsc::channel();
+            let mut completed = HashSet::new();
+            let mut outputs = HashMap::new();
+            let mut failure = None;
+            let mut running = 0;
+
+            loop {
+                while failure.is_none() && running < max_threads {
+                    let ready_index = pending.iter().position(|node| {
+                        node.dependencies
+                            .iter()
+                            .all(|dependency| completed.contains(dependency))
+                    });
+
+                    match ready_index {
+                        Some(index) => {
+                            let LayerNode {
+                                layer_name, job, ..
+                            } = pending.remove(index);
+                            let sender = sender.clone();
+                            running += 1;
+
+                            scope.spawn(move || {
+                                // Panics are caught to ensure that a result is always sent.
+                                // Otherwise, the scheduling loop would wait forever.
+                                let result = panic::catch_unwind(AssertUnwindSafe(|| job(context)));
+                                let _ = sender.send((layer_name, result));
+                            });
+                        }
+                        None => break,
+                    }
+                }
+
+                if running == 0 {
+                    break;
+                }
+
+                let (layer_name, result) = receiver
+                    .recv()
+                    .expect("Sender is kept alive by the scheduling loop");
+                running -= 1;
+
+                match result {
+                    Ok(Ok(output)) => {
+                        completed.insert(layer_name.clone());
+                        outputs.insert(layer_name, output);
+                    }
+                    Ok(Err(error)) => {
+                        if failure.is_none() {
+                            failure =
+                                Some(Error::LayerLifecycleFailed(layer_name, Box::new(error)));
+                        }
+                    }
+                    Err(panic_payload) => panic::resume_unwind(panic_payload),
+                }
+            }
+
+            match failure {
+                Some(error) => Err(error),
+                None => Ok(LayerLifecycleOutputs { outputs }),
+            }
+        })
+    }
+
+    fn validate(&self) -> Result<(), LayerLifecycleError> {
+        let mut layer_names = HashSet::new();
+        for node in &self.nodes {
+            if !layer_names.insert(&node.layer_name) {
+                return Err(LayerLifecycleError::DuplicateLayer(node.layer_name.clone()));
+            }
+        }
+
+        for node in &self.nodes {
+            for dependency in &node.dependencies {
+                if !layer_names.contains(dependency) {
+                    return Err(LayerLifecycleError::UnknownLayerDependency(
+                        node.layer_name.clone(),
+                        dependency.clone(),
+                    ));
+                }
+            }
+        }
+
+        // Repeatedly resolve all layers whose dependencies are resolved. Whatever is left
+        // afterwards is part of, or depends on, a cycle.
+        let mut resolved: HashSet<&LayerName> = HashSet::new();
+        loop {
+            let newly_resolved: Vec<&LayerName> = self
+                .nodes
+                .iter()
+                .filter(|node| !resolved.contains(&node.layer_name))
+                .filter(|node| {
+                    node.dependencies
+                        .iter()
+                        .all(|dependency| resolved.contains(dependency))
+                })
+                .map(|node| &node.layer_name)
+                .collect();
+
+            if newly_resolved.is_empty() {
+                break;
+            }
+
+            resolved.extend(newly_resolved);
+        }
+
+        let unresolved: Vec<LayerName> = self
+            .nodes
+            .iter()
+            .filter(|node| !resolved.contains(&node.layer_name))
+            .map(|node| node.layer_name.clone())
+            .collect();
+
+        if unresolved.is_empty() {
+            Ok(())
+        } else {
+            Err(LayerLifecycleError::CyclicLayerDependencies(unresolved))
+        }
+    }
+}
+
+impl<'a, P: Platform + Sync, BM: Sync, E: Debug + Display + Send> Default
+    for LayerLifecycleGraph<'a, P, BM, E>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Identifies a layer lifecycle added to a [`LayerLifecycleGraph`] and the type of its output.
+pub struct LayerHandle<O> {
+    layer_name: LayerName,
+    output: PhantomData<fn() -> O>,
+}
+
+impl<O> LayerHandle<O> {
+    pub fn layer_name(&self) -> &LayerName {
+        &self.layer_name
+    }
+}
+
+/// Outputs of all layer lifecycles of an executed [`LayerLifecycleGraph`].
+pub struct LayerLifecycleOutputs {
+    outputs: HashMap<LayerName, Box<dyn Any + Send>>,
+}
+
+impl LayerLifecycleOutputs {
+    /// Takes the output of the layer lifecycle identified by `handle`.
+    ///
+    /// Returns `None` if the output was already taken or the handle belongs to another graph.
+    pub fn take<O: 'static>(&mut self, handle: &LayerHandle<O>) -> Option<O> {
+        self.outputs
+            .remove(&handle.layer_name)
+            .and_then(|output| output.downcast::<O>().ok())
+            .map(|output| *output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::layer_content_metadata::LayerContentMetadata;
+    use crate::generic::{GenericBuildContext, GenericMetadata, GenericPlatform};
+    use crate::layer_name;
+    use crate::test_support::build_context;
+    use std::path::Path;
+    use std::str::FromStr;
+    use std::sync::{Arc, Barrier, Mutex};
+
+    struct RecordingLayerLifecycle {
+        name: &'static str,
+        log: Arc<Mutex<Vec<&'static str>>>,
+        barrier: Option<Arc<Barrier>>,
+        fail: bool,
+    }
+
+    impl RecordingLayerLifecycle {
+        fn new(name: &'static str, log: &Arc<Mutex<Vec<&'static str>>>) -> Self {
+            RecordingLayerLifecycle {
+                name,
+                log: log.clone(),
+                barrier: None,
+                fail: false,
+            }
+        }
+    }
+
+    impl LayerLifecycle<GenericPlatform, GenericMetadata, GenericMetadata, String, String>
+        for RecordingLayerLifecycle
+    {
+        fn create(
+            &self,
+            _layer_path: &Path,
+            _build_context: &GenericBuildContext,
+        ) -> Result<LayerContentMetadata<GenericMetadata>, String> {
+            if let Some(barrier) = &self.barrier {
+                barrier.wait();
+            }
+
+            self.log.lock().unwrap().push(self.name);
+
+            if self.fail {
+                Err(String::from("create failed"))
+            } else {
+                Ok(LayerContentMetadata::default())
+            }
+        }
+
+        fn layer_lifecycle_data(
+            &self,
+            _layer_path: &Path,
+            _layer_content_metadata: LayerContentMetadata<GenericMetadata>,
+        ) -> Result<String, String> {
+            Ok(format!("{} output", self.name))
+        }
+    }
+
+    #[test]
+    fn build_context_is_sync() {
+        fn assert_sync<T: Sync>() {}
+        assert_sync::<GenericBuildContext>();
+    }
+
+    #[test]
+    fn executes_independent_layers_in_parallel() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+        let log = Arc::new(Mutex::new(vec![]));
+
+        // Both lifecycles wait for each other. This only succeeds if they run at the same time.
+        let barrier = Arc::new(Barrier::new(2));
+        let mut graph = LayerLifecycleGraph::new().max_threads(NonZeroUsize::new(2).unwrap());
+
+        for name in &["a", "b"] {
+            let mut layer_lifecycle = RecordingLayerLifecycle::new(name, &log);
+            layer_lifecycle.barrier = Some(barrier.clone());
+            graph.add(&LayerName::from_str(name).unwrap(), &[], layer_lifecycle);
+        }
+
+        graph.execute(&context).unwrap();
+        assert_eq!(log.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn executes_dependencies_first() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+        let log = Arc::new(Mutex::new(vec![]));
+
+        let mut graph = LayerLifecycleGraph::new();
+        graph.add(
+            &layer_name!("c"),
+            &[layer_name!("b")],
+            RecordingLayerLifecycle::new("c", &log),
+        );
+        graph.add(
+            &layer_name!("b"),
+            &[layer_name!("a")],
+            RecordingLayerLifecycle::new("b", &log),
+        );
+        graph.add(
+            &layer_name!("a"),
+            &[],
+            RecordingLayerLifecycle::new("a", &log),
+        );
+
+        graph.execute(&context).unwrap();
+        assert_eq!(*log.lock().unwrap(), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn returns_outputs_by_handle() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+        let log = Arc::new(Mutex::new(vec![]));
+
+        let mut graph = LayerLifecycleGraph::new();
+        let a = graph.add(
+            &layer_name!("a"),
+            &[],
+            RecordingLayerLifecycle::new("a", &log),
+        );
+        let b = graph.add(
+            &layer_name!("b"),
+            &[layer_name!("a")],
+            RecordingLayerLifecycle::new("b", &log),
+        );
+
+        let mut outputs = graph.execute(&context).unwrap();
+        assert_eq!(outputs.take(&a), Some(String::from("a output")));
+        assert_eq!(outputs.take(&b), Some(String::from("b output")));
+        assert_eq!(outputs.take(&a), None);
+    }
+
+    #[test]
+    fn reports_failed_layer_and_skips_dependents() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+        let log = Arc::new(Mutex::new(vec![]));
+
+        let mut failing_layer_lifecycle = RecordingLayerLifecycle::new("a", &log);
+        failing_layer_lifecycle.fail = true;
+
+        let mut graph = LayerLifecycleGraph::new();
+        graph.add(&layer_name!("a"), &[], failing_layer_lifecycle);
+        graph.add(
+            &layer_name!("b"),
+            &[layer_name!("a")],
+            RecordingLayerLifecycle::new("b", &log),
+        );
+
+        match graph.execute(&context) {
+            Err(Error::LayerLifecycleFailed(layer_name, error)) => {
+                assert_eq!(layer_name, layer_name!("a"));
+                assert!(matches!(*error, Error::BuildpackError(_)));
+            }
+            _ => panic!("Expected layer lifecycle of layer `a` to fail"),
+        }
+
+        assert_eq!(*log.lock().unwrap(), vec!["a"]);
+    }
+
+    #[test]
+    fn rejects_unknown_dependencies() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+        let log = Arc::new(Mutex::new(vec![]));
+
+        let mut graph = LayerLifecycleGraph::new();
+        graph.add(
+            &layer_name!("a"),
+            &[layer_name!("z")],
+            RecordingLayerLifecycle::new("a", &log),
+        );
+
+        assert!(matches!(
+            graph.execute(&context),
+            Err(Error::LayerLifecycleError(
+                LayerLifecycleError::UnknownLayerDependency(_, _)
+            ))
+        ));
+    }
+
+    #[test]
+    fn rejects_duplicate_layers() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+        let log = Arc::new(Mutex::new(vec![]));
+
+        let mut graph = LayerLifecycleGraph::new();
+        graph.add(
+            &layer_name!("a"),
+            &[],
+            RecordingLayerLifecycle::new("a", &log),
+        );
+        graph.add(
+            &layer_name!("a"),
+            &[],
+            RecordingLayerLifecycle::new("a", &log),
+        );
+
+        assert!(matches!(
+            graph.execute(&context),
+            Err(Error::LayerLifecycleError(
+                LayerLifecycleError::DuplicateLayer(_)
+            ))
+        ));
+    }
+
+    #[test]
+    fn rejects_cyclic_dependencies() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+        let log = Arc::new(Mutex::new(vec![]));
+
+        let mut graph = LayerLifecycleGraph::new();
+        graph.add(
+            &layer_name!("a"),
+            &[],
+            RecordingLayerLifecycle::new("a", &log),
+        );
+        graph.add(
+            &layer_name!("b"),
+            &[layer_name!("a"), layer_name!("c")],
+            RecordingLayerLifecycle::new("b", &log),
+        );
+        graph.add(
+            &layer_name!("c"),
+            &[layer_name!("b")],
+            RecordingLayerLifecycle::new("c", &log),
+        );
+
+        match graph.execute(&context) {
+            Err(Error::LayerLifecycleError(LayerLifecycleError::CyclicLayerDependencies(
+                layer_names,
+            ))) => assert_eq!(layer_names, vec![layer_name!("b"), layer_name!("c")]),
+            _ => panic!("Expected cyclic dependency error"),
+        }
+
+        assert!(log.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,548 @@
+use std::marker::PhantomData;
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::build::BuildContext;
+use crate::buildpack::Buildpack;
+use crate::data::bom::Bom;
+use crate::data::layer::LayerName;
+use crate::data::layer_content_metadata::LayerContentMetadata;
+use crate::error::Error;
+use crate::layer_lifecycle::{
+    execute_layer_lifecycle, LayerLifecycle, MetadataMigrations, MetadataRecoveryStrategy,
+    ValidateResult,
+};
+
+/// A Cloud Native Buildpack layer of buildpack `B`
+///
+/// This is the successor of [`LayerLifecycle`]. Instead of five generic parameters, a layer is
+/// only parameterised with the [`Buildpack`] it belongs to, everything else is expressed with
+/// associated types. Unlike [`LayerLifecycle`], the output type does not need to implement
+/// [`Default`] since [`Layer::output`] always computes it.
+///
+/// Use [`execute_layer`] to execute a layer. Existing [`LayerLifecycle`] implementations can be
+/// used as a layer with [`LayerLifecycleAdapter`].
+///
+/// The functions have the same semantics as their [`LayerLifecycle`] counterparts.
+///
+/// # Examples
+/// ```
+/// use libcnb::data::layer_content_metadata::LayerContentMetadata;
+/// use libcnb::layer_lifecycle::{execute_layer, Layer};
+/// use libcnb::layer_name;
+/// use libcnb::{
+///     BuildContext, Buildpack, DetectOutcome, GenericBuildContext, GenericDetectContext,
+///     GenericMetadata, GenericPlatform,
+/// };
+/// use std::path::{Path, PathBuf};
+///
+/// struct RubyBuildpack;
+///
+/// impl Buildpack for RubyBuildpack {
+///     type Platform = GenericPlatform;
+///     type Metadata = GenericMetadata;
+///     type Error = std::io::Error;
+///
+///     fn detect(&self, context: GenericDetectContext) -> libcnb::Result<DetectOutcome, Self::Error> {
+///         Ok(DetectOutcome::Fail(None))
+///     }
+///
+///     fn build(&self, context: GenericBuildContext) -> libcnb::Result<(), Self::Error> {
+///         let ruby_path = execute_layer(&layer_name!("ruby"), RubyLayer, &context)?;
+///         Ok(())
+///     }
+/// }
+///
+/// struct RubyLayer;
+///
+/// impl Layer<RubyBuildpack> for RubyLayer {
+///     type Metadata = GenericMetadata;
+///     type Output = PathBuf;
+///     type Error = std::io::Error;
+///
+///     fn create(
+///         &self,
+///         layer_path: &Path,
+///         build_context: &BuildContext<GenericPlatform, GenericMetadata>,
+///     ) -> Result<LayerContentMetadata<GenericMetadata>, std::io::Error> {
+///         std::fs::write(layer_path.join("ruby"), "#!/bin/sh")?;
+///         Ok(LayerContentMetadata::default().launch(true))
+///     }
+///
+///     fn output(
+///         &self,
+///         layer_path: &Path,
+///         _layer_content_metadata: LayerContentMetadata<GenericMetadata>,
+///     ) -> Result<PathBuf, std::io::Error> {
+///         Ok(layer_path.join("ruby"))
+///     }
+/// }
+/// ```
+pub trait Layer<B: Buildpack> {
+    type Metadata: Serialize + DeserializeOwned;
+    type Output;
+    type Error: Into<B::Error>;
+
+    /// See [`LayerLifecycle::create`]
+    fn create(
+        &self,
+        layer_path: &Path,
+        build_context: &BuildContext<B::Platform, B::Metadata>,
+    ) -> Result<LayerContentMetadata<Self::Metadata>, Self::Error>;
+
+    /// Computes the output of [`execute_layer`] from the final state of the layer
+    fn output(
+        &self,
+        layer_path: &Path,
+        layer_content_metadata: LayerContentMetadata<Self::Metadata>,
+    ) -> Result<Self::Output, Self::Error>;
+
+    /// See [`LayerLifecycle::recover_from_invalid_metadata`]
+    fn recover_from_invalid_metadata(
+        &self,
+        #[allow(unused_variables)] layer_metadata: &toml::value::Table,
+        #[allow(unused_variables)] build_context: &BuildContext<B::Platform, B::Metadata>,
+    ) -> Result<MetadataRecoveryStrategy<Self::Metadata>, Self::Error> {
+        Ok(MetadataRecoveryStrategy::DeleteLayer)
+    }
+
+    /// See [`LayerLifecycle::metadata_migrations`]
+    fn metadata_migrations(&self) -> Option<MetadataMigrations<Self::Metadata>> {
+        None
+    }
+
+    /// See [`LayerLifecycle::validate`]
+    fn validate(
+        &self,
+        #[allow(unused_variables)] layer_path: &Path,
+        #[allow(unused_variables)] layer_content_metadata: &LayerContentMetadata<Self::Metadata>,
+        #[allow(unused_variables)] build_context: &BuildContext<B::Platform, B::Metadata>,
+    ) -> ValidateResult {
+        ValidateResult::RecreateLayer
+    }
+
+    /// See [`LayerLifecycle::update`]
+    fn update(
+        &self,
+        #[allow(unused_variables)] layer_path: &Path,
+        layer_content_metadata: LayerContentMetadata<Self::Metadata>,
+        #[allow(unused_variables)] build_context: &BuildContext<B::Platform, B::Metadata>,
+    ) -> Result<LayerContentMetadata<Self::Metadata>, Self::Error> {
+        Ok(layer_content_metadata)
+    }
+
+    /// See [`LayerLifecycle::bom`]
+    fn bom(
+        &self,
+        #[allow(unused_variables)] layer_path: &Path,
+        #[allow(unused_variables)] layer_content_metadata: &LayerContentMetadata<Self::Metadata>,
+        #[allow(unused_variables)] build_context: &BuildContext<B::Platform, B::Metadata>,
+    ) -> Bom {
+        Bom::new()
+    }
+
+    fn on_lifecycle_start(&self) {}
+    fn on_keep(&self) {}
+    fn on_update(&self) {}
+    fn on_create(&self) {}
+    fn on_lifecycle_end(&self) {}
+}
+
+/// Executes a [`Layer`] for a given layer name and [`BuildContext`]
+///
+/// The layer is processed exactly like a [`LayerLifecycle`] with [`execute_layer_lifecycle`],
+/// including the guarantees about failures.
+pub fn execute_layer<B: Buildpack, L: Layer<B>>(
+    layer_name: &LayerName,
+    layer: L,
+    context: &BuildContext<B::Platform, B::Metadata>,
+) -> Result<L::Output, Error<B::Error>> {
+    let output = execute_layer_lifecycle(
+        layer_name,
+        LayerAsLayerLifecycle {
+            layer,
+            buildpack: PhantomData,
+        },
+        context,
+    )?;
+
+    // `LayerAsLayerLifecycle::layer_lifecycle_data` only ever returns `Some`, `Option` merely
+    // satisfies the `Default` bound of `LayerLifecycle`.
+    match output {
+        Some(output) => Ok(output),
+        None => unreachable!("layer output is always computed"),
+    }
+}
+
+/// Runs a [`Layer`] through the [`LayerLifecycle`] machinery.
+struct LayerAsLayerLifecycle<B, L> {
+    layer: L,
+    buildpack: PhantomData<fn() -> B>,
+}
+
+impl<B: Buildpack, L: Layer<B>>
+    LayerLifecycle<B::Platform, B::Metadata, L::Metadata, Option<L::Output>, B::Error>
+    for LayerAsLayerLifecycle<B, L>
+{
+    fn create(
+        &self,
+        layer_path: &Path,
+        build_context: &BuildContext<B::Platform, B::Metadata>,
+    ) -> Result<LayerContentMetadata<L::Metadata>, B::Error> {
+        self.layer
+            .create(layer_path, build_context)
+            .map_err(Into::into)
+    }
+
+    fn recover_from_invalid_metadata(
+        &self,
+        layer_metadata: &toml::value::Table,
+        build_context: &BuildContext<B::Platform, B::Metadata>,
+    ) -> Result<MetadataRecoveryStrategy<L::Metadata>, B::Error> {
+        self.layer
+            .recover_from_invalid_metadata(layer_metadata, build_context)
+            .map_err(Into::into)
+    }
+
+    fn metadata_migrations(&self) -> Option<MetadataMigrations<L::Metadata>> {
+        self.layer.metadata_migrations()
+    }
+
+    fn validate(
+        &self,
+        layer_path: &Path,
+        layer_content_metadata: &LayerContentMetadata<L::Metadata>,
+        build_context: &BuildContext<B::Platform, B::Metadata>,
+    ) -> ValidateResult {
+        self.layer
+            .validate(layer_path, layer_content_metadata, build_context)
+    }
+
+    fn update(
+        &self,
+        layer_path: &Path,
+        layer_content_metadata: LayerContentMetadata<L::Metadata>,
+        build_context: &BuildContext<B::Platform, B::Metadata>,
+    ) -> Result<LayerContentMetadata<L::Metadata>, B::Error> {
+        self.layer
+            .update(layer_path, layer_content_metadata, build_context)
+            .map_err(Into::into)
+    }
+
+    fn layer_lifecycle_data(
+        &self,
+        layer_path: &Path,
+        layer_content_metadata: LayerContentMetadata<L::Metadata>,
+    ) -> Result<Option<L::Output>, B::Error> {
+        self.layer
+            .output(layer_path, layer_content_metadata)
+            .map(Some)
+            .map_err(Into::into)
+    }
+
+    fn bom(
+        &self,
+        layer_path: &Path,
+        layer_content_metadata: &LayerContentMetadata<L::Metadata>,
+        build_context: &BuildContext<B::Platform, B::Metadata>,
+    ) -> Bom {
+        self.layer
+            .bom(layer_path, layer_content_metadata, build_context)
+    }
+
+    fn on_lifecycle_start(&self) {
+        self.layer.on_lifecycle_start()
+    }
+
+    fn on_keep(&self) {
+        self.layer.on_keep()
+    }
+
+    fn on_update(&self) {
+        self.layer.on_update()
+    }
+
+    fn on_create(&self) {
+        self.layer.on_create()
+    }
+
+    fn on_lifecycle_end(&self) {
+        self.layer.on_lifecycle_end()
+    }
+}
+
+/// Uses an existing [`LayerLifecycle`] implementation as a [`Layer`]
+///
+/// This allows migrating to [`Layer`] and [`execute_layer`] incrementally.
+///
+/// # Examples
+/// ```
+/// # use libcnb::data::layer_content_metadata::LayerContentMetadata;
+/// # use libcnb::layer_lifecycle::{LayerLifecycle, LayerLifecycleAdapter};
+/// # use libcnb::{
+/// #     Buildpack, DetectOutcome, GenericBuildContext, GenericDetectContext, GenericMetadata,
+/// #     GenericPlatform,
+/// # };
+/// # use std::path::Path;
+/// #
+/// # struct RubyBuildpack;
+/// #
+/// # impl Buildpack for RubyBuildpack {
+/// #     type Platform = GenericPlatform;
+/// #     type Metadata = GenericMetadata;
+/// #     type Error = std::io::Error;
+/// #
+/// #     fn detect(&self, _: GenericDetectContext) -> libcnb::Result<DetectOutcome, Self::Error> {
+/// #         Ok(DetectOutcome::Fail(None))
+/// #     }
+/// #
+/// #     fn build(&self, _: GenericBuildContext) -> libcnb::Result<(), Self::Error> {
+/// #         Ok(())
+/// #     }
+/// # }
+/// #
+/// struct RubyLayerLifecycle;
+///
+/// impl LayerLifecycle<GenericPlatform, GenericMetadata, GenericMetadata, (), std::io::Error>
+///     for RubyLayerLifecycle
+/// {
+///     fn create(
+///         &self,
+///         layer_path: &Path,
+///         build_context: &GenericBuildContext,
+///     ) -> Result<LayerContentMetadata<GenericMetadata>, std::io::Error> {
+///         Ok(LayerContentMetadata::default())
+///     }
+/// }
+///
+/// fn build(context: GenericBuildContext) -> libcnb::Result<(), std::io::Error> {
+///     libcnb::layer_lifecycle::execute_layer::<RubyBuildpack, _>(
+///         &libcnb::layer_name!("ruby"),
+///         LayerLifecycleAdapter::new(RubyLayerLifecycle),
+///         &context,
+///     )
+/// }
+/// ```
+pub struct LayerLifecycleAdapter<LL, LM, O, E> {
+    layer_lifecycle: LL,
+    types: PhantomData<AdaptedTypes<LM, O, E>>,
+}
+
+// Function pointers keep the adapter `Send` and `Sync` regardless of the adapted types.
+type AdaptedTypes<LM, O, E> = fn() -> (LM, O, E);
+
+impl<LL, LM, O, E> LayerLifecycleAdapter<LL, LM, O, E> {
+    pub fn new(layer_lifecycle: LL) -> Self {
+        LayerLifecycleAdapter {
+            layer_lifecycle,
+            types: PhantomData,
+        }
+    }
+}
+
+impl<B, LL, LM, O, E> Layer<B> for LayerLifecycleAdapter<LL, LM, O, E>
+where
+    B: Buildpack,
+    LL: LayerLifecycle<B::Platform, B::Metadata, LM, O, E>,
+    LM: Serialize + DeserializeOwned,
+    O: Default,
+    E: std::fmt::Debug + std::fmt::Display + Into<B::Error>,
+{
+    type Metadata = LM;
+    type Output = O;
+    type Error = E;
+
+    fn create(
+        &self,
+        layer_path: &Path,
+        build_context: &BuildContext<B::Platform, B::Metadata>,
+    ) -> Result<LayerContentMetadata<LM>, E> {
+        self.layer_lifecycle.create(layer_path, build_context)
+    }
+
+    fn output(
+        &self,
+        layer_path: &Path,
+        layer_content_metadata: LayerContentMetadata<LM>,
+    ) -> Result<O, E> {
+        self.layer_lifecycle
+            .layer_lifecycle_data(layer_path, layer_content_metadata)
+    }
+
+    fn recover_from_invalid_metadata(
+        &self,
+        layer_metadata: &toml::value::Table,
+        build_context: &BuildContext<B::Platform, B::Metadata>,
+    ) -> Result<MetadataRecoveryStrategy<LM>, E> {
+        self.layer_lifecycle
+            .recover_from_invalid_metadata(layer_metadata, build_context)
+    }
+
+    fn metadata_migrations(&self) -> Option<MetadataMigrations<LM>> {
+        self.layer_lifecycle.metadata_migrations()
+    }
+
+    fn validate(
+        &self,
+        layer_path: &Path,
+        layer_content_metadata: &LayerContentMetadata<LM>,
+        build_context: &BuildContext<B::Platform, B::Metadata>,
+    ) -> ValidateResult {
+        self.layer_lifecycle
+            .validate(layer_path, layer_content_metadata, build_context)
+    }
+
+    fn update(
+        &self,
+        layer_path: &Path,
+        layer_content_metadata: LayerContentMetadata<LM>,
+        build_context: &BuildContext<B::Platform, B::Metadata>,
+    ) -> Result<LayerContentMetadata<LM>, E> {
+        self.layer_lifecycle
+            .update(layer_path, layer_content_metadata, build_context)
+    }
+
+    fn bom(
+        &self,
+        layer_path: &Path,
+        layer_content_metadata: &LayerContentMetadata<LM>,
+        build_context: &BuildContext<B::Platform, B::Metadata>,
+    ) -> Bom {
+        self.layer_lifecycle
+            .bom(layer_path, layer_content_metadata, build_context)
+    }
+
+    fn on_lifecycle_start(&self) {
+        self.layer_lifecycle.on_lifecycle_start()
+    }
+
+    fn on_keep(&self) {
+        self.layer_lifecycle.on_keep()
+    }
+
+    fn on_update(&self) {
+        self.layer_lifecycle.on_update()
+    }
+
+    fn on_create(&self) {
+        self.layer_lifecycle.on_create()
+    }
+
+    fn on_lifecycle_end(&self) {
+        self.layer_lifecycle.on_lifecycle_end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detect::DetectOutcome;
+    use crate::generic::{
+        GenericBuildContext, GenericDetectContext, GenericMetadata, GenericPlatform,
+    };
+    use crate::layer_name;
+    use crate::test_support::build_context;
+    use std::fs;
+
+    struct TestBuildpack;
+
+    impl Buildpack for TestBuildpack {
+        type Platform = GenericPlatform;
+        type Metadata = GenericMetadata;
+        type Error = String;
+
+        fn detect(&self, _context: GenericDetectContext) -> crate::Result<DetectOutcome, String> {
+            Ok(DetectOutcome::Fail(None))
+        }
+
+        fn build(&self, _context: GenericBuildContext) -> crate::Result<(), String> {
+            Ok(())
+        }
+    }
+
+    struct CountingLayer;
+
+    // The output type deliberately does not implement `Default`.
+    #[derive(Debug, PartialEq)]
+    struct LayerFileCount(usize);
+
+    impl Layer<TestBuildpack> for CountingLayer {
+        type Metadata = GenericMetadata;
+        type Output = LayerFileCount;
+        type Error = &'static str;
+
+        fn create(
+            &self,
+            layer_path: &Path,
+            _build_context: &GenericBuildContext,
+        ) -> Result<LayerContentMetadata<GenericMetadata>, &'static str> {
+            fs::write(layer_path.join("a"), "").map_err(|_| "write failed")?;
+            Ok(LayerContentMetadata::default())
+        }
+
+        fn output(
+            &self,
+            layer_path: &Path,
+            _layer_content_metadata: LayerContentMetadata<GenericMetadata>,
+        ) -> Result<LayerFileCount, &'static str> {
+            Ok(LayerFileCount(fs::read_dir(layer_path).unwrap().count()))
+        }
+
+        fn validate(
+            &self,
+            _layer_path: &Path,
+            _layer_content_metadata: &LayerContentMetadata<GenericMetadata>,
+            _build_context: &GenericBuildContext,
+        ) -> ValidateResult {
+            ValidateResult::KeepLayer
+        }
+    }
+
+    struct FailingLayerLifecycle;
+
+    impl LayerLifecycle<GenericPlatform, GenericMetadata, GenericMetadata, (), String>
+        for FailingLayerLifecycle
+    {
+        fn create(
+            &self,
+            _layer_path: &Path,
+            _build_context: &GenericBuildContext,
+        ) -> Result<LayerContentMetadata<GenericMetadata>, String> {
+            Err(String::from("create failed"))
+        }
+    }
+
+    #[test]
+    fn executes_layer() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+
+        assert_eq!(
+            execute_layer(&layer_name!("counting"), CountingLayer, &context).unwrap(),
+            LayerFileCount(1)
+        );
+
+        fs::write(context.layer_path(&layer_name!("counting")).join("b"), "").unwrap();
+        assert_eq!(
+            execute_layer(&layer_name!("counting"), CountingLayer, &context).unwrap(),
+            LayerFileCount(2)
+        );
+    }
+
+    #[test]
+    fn adapts_layer_lifecycle() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+
+        let result = execute_layer::<TestBuildpack, _>(
+            &layer_name!("failing"),
+            LayerLifecycleAdapter::new(FailingLayerLifecycle),
+            &context,
+        );
+
+        assert!(matches!(result, Err(Error::BuildpackError(error)) if error == "create failed"));
+        assert!(!context.layer_path(&layer_name!("failing")).exists());
+    }
+}
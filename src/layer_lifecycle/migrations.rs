@@ -0,0 +1,316 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::marker::PhantomData;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use toml::value::Table;
+
+/// The key under which [`execute_layer_lifecycle`](crate::layer_lifecycle::execute_layer_lifecycle)
+/// stores the metadata version in the layer's `metadata` table.
+pub const METADATA_VERSION_KEY: &str = "metadata-version";
+
+/// Layer metadata that declares its version.
+///
+/// Layer metadata written without a version, for example before migrations were introduced, is
+/// considered to be version `0`.
+pub trait VersionedMetadata: Serialize + DeserializeOwned {
+    const VERSION: u32;
+}
+
+type MigrationFn = Box<dyn Fn(Table) -> Result<Table, MigrationError>>;
+
+struct MigrationStep {
+    to_version: u32,
+    migrate: MigrationFn,
+}
+
+/// A set of migration steps that upgrade older versions of layer metadata to `M`.
+///
+/// Each step converts one version into another via a [`From`] implementation. Steps are chained
+/// automatically, there is no need to register a direct migration for every older version.
+///
+/// # Examples
+/// ```
+/// use libcnb::layer_lifecycle::{MetadataMigrations, VersionedMetadata};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Deserialize, Serialize)]
+/// struct RubyLayerMetadataV1 {
+///     ruby: String,
+/// }
+///
+/// impl VersionedMetadata for RubyLayerMetadataV1 {
+///     const VERSION: u32 = 1;
+/// }
+///
+/// #[derive(Deserialize, Serialize)]
+/// struct RubyLayerMetadataV2 {
+///     ruby_version: String,
+///     stack_id: Option<String>,
+/// }
+///
+/// impl VersionedMetadata for RubyLayerMetadataV2 {
+///     const VERSION: u32 = 2;
+/// }
+///
+/// impl From<RubyLayerMetadataV1> for RubyLayerMetadataV2 {
+///     fn from(v1: RubyLayerMetadataV1) -> Self {
+///         RubyLayerMetadataV2 {
+///             ruby_version: v1.ruby,
+///             stack_id: None,
+///         }
+///     }
+/// }
+///
+/// let migrations = MetadataMigrations::<RubyLayerMetadataV2>::new()
+///     .migration::<RubyLayerMetadataV1, RubyLayerMetadataV2>();
+/// ```
+pub struct MetadataMigrations<M> {
+    version: u32,
+    steps: HashMap<u32, MigrationStep>,
+    target: PhantomData<M>,
+}
+
+impl<M: VersionedMetadata> MetadataMigrations<M> {
+    pub fn new() -> Self {
+        MetadataMigrations {
+            version: M::VERSION,
+            steps: HashMap::new(),
+            target: PhantomData,
+        }
+    }
+
+    /// Registers a migration step from metadata `F` to metadata `T`.
+    ///
+    /// Registering another step for the same version of `F` replaces the existing one.
+    pub fn migration<F: VersionedMetadata, T: VersionedMetadata + From<F>>(mut self) -> Self {
+        self.steps.insert(
+            F::VERSION,
+            MigrationStep {
+                to_version: T::VERSION,
+                migrate: Box::new(|table| {
+                    let from: F = toml::Value::Table(table).try_into()?;
+                    to_table(&T::from(from))
+                }),
+            },
+        );
+
+        self
+    }
+}
+
+impl<M: Serialize + DeserializeOwned> MetadataMigrations<M> {
+    /// The current metadata version, i.e. the version of `M`.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Migrates the given untyped layer metadata to `M`.
+    ///
+    /// The version is read from the [`METADATA_VERSION_KEY`] key of the given table. Returns
+    /// [`MigrationError::NoMigrationPath`] if `M` cannot be reached from the stored version.
+    pub fn migrate(&self, metadata: &Table) -> Result<M, MigrationError> {
+        let mut version = stored_version(metadata)?;
+        let mut table = metadata.clone();
+        table.remove(METADATA_VERSION_KEY);
+
+        // Each step can be used at most once, anything beyond that is a cycle.
+        for _ in 0..=self.steps.len() {
+            if version == self.version {
+                return Ok(toml::Value::Table(table).try_into()?);
+            }
+
+            let step = self
+                .steps
+                .get(&version)
+                .ok_or(MigrationError::NoMigrationPath(version, self.version))?;
+
+            table = (step.migrate)(table)?;
+            version = step.to_version;
+        }
+
+        Err(MigrationError::NoMigrationPath(
+            stored_version(metadata)?,
+            self.version,
+        ))
+    }
+
+    /// Serializes the given metadata into a table that includes the metadata version.
+    pub(crate) fn versioned_table(&self, metadata: &M) -> Result<Table, MigrationError> {
+        let mut table = to_table(metadata)?;
+        table.insert(
+            String::from(METADATA_VERSION_KEY),
+            toml::Value::Integer(i64::from(self.version)),
+        );
+
+        Ok(table)
+    }
+}
+
+impl<M: VersionedMetadata> Default for MetadataMigrations<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the version of the given untyped layer metadata.
+pub(crate) fn stored_version(metadata: &Table) -> Result<u32, MigrationError> {
+    match metadata.get(METADATA_VERSION_KEY) {
+        None => Ok(0),
+        Some(value) => value
+            .as_integer()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or_else(|| MigrationError::InvalidVersion(value.clone())),
+    }
+}
+
+fn to_table(value: &impl Serialize) -> Result<Table, MigrationError> {
+    match toml::Value::try_from(value)? {
+        toml::Value::Table(table) => Ok(table),
+        _ => Err(MigrationError::MetadataIsNotATable),
+    }
+}
+
+/// An error that occurred while migrating layer metadata.
+#[derive(thiserror::Error, Debug)]
+pub enum MigrationError {
+    #[error("No migration path from metadata version {0} to version {1}")]
+    NoMigrationPath(u32, u32),
+
+    #[error("Invalid metadata version: {0}")]
+    InvalidVersion(toml::Value),
+
+    #[error("Versioned layer metadata must serialize to a TOML table")]
+    MetadataIsNotATable,
+
+    #[error("Could not deserialize layer metadata: {0}")]
+    DeserializationError(#[from] toml::de::Error),
+
+    #[error("Could not serialize layer metadata: {0}")]
+    SerializationError(#[from] toml::ser::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Deserialize, Serialize)]
+    struct V0 {
+        ruby: String,
+    }
+
+    impl VersionedMetadata for V0 {
+        const VERSION: u32 = 0;
+    }
+
+    #[derive(Deserialize, Serialize)]
+    struct V1 {
+        ruby_version: String,
+    }
+
+    impl VersionedMetadata for V1 {
+        const VERSION: u32 = 1;
+    }
+
+    impl From<V0> for V1 {
+        fn from(v0: V0) -> Self {
+            V1 {
+                ruby_version: v0.ruby,
+            }
+        }
+    }
+
+    impl From<V1> for V0 {
+        fn from(v1: V1) -> Self {
+            V0 {
+                ruby: v1.ruby_version,
+            }
+        }
+    }
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq)]
+    struct V2 {
+        ruby_version: String,
+        bundler_version: String,
+    }
+
+    impl VersionedMetadata for V2 {
+        const VERSION: u32 = 2;
+    }
+
+    impl From<V1> for V2 {
+        fn from(v1: V1) -> Self {
+            V2 {
+                ruby_version: v1.ruby_version,
+                bundler_version: String::from("2.2.21"),
+            }
+        }
+    }
+
+    fn migrations() -> MetadataMigrations<V2> {
+        MetadataMigrations::new()
+            .migration::<V1, V2>()
+            .migration::<V0, V1>()
+    }
+
+    #[test]
+    fn chains_migrations_from_unversioned_metadata() {
+        let table: Table = toml::from_str(r#"ruby = "3.0.1""#).unwrap();
+
+        assert_eq!(
+            migrations().migrate(&table).unwrap(),
+            V2 {
+                ruby_version: String::from("3.0.1"),
+                bundler_version: String::from("2.2.21")
+            }
+        );
+    }
+
+    #[test]
+    fn reads_current_version_without_migration() {
+        let table = migrations()
+            .versioned_table(&V2 {
+                ruby_version: String::from("3.0.1"),
+                bundler_version: String::from("2.1.0"),
+            })
+            .unwrap();
+
+        assert_eq!(stored_version(&table).unwrap(), 2);
+        assert_eq!(
+            migrations().migrate(&table).unwrap().bundler_version,
+            "2.1.0"
+        );
+    }
+
+    #[test]
+    fn fails_without_migration_path() {
+        let table: Table = toml::from_str(
+            r#"
+            metadata-version = 7
+            ruby = "3.0.1"
+            "#,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            migrations().migrate(&table),
+            Err(MigrationError::NoMigrationPath(7, 2))
+        ));
+    }
+
+    #[test]
+    fn fails_on_migration_cycle() {
+        let migrations = MetadataMigrations::<V2>::new()
+            .migration::<V0, V1>()
+            .migration::<V1, V0>();
+
+        let table: Table = toml::from_str(r#"ruby = "3.0.1""#).unwrap();
+
+        assert!(matches!(
+            migrations.migrate(&table),
+            Err(MigrationError::NoMigrationPath(0, 2))
+        ));
+    }
+}
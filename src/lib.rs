@@ -1,20 +1,30 @@
 //! This crate provides a library to implement [Cloud Native Buildpacks](https://buildpacks.io/).
 
+#[cfg(feature = "app")]
+pub mod app;
 pub mod data;
+pub mod diagnostics;
 pub mod layer_lifecycle;
 pub use build::BuildContext;
+pub use buildpack::Buildpack;
 pub use detect::DetectContext;
 pub use detect::DetectOutcome;
+pub use detect::DetectRule;
+pub use detect::RuleOutcome;
 pub use error::*;
 pub use generic::*;
 pub use platform::*;
+pub use runtime::buildpack_runtime;
 pub use runtime::cnb_runtime;
 pub use toml_file::*;
 
 mod build;
+mod buildpack;
 mod detect;
 mod error;
 mod generic;
 mod platform;
 mod runtime;
+#[cfg(test)]
+mod test_support;
 mod toml_file;
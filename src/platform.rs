@@ -1,11 +1,14 @@
 use std::{
     collections::HashMap,
-    env::VarError,
+    env::{self, VarError},
     ffi::{OsStr, OsString},
-    fs, io,
-    path::Path,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
 };
 
+use crate::data::buildpack::{BuildpackApi, BuildpackTomlError};
+
 /// Represents a Cloud Native Buildpack platform.
 ///
 /// Most buildpacks target a generic platform and this library provides a [`crate::generic::GenericPlatform`] for that
@@ -13,15 +16,48 @@ use std::{
 /// [detection](https://github.com/buildpacks/spec/blob/main/buildpack.md#detection) and
 /// [build](https://github.com/buildpacks/spec/blob/main/buildpack.md#build) in the buildpack
 /// specification for details.
+///
+/// Custom platforms can validate or augment the [`PlatformContext`] at startup by implementing
+/// [`Platform::from_context`].
 pub trait Platform
 where
     Self: Sized,
 {
+    /// The platform contract as seen by the buildpack at startup.
+    fn context(&self) -> &PlatformContext;
+
+    /// Initializes the platform from the given context. Returning an error aborts the buildpack
+    /// with [`Error::CannotCreatePlatformFromPath`](crate::Error::CannotCreatePlatformFromPath).
+    fn from_context(context: PlatformContext) -> Result<Self, PlatformError>;
+
     /// Retrieve a [`PlatformEnv`] reference for convenient access to environment variables which
     /// all platforms have to provide.
-    fn env(&self) -> &PlatformEnv;
+    fn env(&self) -> &PlatformEnv {
+        &self.context().env
+    }
+
+    /// The platform directory.
+    fn platform_dir(&self) -> &Path {
+        &self.context().platform_dir
+    }
+
+    /// The Platform API version from `CNB_PLATFORM_API`, if set.
+    fn platform_api(&self) -> Option<&PlatformApi> {
+        self.context().platform_api.as_ref()
+    }
 
-    /// Initializes the platform from the given platform directory.
+    /// The target from the `CNB_TARGET_*` environment variables.
+    fn target(&self) -> &Target {
+        &self.context().target
+    }
+
+    /// The build config directory from `CNB_BUILD_CONFIG_DIR`, if set.
+    fn build_config_dir(&self) -> Option<&Path> {
+        self.context().build_config_dir.as_deref()
+    }
+
+    /// Initializes the platform from the given platform directory and the environment of the
+    /// current process.
     ///
     /// # Examples
     /// ```no_run
@@ -29,17 +65,161 @@ where
     ///use libcnb::GenericPlatform;
     ///let platform = GenericPlatform::from_path("/platform").unwrap();
     /// ```
-    fn from_path(platform_dir: impl AsRef<Path>) -> io::Result<Self>;
+    fn from_path(platform_dir: impl AsRef<Path>) -> Result<Self, PlatformError> {
+        Self::from_context(PlatformContext::from_path(platform_dir)?)
+    }
+}
+
+/// The platform contract as seen by the buildpack at startup.
+///
+/// See [`Platform`].
+#[derive(Debug)]
+pub struct PlatformContext {
+    pub platform_dir: PathBuf,
+    pub env: PlatformEnv,
+    pub platform_api: Option<PlatformApi>,
+    pub target: Target,
+    pub build_config_dir: Option<PathBuf>,
+}
+
+impl PlatformContext {
+    /// Reads the platform context from the given platform directory and the environment of the
+    /// current process.
+    pub fn from_path(platform_dir: impl AsRef<Path>) -> Result<Self, PlatformError> {
+        PlatformContext::from_vars(platform_dir, |key| env::var_os(key))
+    }
+
+    fn from_vars(
+        platform_dir: impl AsRef<Path>,
+        vars: impl Fn(&str) -> Option<OsString>,
+    ) -> Result<Self, PlatformError> {
+        let platform_dir = platform_dir.as_ref();
+
+        let var = |key: &'static str| -> Result<Option<String>, PlatformError> {
+            vars(key)
+                .map(|value| {
+                    value
+                        .into_string()
+                        .map_err(|_| PlatformError::NonUnicodeVariable(key))
+                })
+                .transpose()
+        };
+
+        let env = PlatformEnv::from_path(platform_dir)
+            .map_err(|error| PlatformError::CannotReadEnv(platform_dir.join("env"), error))?;
+
+        let platform_api = var("CNB_PLATFORM_API")?
+            .map(|value| {
+                PlatformApi::from_str(&value)
+                    .map_err(|_| PlatformError::InvalidPlatformApi(value.clone()))
+            })
+            .transpose()?;
+
+        let target = Target {
+            os: var("CNB_TARGET_OS")?,
+            arch: var("CNB_TARGET_ARCH")?,
+            arch_variant: var("CNB_TARGET_ARCH_VARIANT")?,
+            distro_name: var("CNB_TARGET_DISTRO_NAME")?,
+            distro_version: var("CNB_TARGET_DISTRO_VERSION")?,
+        };
+
+        Ok(PlatformContext {
+            platform_dir: platform_dir.to_path_buf(),
+            env,
+            platform_api,
+            target,
+            build_config_dir: vars("CNB_BUILD_CONFIG_DIR").map(PathBuf::from),
+        })
+    }
+}
+
+/// The Platform API version, i.e. `0.7`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PlatformApi {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl FromStr for PlatformApi {
+    type Err = BuildpackTomlError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        // Platform API versions use the same format as Buildpack API versions.
+        BuildpackApi::from_str(value).map(|api| PlatformApi {
+            major: api.major,
+            minor: api.minor,
+        })
+    }
+}
+
+impl fmt::Display for PlatformApi {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// The target the buildpack runs on, as provided by the platform via `CNB_TARGET_*` variables.
+///
+/// Older platforms do not provide these variables, use [`Target::os`] and [`Target::arch`] for
+/// values that fall back to the current process.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Target {
+    pub os: Option<String>,
+    pub arch: Option<String>,
+    pub arch_variant: Option<String>,
+    pub distro_name: Option<String>,
+    pub distro_version: Option<String>,
+}
+
+impl Target {
+    /// The target OS, falling back to the OS this buildpack was compiled for.
+    pub fn os(&self) -> &str {
+        self.os.as_deref().unwrap_or(std::env::consts::OS)
+    }
+
+    /// The target architecture in OCI notation, falling back to the architecture this buildpack
+    /// was compiled for.
+    pub fn arch(&self) -> &str {
+        self.arch
+            .as_deref()
+            .unwrap_or(match std::env::consts::ARCH {
+                "x86_64" => "amd64",
+                "aarch64" => "arm64",
+                arch => arch,
+            })
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum PlatformError {
+    #[error("Cannot read platform env directory {}: {1}", .0.display())]
+    CannotReadEnv(PathBuf, io::Error),
+
+    #[error("Invalid platform API in CNB_PLATFORM_API: `{0}`")]
+    InvalidPlatformApi(String),
+
+    #[error("Environment variable {0} is not valid unicode")]
+    NonUnicodeVariable(&'static str),
+
+    #[error("Platform validation failed: {0}")]
+    ValidationFailed(String),
 }
 
 /// Provides access to platform environment variables.
+///
+/// Values are stored as read from the files in `<platform>/env`, use [`PlatformEnv::var_os`] for
+/// values that are not necessarily valid unicode.
+#[derive(Debug)]
 pub struct PlatformEnv {
-    vars: HashMap<OsString, String>,
+    vars: HashMap<OsString, OsString>,
 }
 
 impl PlatformEnv {
     /// Fetches the environment variable `key` from the platform.
     ///
+    /// Just like [`std::env::var`], this returns [`VarError::NotUnicode`] if the value is not
+    /// valid unicode.
+    ///
     /// # Examples
     /// ```no_run
     ///use libcnb::PlatformEnv;
@@ -47,14 +227,23 @@ impl PlatformEnv {
     ///let value = env.var("SOME_ENV_VAR");
     /// ```
     pub fn var<K: AsRef<OsStr>>(&self, key: K) -> Result<String, VarError> {
-        self.vars
-            .get(key.as_ref())
-            .map(|s| s.to_owned())
-            .ok_or(VarError::NotPresent)
+        self.var_os(key)
+            .ok_or(VarError::NotPresent)?
+            .into_string()
+            .map_err(VarError::NotUnicode)
+    }
+
+    /// Fetches the environment variable `key` from the platform without checking whether it is
+    /// valid unicode.
+    pub fn var_os<K: AsRef<OsStr>>(&self, key: K) -> Option<OsString> {
+        self.vars.get(key.as_ref()).cloned()
     }
 
     /// Initializes a new PlatformEnv from the given platform directory.
     ///
+    /// A missing `env` directory is treated as an empty environment. Subdirectories of the `env`
+    /// directory are skipped.
+    ///
     /// Buildpack authors usually do not need to create their own [`PlatformEnv`] and instead use the
     /// one passed via context structs ([`DetectContext`](crate::detect::DetectContext) and [`BuildContext`](crate::build::BuildContext)).
     ///
@@ -65,18 +254,156 @@ impl PlatformEnv {
     /// ```
     pub fn from_path(platform_dir: impl AsRef<Path>) -> Result<Self, io::Error> {
         let env_path = platform_dir.as_ref().join("env");
-        let mut env_vars: HashMap<OsString, String> = HashMap::new();
+        let mut env_vars: HashMap<OsString, OsString> = HashMap::new();
 
-        for entry in fs::read_dir(env_path)? {
-            let entry = entry?;
-            let path = entry.path();
+        let entries = match fs::read_dir(env_path) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                return Ok(PlatformEnv { vars: env_vars })
+            }
+            Err(error) => return Err(error),
+        };
+
+        for entry in entries {
+            let path = entry?.path();
+
+            if path.is_dir() {
+                continue;
+            }
 
             if let Some(file_name) = path.file_name() {
-                let file_contents = fs::read_to_string(&path)?;
-                env_vars.insert(file_name.to_owned(), file_contents);
+                env_vars.insert(file_name.to_owned(), os_string_from_bytes(fs::read(&path)?));
             }
         }
 
         Ok(PlatformEnv { vars: env_vars })
     }
 }
+
+#[cfg(unix)]
+fn os_string_from_bytes(bytes: Vec<u8>) -> OsString {
+    use std::os::unix::ffi::OsStringExt;
+    OsString::from_vec(bytes)
+}
+
+#[cfg(not(unix))]
+fn os_string_from_bytes(bytes: Vec<u8>) -> OsString {
+    OsString::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(vars: &[(&str, &str)]) -> Result<PlatformContext, PlatformError> {
+        let temp_dir = tempfile::tempdir().unwrap();
+        fs::create_dir(temp_dir.path().join("env")).unwrap();
+
+        let vars: HashMap<String, OsString> = vars
+            .iter()
+            .map(|(key, value)| (String::from(*key), OsString::from(value)))
+            .collect();
+
+        PlatformContext::from_vars(temp_dir.path(), |key| vars.get(key).cloned())
+    }
+
+    #[test]
+    fn reads_platform_contract() {
+        let context = context(&[
+            ("CNB_PLATFORM_API", "0.7"),
+            ("CNB_TARGET_OS", "linux"),
+            ("CNB_TARGET_ARCH", "arm64"),
+            ("CNB_TARGET_DISTRO_NAME", "ubuntu"),
+            ("CNB_BUILD_CONFIG_DIR", "/cnb/build-config"),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            context.platform_api,
+            Some(PlatformApi { major: 0, minor: 7 })
+        );
+        assert_eq!(context.target.os(), "linux");
+        assert_eq!(context.target.arch(), "arm64");
+        assert_eq!(context.target.distro_name.as_deref(), Some("ubuntu"));
+        assert_eq!(context.target.distro_version, None);
+        assert_eq!(
+            context.build_config_dir,
+            Some(PathBuf::from("/cnb/build-config"))
+        );
+    }
+
+    #[test]
+    fn optional_parts_may_be_missing() {
+        let context = context(&[]).unwrap();
+
+        assert_eq!(context.platform_api, None);
+        assert_eq!(context.target, Target::default());
+        assert_eq!(context.target.os(), std::env::consts::OS);
+        assert_eq!(context.build_config_dir, None);
+    }
+
+    #[test]
+    fn describes_failing_part() {
+        assert_eq!(
+            context(&[("CNB_PLATFORM_API", "latest")])
+                .unwrap_err()
+                .to_string(),
+            "Invalid platform API in CNB_PLATFORM_API: `latest`"
+        );
+
+        // A file where the env directory is expected cannot be read as a directory.
+        let temp_dir = tempfile::tempdir().unwrap();
+        fs::write(temp_dir.path().join("env"), "").unwrap();
+        let error = PlatformContext::from_vars(temp_dir.path(), |_| None).unwrap_err();
+        assert!(
+            matches!(error, PlatformError::CannotReadEnv(path, _) if path == temp_dir.path().join("env"))
+        );
+    }
+
+    #[test]
+    fn env_reads_values() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        fs::create_dir(temp_dir.path().join("env")).unwrap();
+        fs::write(temp_dir.path().join("env").join("FOO"), "bar").unwrap();
+
+        let env = PlatformEnv::from_path(temp_dir.path()).unwrap();
+        assert_eq!(env.var("FOO"), Ok(String::from("bar")));
+        assert_eq!(env.var("BAR"), Err(VarError::NotPresent));
+    }
+
+    #[test]
+    fn env_treats_missing_directory_as_empty() {
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        let env = PlatformEnv::from_path(temp_dir.path()).unwrap();
+        assert_eq!(env.var_os("FOO"), None);
+    }
+
+    #[test]
+    fn env_skips_subdirectories() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(temp_dir.path().join("env").join("FOO")).unwrap();
+        fs::write(temp_dir.path().join("env").join("BAR"), "baz").unwrap();
+
+        let env = PlatformEnv::from_path(temp_dir.path()).unwrap();
+        assert_eq!(env.var_os("FOO"), None);
+        assert_eq!(env.var("BAR"), Ok(String::from("baz")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn env_keeps_non_unicode_values() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        fs::create_dir(temp_dir.path().join("env")).unwrap();
+        fs::write(temp_dir.path().join("env").join("FOO"), [0x66, 0x6f, 0xff]).unwrap();
+
+        let env = PlatformEnv::from_path(temp_dir.path()).unwrap();
+        assert_eq!(
+            env.var_os("FOO").unwrap().as_bytes(),
+            &[0x66, 0x6f, 0xff][..]
+        );
+        assert!(matches!(env.var("FOO"), Err(VarError::NotUnicode(_))));
+    }
+}
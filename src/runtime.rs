@@ -1,15 +1,26 @@
 use std::env;
-use std::path::PathBuf;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::process;
 use std::process::exit;
 
 use serde::de::DeserializeOwned;
 
 use crate::build::BuildContext;
+use crate::buildpack::Buildpack;
+use crate::data::bom::BomCollector;
+use crate::data::build::Build;
+use crate::data::launch::Launch;
+use crate::data::layer_env::LayerEnv;
+use crate::data::sbom::SbomFormat;
 use crate::detect::{DetectContext, DetectOutcome};
+use crate::diagnostics::Diagnostics;
 use crate::error::{Error, ErrorHandler};
+use crate::layer_lifecycle::{
+    delete_untouched_layers, is_work_path, list_layers, LayerCleanupPolicy, LayerTracker,
+};
 use crate::platform::Platform;
-use crate::toml_file::{read_toml_file, write_toml_file};
+use crate::toml_file::{read_toml_file, write_toml_file, TomlFileError};
 use crate::Result;
 use std::fmt::{Debug, Display};
 
@@ -21,7 +32,7 @@ use std::fmt::{Debug, Display};
 ///
 /// fn detect(context: GenericDetectContext) -> Result<DetectOutcome, std::io::Error> {
 ///     // ...
-///     Ok(DetectOutcome::Fail)
+///     Ok(DetectOutcome::Fail(None))
 /// }
 ///
 /// fn build(context: GenericBuildContext) -> Result<(), std::io::Error> {
@@ -44,7 +55,7 @@ pub fn cnb_runtime<P: Platform, BM: DeserializeOwned, E: Debug + Display>(
         .and_then(|path| path.file_name())
         .and_then(|file_name| file_name.to_str());
 
-    #[cfg(any(target_family = "unix"))]
+    #[cfg(target_family = "unix")]
     let result = match current_exe_file_name {
         Some("detect") => cnb_runtime_detect(detect_fn),
         Some("build") => cnb_runtime_build(build_fn),
@@ -56,6 +67,25 @@ pub fn cnb_runtime<P: Platform, BM: DeserializeOwned, E: Debug + Display>(
     }
 }
 
+/// Main entry point for buildpacks implementing the [`Buildpack`] trait.
+///
+/// Usually invoked via [`buildpack_main!`](crate::buildpack_main).
+pub fn buildpack_runtime<B: Buildpack>(buildpack: B) {
+    cnb_runtime(
+        |context| buildpack.detect(context),
+        |context| buildpack.build(context),
+        BuildpackErrorHandler(&buildpack),
+    )
+}
+
+struct BuildpackErrorHandler<'a, B>(&'a B);
+
+impl<'a, B: Buildpack> ErrorHandler<B::Error> for BuildpackErrorHandler<'a, B> {
+    fn handle_error(&self, error: Error<B::Error>) -> i32 {
+        self.0.handle_error(error)
+    }
+}
+
 fn cnb_runtime_detect<
     P: Platform,
     BM: DeserializeOwned,
@@ -83,20 +113,37 @@ fn cnb_runtime_detect<
     let build_plan_path = args.build_plan_path;
 
     let detect_context = DetectContext {
-        app_dir,
+        app_dir: app_dir.clone(),
         buildpack_dir,
         stack_id,
         platform,
         buildpack_descriptor,
     };
 
-    match detect_fn(detect_context)? {
+    let outcome = detect_fn(detect_context)?;
+    let exit_code = outcome.exit_code();
+
+    match outcome {
         DetectOutcome::Pass(build_plan) => {
             write_toml_file(&build_plan, build_plan_path).map_err(Error::CannotWriteBuildPlan)?;
-            process::exit(0)
         }
-        DetectOutcome::Fail => process::exit(100),
+        DetectOutcome::PassWithTriggers(build_plan, triggers) => {
+            for trigger in triggers {
+                let trigger = trigger.strip_prefix(&app_dir).unwrap_or(&trigger);
+                println!("Detected {}", trigger.display());
+            }
+
+            write_toml_file(&build_plan, build_plan_path).map_err(Error::CannotWriteBuildPlan)?;
+        }
+        DetectOutcome::Fail(reason) => {
+            if let Some(reason) = reason {
+                println!("{}", reason);
+            }
+        }
+        DetectOutcome::Error(message) => eprintln!("Detection failed: {}", message),
     }
+
+    process::exit(exit_code)
 }
 
 fn cnb_runtime_build<
@@ -128,17 +175,231 @@ fn cnb_runtime_build<
     let buildpack_descriptor = read_toml_file(buildpack_dir.join("buildpack.toml"))
         .map_err(Error::CannotReadBuildpackDescriptor)?;
 
+    let diagnostics = Diagnostics::new();
+    let bom = BomCollector::new();
+    let layer_tracker = LayerTracker::new();
+
     let context = BuildContext {
-        layers_dir,
+        layers_dir: layers_dir.clone(),
         app_dir,
         buildpack_dir,
         stack_id,
         platform,
         buildpack_plan,
         buildpack_descriptor,
+        diagnostics: diagnostics.clone(),
+        bom: bom.clone(),
+        layer_tracker: layer_tracker.clone(),
     };
 
-    build_fn(context)
+    let launch_path = context.launch_path();
+    let build_path = context.build_path();
+    let sbom_formats = context.buildpack_descriptor.buildpack.sbom_formats.clone();
+
+    let result = build_fn(context).and_then(|_| {
+        write_contributed_bom(&launch_path, &build_path, &bom)?;
+        warn_about_unknown_process_env(&layers_dir, &launch_path, &diagnostics);
+        clean_up_layers(&layers_dir, &layer_tracker, &diagnostics);
+        warn_about_unwritten_sbom_formats(&layers_dir, &sbom_formats, &diagnostics);
+
+        Ok(())
+    });
+
+    if !diagnostics.is_empty() {
+        eprintln!("{}", diagnostics);
+    }
+
+    match result {
+        Ok(()) if diagnostics.should_fail_build() => {
+            Err(Error::DiagnosticErrorsRecorded(diagnostics.error_count()))
+        }
+        other => other,
+    }
+}
+
+// Layers may contribute bill-of-materials entries even if the buildpack itself does not write
+// launch.toml or build.toml, or after it did. Entries are merged into existing files.
+fn write_contributed_bom<E: Debug + Display>(
+    launch_path: &Path,
+    build_path: &Path,
+    bom: &BomCollector,
+) -> Result<(), E> {
+    let launch_bom = bom.launch_entries();
+    if !launch_bom.is_empty() {
+        let existing =
+            read_toml_file_or(launch_path, Launch::new).map_err(Error::CannotReadLaunch)?;
+
+        if !launch_bom.iter().all(|entry| existing.bom.contains(entry)) {
+            let launch = existing.merge(Launch {
+                bom: launch_bom,
+                ..Launch::new()
+            });
+
+            write_toml_file(&launch, launch_path).map_err(Error::CannotWriteLaunch)?;
+        }
+    }
+
+    let build_bom = bom.build_entries();
+    if !build_bom.is_empty() {
+        let mut build =
+            read_toml_file_or(build_path, Build::new).map_err(Error::CannotReadBuild)?;
+
+        if !build_bom.iter().all(|entry| build.bom.contains(entry)) {
+            for entry in build_bom {
+                if !build.bom.contains(&entry) {
+                    build.bom.push(entry);
+                }
+            }
+
+            write_toml_file(&build, build_path).map_err(Error::CannotWriteBuild)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn read_toml_file_or<A: DeserializeOwned>(
+    path: &Path,
+    default: impl FnOnce() -> A,
+) -> std::result::Result<A, TomlFileError> {
+    if path.exists() {
+        read_toml_file(path)
+    } else {
+        Ok(default())
+    }
+}
+
+// Reports orphaned layer files and applies the layer cleanup policy. Failures are reported as
+// warnings since they do not affect the layers that were created during this build.
+fn clean_up_layers(layers_dir: &Path, layer_tracker: &LayerTracker, diagnostics: &Diagnostics) {
+    match list_layers::<Option<toml::Value>>(layers_dir) {
+        Ok(listing) => {
+            for (layer_name, error) in listing.unreadable_metadata {
+                diagnostics.warning(format!(
+                    "Could not read {}.toml: {}",
+                    layer_name.as_str(),
+                    error
+                ));
+            }
+
+            for name in listing.orphaned_metadata {
+                diagnostics.warning(format!("Found {}.toml without a layer directory", name));
+            }
+
+            for name in listing.orphaned_directories {
+                diagnostics.warning(format!(
+                    "Found layer directory {} without {}.toml",
+                    name, name
+                ));
+            }
+        }
+        Err(error) => diagnostics.warning(format!("Could not list layers: {}", error)),
+    }
+
+    if layer_tracker.cleanup_policy() == LayerCleanupPolicy::DeleteUntouched {
+        match delete_untouched_layers(layers_dir, layer_tracker) {
+            Ok(deleted) => {
+                for name in deleted {
+                    diagnostics.warning(format!("Deleted unused layer {}", name));
+                }
+            }
+            Err(error) => diagnostics.warning(format!("Could not delete unused layers: {}", error)),
+        }
+    }
+}
+
+// A format declared in the `sbom-formats` of buildpack.toml without any SBOM file in that format
+// usually means that a `write_*_sbom` call is missing.
+fn warn_about_unwritten_sbom_formats(
+    layers_dir: &Path,
+    sbom_formats: &[SbomFormat],
+    diagnostics: &Diagnostics,
+) {
+    if sbom_formats.is_empty() {
+        return;
+    }
+
+    let file_names: Vec<String> = match fs::read_dir(layers_dir) {
+        Ok(entries) => entries
+            .flatten()
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .collect(),
+        Err(error) => {
+            diagnostics.warning(format!("Could not check for written SBOM files: {}", error));
+            return;
+        }
+    };
+
+    for sbom_format in sbom_formats {
+        let suffix = format!(".sbom.{}", sbom_format.extension());
+
+        if !file_names
+            .iter()
+            .any(|file_name| file_name.ends_with(&suffix))
+        {
+            diagnostics.warning(format!(
+                "SBOM format `{}` is declared in buildpack.toml, but no SBOM was written in it",
+                sbom_format
+            ));
+        }
+    }
+}
+
+// Process-specific env of process types that are not declared in launch.toml is never applied,
+// which usually indicates a typo or a process that was removed.
+fn warn_about_unknown_process_env(
+    layers_dir: &Path,
+    launch_path: &Path,
+    diagnostics: &Diagnostics,
+) {
+    let launch = match read_toml_file_or(launch_path, Launch::new) {
+        Ok(launch) => launch,
+        Err(error) => {
+            diagnostics.warning(format!(
+                "Could not read launch.toml to check process-specific layer environment variables: {}",
+                error
+            ));
+            return;
+        }
+    };
+
+    let entries = match fs::read_dir(layers_dir) {
+        Ok(entries) => entries,
+        Err(error) => {
+            diagnostics.warning(format!(
+                "Could not read layers to check process-specific environment variables: {}",
+                error
+            ));
+            return;
+        }
+    };
+
+    for layer_path in entries.flatten().map(|entry| entry.path()) {
+        let layer_name = layer_path
+            .file_name()
+            .map(|file_name| file_name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        if !layer_path.is_dir() || is_work_path(&layer_name) {
+            continue;
+        }
+
+        match LayerEnv::read_from_layer_dir(&layer_path) {
+            Ok(layer_env) => {
+                for process_type in layer_env.unknown_process_types(&launch) {
+                    diagnostics.warning(format!(
+                        "Layer `{}` sets environment variables for process type `{}`, but launch.toml does not declare this process type",
+                        layer_name,
+                        process_type.as_str()
+                    ));
+                }
+            }
+            Err(error) => diagnostics.warning(format!(
+                "Could not read environment variables of layer `{}`: {}",
+                layer_name, error
+            )),
+        }
+    }
 }
 
 struct DetectArgs {
@@ -182,3 +443,190 @@ fn parse_build_args_or_exit() -> BuildArgs {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::bom::{Bom, Entry};
+    use crate::data::launch::{LaunchBuilder, ProcessBuilder, ProcessType};
+    use crate::data::layer_content_metadata::LayerContentMetadata;
+    use crate::data::layer_env::{ModificationBehavior, Scope};
+    use crate::generic::{GenericBuildContext, GenericMetadata, GenericPlatform};
+    use crate::layer_lifecycle::{execute_layer_lifecycle, LayerLifecycle};
+    use crate::layer_name;
+    use crate::process_type;
+    use crate::test_support::build_context;
+    use std::str::FromStr;
+
+    struct BomLayerLifecycle;
+
+    impl LayerLifecycle<GenericPlatform, GenericMetadata, GenericMetadata, (), String>
+        for BomLayerLifecycle
+    {
+        fn create(
+            &self,
+            _layer_path: &Path,
+            _build_context: &GenericBuildContext,
+        ) -> std::result::Result<LayerContentMetadata<GenericMetadata>, String> {
+            Ok(LayerContentMetadata::default().launch(true).build(true))
+        }
+
+        fn bom(
+            &self,
+            _layer_path: &Path,
+            _layer_content_metadata: &LayerContentMetadata<GenericMetadata>,
+            _build_context: &GenericBuildContext,
+        ) -> Bom {
+            vec![Entry::new("ruby")]
+        }
+    }
+
+    #[test]
+    fn merges_layer_bom_into_written_launch_and_build() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+
+        context
+            .write_launch(
+                LaunchBuilder::new()
+                    .process(ProcessBuilder::new(process_type!("web"), "puma"))
+                    .build()
+                    .unwrap(),
+            )
+            .unwrap();
+        context.write_build(Build::new()).unwrap();
+
+        execute_layer_lifecycle(&layer_name!("ruby"), BomLayerLifecycle, &context).unwrap();
+        write_contributed_bom::<String>(
+            &context.launch_path(),
+            &context.build_path(),
+            &context.bom,
+        )
+        .unwrap();
+
+        let launch: Launch = read_toml_file(context.launch_path()).unwrap();
+        assert_eq!(launch.processes.len(), 1);
+        assert_eq!(launch.bom, vec![Entry::new("ruby")]);
+
+        let build: Build = read_toml_file(context.build_path()).unwrap();
+        assert_eq!(build.bom, vec![Entry::new("ruby")]);
+    }
+
+    #[test]
+    fn writes_layer_bom_without_launch_and_build() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = build_context(&temp_dir);
+
+        execute_layer_lifecycle(&layer_name!("ruby"), BomLayerLifecycle, &context).unwrap();
+        write_contributed_bom::<String>(
+            &context.launch_path(),
+            &context.build_path(),
+            &context.bom,
+        )
+        .unwrap();
+
+        let launch: Launch = read_toml_file(context.launch_path()).unwrap();
+        assert!(launch.processes.is_empty());
+        assert_eq!(launch.bom, vec![Entry::new("ruby")]);
+
+        let build: Build = read_toml_file(context.build_path()).unwrap();
+        assert_eq!(build.bom, vec![Entry::new("ruby")]);
+    }
+
+    #[test]
+    fn warns_about_unwritten_sbom_formats() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        fs::write(temp_dir.path().join("ruby.sbom.cdx.json"), "{}").unwrap();
+
+        let diagnostics = Diagnostics::new();
+        warn_about_unwritten_sbom_formats(
+            temp_dir.path(),
+            &[SbomFormat::CycloneDxJson, SbomFormat::SpdxJson],
+            &diagnostics,
+        );
+
+        assert_eq!(diagnostics.warning_count(), 1);
+        assert!(diagnostics.diagnostics()[0]
+            .message
+            .contains("application/spdx+json"));
+    }
+
+    #[test]
+    fn warns_about_unreadable_launch_toml_and_layers_dir() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let launch_path = temp_dir.path().join("launch.toml");
+        fs::write(&launch_path, "processes = 42").unwrap();
+
+        let diagnostics = Diagnostics::new();
+        warn_about_unknown_process_env(temp_dir.path(), &launch_path, &diagnostics);
+        warn_about_unknown_process_env(
+            &temp_dir.path().join("missing"),
+            &temp_dir.path().join("missing.toml"),
+            &diagnostics,
+        );
+
+        assert_eq!(diagnostics.warning_count(), 2);
+        assert!(diagnostics.diagnostics()[0]
+            .message
+            .contains("Could not read launch.toml"));
+        assert!(diagnostics.diagnostics()[1]
+            .message
+            .contains("Could not read layers"));
+    }
+
+    #[test]
+    fn skips_work_directories_when_checking_process_env() {
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        LayerEnv::new()
+            .chainable_insert(
+                Scope::Process(ProcessType::from_str("web").unwrap()),
+                ModificationBehavior::Override,
+                "MALLOC_ARENA_MAX",
+                "2",
+            )
+            .write_to_layer_dir(temp_dir.path().join("ruby.libcnb-backup"))
+            .unwrap();
+
+        let diagnostics = Diagnostics::new();
+        warn_about_unknown_process_env(
+            temp_dir.path(),
+            &temp_dir.path().join("launch.toml"),
+            &diagnostics,
+        );
+
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn warns_about_env_for_unknown_process_types() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let launch_path = temp_dir.path().join("launch.toml");
+
+        let launch = LaunchBuilder::new()
+            .process(ProcessBuilder::new(process_type!("web"), "puma"))
+            .build()
+            .unwrap();
+        write_toml_file(&launch, &launch_path).unwrap();
+
+        for process_type in &["web", "wroker"] {
+            LayerEnv::new()
+                .chainable_insert(
+                    Scope::Process(ProcessType::from_str(process_type).unwrap()),
+                    ModificationBehavior::Override,
+                    "MALLOC_ARENA_MAX",
+                    "2",
+                )
+                .write_to_layer_dir(temp_dir.path().join("ruby"))
+                .unwrap();
+        }
+
+        let diagnostics = Diagnostics::new();
+        warn_about_unknown_process_env(temp_dir.path(), &launch_path, &diagnostics);
+
+        assert_eq!(diagnostics.warning_count(), 1);
+        assert!(diagnostics.diagnostics()[0]
+            .message
+            .contains("process type `wroker`"));
+    }
+}
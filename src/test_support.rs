@@ -0,0 +1,651 @@
+//! Helpers shared by unit tests across modules.
+
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use proptest::prelude::*;
+use semver::Version;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tempfile::TempDir;
+use toml::value::Table;
+
+use crate::build::BuildContext;
+use crate::data::bom::{self, BomCollector};
+use crate::data::build::{Build, Unmet};
+use crate::data::build_plan::{BuildPlan, Or, Provide, Require};
+use crate::data::buildpack::{
+    self, BuildpackApi, BuildpackId, BuildpackToml, Group, Order, Stack, StackId,
+};
+use crate::data::buildpack_plan::{self, BuildpackPlan};
+use crate::data::dependency::{DependenciesMetadata, Dependency};
+use crate::data::launch::{self, Label, Launch, ProcessType, Slice};
+use crate::data::layer::{self, LayerName};
+use crate::data::procfile::{Procfile, ProcfileEntry};
+use crate::data::sbom::{Component, Sbom, SbomFormat};
+use crate::data::store::Store;
+use crate::diagnostics::Diagnostics;
+use crate::generic::{GenericBuildContext, GenericPlatform};
+use crate::layer_lifecycle::LayerTracker;
+use crate::platform::Platform;
+
+/// Creates a [`GenericBuildContext`] with all directories located in `temp_dir`.
+pub(crate) fn build_context(temp_dir: &TempDir) -> GenericBuildContext {
+    let layers_dir = temp_dir.path().join("layers");
+    let platform_dir = temp_dir.path().join("platform");
+    fs::create_dir_all(&layers_dir).unwrap();
+    fs::create_dir_all(platform_dir.join("env")).unwrap();
+
+    BuildContext {
+        layers_dir,
+        app_dir: temp_dir.path().join("app"),
+        buildpack_dir: temp_dir.path().join("buildpack"),
+        stack_id: String::from("io.buildpacks.stacks.bionic"),
+        platform: GenericPlatform::from_path(&platform_dir).unwrap(),
+        buildpack_plan: BuildpackPlan { entries: vec![] },
+        buildpack_descriptor: toml::from_str(
+            r#"
+api = "0.4"
+
+[buildpack]
+id = "foo/bar"
+name = "Bar Buildpack"
+version = "0.0.1"
+
+[[stacks]]
+id = "io.buildpacks.stacks.bionic"
+"#,
+        )
+        .unwrap(),
+        diagnostics: Diagnostics::new(),
+        bom: BomCollector::new(),
+        layer_tracker: LayerTracker::new(),
+    }
+}
+
+/// Serializes `value` to TOML and deserializes it again.
+pub(crate) fn toml_round_trip<T: Serialize + DeserializeOwned>(value: &T) -> T {
+    toml::from_str(&toml::to_string(value).unwrap()).unwrap()
+}
+
+/// Asserts that `value` serializes to the same TOML document as the one in the file at `path`
+/// with the contents `expected`, ignoring formatting and key order.
+pub(crate) fn assert_serializes_to(value: &impl Serialize, path: &Path, expected: &str) {
+    assert_eq!(
+        toml::Value::try_from(value).unwrap(),
+        toml::from_str::<toml::Value>(expected).unwrap(),
+        "{}",
+        path.display()
+    );
+}
+
+// proptest strategies for values that are valid according to the spec.
+
+pub(crate) fn buildpack_id() -> impl Strategy<Value = BuildpackId> {
+    "[a-zA-Z0-9./-]{1,24}"
+        .prop_filter("reserved", |value| buildpack::is_valid_buildpack_id(value))
+        .prop_map(|value| BuildpackId::from_str(&value).unwrap())
+}
+
+pub(crate) fn stack_id() -> impl Strategy<Value = StackId> {
+    "[a-zA-Z0-9./-]{1,24}".prop_map(|value| StackId::from_str(&value).unwrap())
+}
+
+pub(crate) fn process_type() -> impl Strategy<Value = ProcessType> {
+    "[a-zA-Z0-9_-]{1,16}".prop_map(|value| ProcessType::from_str(&value).unwrap())
+}
+
+pub(crate) fn layer_name() -> impl Strategy<Value = LayerName> {
+    "[a-zA-Z0-9._-]{1,16}"
+        .prop_filter("reserved", |value| layer::is_valid_layer_name(value))
+        .prop_map(|value| LayerName::from_str(&value).unwrap())
+}
+
+pub(crate) fn version() -> impl Strategy<Value = Version> {
+    (0..100u64, 0..100u64, 0..100u64)
+        .prop_map(|(major, minor, patch)| Version::new(major, minor, patch))
+}
+
+/// Printable strings, including non-ASCII characters.
+pub(crate) fn text() -> impl Strategy<Value = String> {
+    "\\PC{0,16}"
+}
+
+/// Arbitrary TOML tables with nested tables. Keys start with `x-` so that they never collide
+/// with well-known keys of the surrounding data type.
+pub(crate) fn toml_table() -> impl Strategy<Value = Table> {
+    let leaf = prop_oneof![
+        text().prop_map(toml::Value::from),
+        any::<i64>().prop_map(toml::Value::from),
+        any::<bool>().prop_map(toml::Value::from),
+        prop::collection::vec(text(), 0..3).prop_map(toml::Value::from),
+    ];
+
+    let value = leaf.prop_recursive(2, 16, 4, |inner| {
+        prop::collection::btree_map("x-[a-z0-9_-]{1,8}", inner, 0..4)
+            .prop_map(|map| toml::Value::Table(map.into_iter().collect()))
+    });
+
+    prop::collection::btree_map("x-[a-z0-9_-]{1,8}", value, 0..4)
+        .prop_map(|map| map.into_iter().collect())
+}
+
+pub(crate) fn bom_entry() -> impl Strategy<Value = bom::Entry> {
+    (
+        text(),
+        prop::option::of(text()),
+        prop::collection::vec(text(), 0..3),
+        prop::option::of(text()),
+        prop::collection::vec(stack_id(), 0..3),
+        toml_table(),
+    )
+        .prop_map(|(name, version, licenses, purl, stacks, extra)| {
+            let mut entry = bom::Entry::new(name);
+            entry.metadata.version = version;
+            entry.metadata.licenses = licenses;
+            entry.metadata.purl = purl;
+            entry.metadata.stacks = stacks.iter().map(|id| String::from(id.as_str())).collect();
+            entry.metadata.extra = extra;
+            entry
+        })
+}
+
+pub(crate) fn process() -> impl Strategy<Value = launch::Process> {
+    (
+        process_type(),
+        text(),
+        prop::collection::vec(text(), 0..4),
+        any::<bool>(),
+        any::<bool>(),
+        prop::option::of("[a-z0-9/_-]{1,16}"),
+    )
+        .prop_map(
+            |(r#type, command, args, direct, default, working_directory)| launch::Process {
+                default,
+                working_directory: working_directory.map(Into::into),
+                ..launch::Process::with_type(r#type, command, args, direct)
+            },
+        )
+}
+
+pub(crate) fn launch() -> impl Strategy<Value = Launch> {
+    (
+        prop::collection::vec(bom_entry(), 0..3),
+        prop::collection::vec((text(), text()), 0..3),
+        prop::collection::vec(process(), 0..4),
+        prop::collection::vec(prop::collection::vec(text(), 0..3), 0..3),
+    )
+        .prop_map(|(bom, labels, processes, slices)| Launch {
+            bom,
+            labels: labels
+                .into_iter()
+                .map(|(key, value)| Label::new(key, value))
+                .collect(),
+            processes,
+            slices: slices.into_iter().map(Slice::new).collect(),
+        })
+}
+
+pub(crate) fn build() -> impl Strategy<Value = Build> {
+    (
+        prop::collection::vec(bom_entry(), 0..3),
+        prop::collection::vec(text(), 0..3),
+    )
+        .prop_map(|(bom, unmet)| Build {
+            bom,
+            unmet: unmet.into_iter().map(Unmet::new).collect(),
+        })
+}
+
+pub(crate) fn build_plan() -> impl Strategy<Value = BuildPlan> {
+    fn alternative() -> impl Strategy<Value = (Vec<Provide>, Vec<Require>)> {
+        let require =
+            (text(), toml_table()).prop_map(|(name, metadata)| Require { name, metadata });
+
+        (
+            prop::collection::vec(text().prop_map(Provide::new), 0..3),
+            prop::collection::vec(require, 0..3),
+        )
+    }
+
+    (alternative(), prop::collection::vec(alternative(), 0..3)).prop_map(
+        |((provides, requires), alternatives)| BuildPlan {
+            provides,
+            requires,
+            or: alternatives
+                .into_iter()
+                .map(|(provides, requires)| Or { provides, requires })
+                .collect(),
+        },
+    )
+}
+
+pub(crate) fn buildpack_plan() -> impl Strategy<Value = BuildpackPlan> {
+    prop::collection::vec((text(), toml_table()), 0..4).prop_map(|entries| BuildpackPlan {
+        entries: entries
+            .into_iter()
+            .map(|(name, metadata)| buildpack_plan::Entry { name, metadata })
+            .collect(),
+    })
+}
+
+pub(crate) fn buildpack_toml() -> impl Strategy<Value = BuildpackToml<toml::Value>> {
+    let buildpack = (
+        buildpack_id(),
+        text(),
+        version(),
+        prop::option::of(text()),
+        any::<bool>(),
+        prop::sample::subsequence(SbomFormat::ALL.to_vec(), 0..=3),
+    )
+        .prop_map(|(id, name, version, homepage, clear_env, sbom_formats)| {
+            buildpack::Buildpack {
+                id,
+                name,
+                version,
+                homepage,
+                clear_env,
+                sbom_formats,
+            }
+        });
+
+    let stack = (stack_id(), prop::collection::vec(text(), 0..3))
+        .prop_map(|(id, mixins)| Stack { id, mixins });
+
+    let group =
+        (buildpack_id(), version(), any::<bool>()).prop_map(|(id, version, optional)| Group {
+            id,
+            version,
+            optional,
+        });
+
+    let order = prop::collection::vec(group, 1..3).prop_map(|group| Order { group });
+
+    (
+        any::<(u32, u32)>(),
+        buildpack,
+        prop::collection::vec(stack, 0..3),
+        prop::collection::vec(order, 0..3),
+        toml_table(),
+    )
+        .prop_map(
+            |((major, minor), buildpack, stacks, order, metadata)| BuildpackToml {
+                api: BuildpackApi { major, minor },
+                buildpack,
+                stacks,
+                order,
+                metadata: toml::Value::Table(metadata),
+            },
+        )
+}
+
+pub(crate) fn dependencies_metadata() -> impl Strategy<Value = DependenciesMetadata> {
+    let dependency = (
+        "[a-z0-9-]{1,12}",
+        version(),
+        text(),
+        "[0-9a-f]{64}",
+        prop::collection::vec(stack_id(), 1..3),
+        prop::collection::vec(text(), 0..3),
+        prop::option::of((2000..2100u32, 1..=12u32, 1..=28u32)),
+    )
+        .prop_map(
+            |(id, version, uri, sha256, stacks, licenses, deprecation_date)| Dependency {
+                id,
+                version,
+                uri,
+                sha256,
+                stacks: stacks.iter().map(|id| String::from(id.as_str())).collect(),
+                licenses,
+                deprecation_date: deprecation_date.map(|(year, month, day)| {
+                    format!("{}-{:02}-{:02}", year, month, day).parse().unwrap()
+                }),
+            },
+        );
+
+    prop::collection::vec(dependency, 0..4)
+        .prop_map(|dependencies| DependenciesMetadata { dependencies })
+}
+
+pub(crate) fn store() -> impl Strategy<Value = Store> {
+    toml_table().prop_map(|metadata| Store { metadata })
+}
+
+/// SBOM components with non-empty optional values, since Syft writes missing values as empty
+/// strings.
+pub(crate) fn sbom_component() -> impl Strategy<Value = Component> {
+    let non_empty_text = || prop::option::of("\\PC{1,16}");
+
+    (
+        text(),
+        non_empty_text(),
+        prop::collection::vec("[A-Za-z0-9.+-]{1,12}", 0..3),
+        non_empty_text(),
+        prop::option::of("[0-9a-f]{64}"),
+        non_empty_text(),
+        non_empty_text(),
+    )
+        .prop_map(
+            |(name, version, licenses, uri, sha256, cpe, purl)| Component {
+                name,
+                version,
+                licenses,
+                uri,
+                sha256,
+                cpe,
+                purl,
+            },
+        )
+}
+
+/// Procfile contents with comments and blank lines between entries, and the [`Procfile`] they
+/// describe.
+pub(crate) fn procfile() -> impl Strategy<Value = (String, Procfile)> {
+    let line = prop_oneof![
+        "# [ -~]{0,16}".prop_map(Err),
+        Just(Err(String::new())),
+        (process_type(), "[!-~]([ -~]{0,14}[!-~])?").prop_map(Ok),
+    ];
+
+    prop::collection::vec(line, 0..8).prop_map(|lines| {
+        let mut contents = String::new();
+        let mut procfile = Procfile { entries: vec![] };
+
+        for (index, line) in lines.into_iter().enumerate() {
+            match line {
+                Ok((process_type, command))
+                    if !procfile
+                        .entries
+                        .iter()
+                        .any(|entry| entry.process_type == process_type) =>
+                {
+                    contents.push_str(&format!("{}: {}\n", process_type.as_str(), command));
+                    procfile.entries.push(ProcfileEntry {
+                        process_type,
+                        command,
+                        line: index + 1,
+                    });
+                }
+                Ok(_) => contents.push('\n'),
+                Err(comment) => contents.push_str(&format!("{}\n", comment)),
+            }
+        }
+
+        (contents, procfile)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buildpack_id;
+    use crate::data::layer_content_metadata::LayerContentMetadata;
+    use crate::process_type;
+
+    /// Buildpack API versions with golden files in `test-data/golden/<api>`. The files are
+    /// hand-written and use the file shapes each Buildpack API expects, i.e. `[types]` in
+    /// layer.toml from 0.6 and `default` processes from 0.6.
+    const GOLDEN_BUILDPACK_APIS: &[&str] = &["0.4", "0.5", "0.6", "0.7"];
+
+    /// Parses a golden file, asserts values of the parsed data and asserts that it serializes back
+    /// to the same document.
+    type GoldenFileCheck = fn(&BuildpackApi, &Path, &str);
+
+    /// Golden files and the check run against every version of them.
+    const GOLDEN_FILES: &[(&str, GoldenFileCheck)] = &[
+        ("buildpack.toml", check_buildpack_toml),
+        ("launch.toml", check_launch_toml),
+        ("layer.toml", check_layer_toml),
+        ("build.toml", check_build_toml),
+        ("build-plan.toml", check_build_plan_toml),
+        ("plan.toml", check_plan_toml),
+        ("store.toml", check_store_toml),
+    ];
+
+    const API_0_6: BuildpackApi = BuildpackApi { major: 0, minor: 6 };
+    const API_0_7: BuildpackApi = BuildpackApi { major: 0, minor: 7 };
+
+    #[test]
+    fn golden_files_round_trip() {
+        for api in GOLDEN_BUILDPACK_APIS {
+            for (file_name, check) in GOLDEN_FILES {
+                let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+                    .join("test-data/golden")
+                    .join(api)
+                    .join(file_name);
+                let raw = fs::read_to_string(&path)
+                    .unwrap_or_else(|error| panic!("{}: {}", path.display(), error));
+
+                check(&BuildpackApi::from_str(api).unwrap(), &path, &raw);
+            }
+        }
+    }
+
+    fn check_buildpack_toml(api: &BuildpackApi, path: &Path, raw: &str) {
+        let buildpack_toml: BuildpackToml<Table> =
+            toml::from_str(raw).unwrap_or_else(|error| panic!("{}: {}", path.display(), error));
+
+        assert_eq!(&buildpack_toml.api, api, "{}", path.display());
+        assert_eq!(
+            buildpack_toml.buildpack.id,
+            buildpack_id!("example/ruby"),
+            "{}",
+            path.display()
+        );
+        assert_eq!(
+            buildpack_toml.stacks[0].mixins,
+            vec!["build:git", "libpq-dev"],
+            "{}",
+            path.display()
+        );
+        assert_eq!(
+            buildpack_toml.buildpack.sbom_formats.is_empty(),
+            api < &API_0_7,
+            "{}",
+            path.display()
+        );
+        assert_eq!(
+            buildpack_toml.metadata["default-ruby"].as_str(),
+            Some("3.0.1"),
+            "{}",
+            path.display()
+        );
+        assert_serializes_to(&buildpack_toml, path, raw);
+    }
+
+    fn check_launch_toml(api: &BuildpackApi, path: &Path, raw: &str) {
+        let launch: Launch =
+            toml::from_str(raw).unwrap_or_else(|error| panic!("{}: {}", path.display(), error));
+
+        let processes: Vec<(&ProcessType, bool, bool)> = launch
+            .processes
+            .iter()
+            .map(|process| (&process.r#type, process.direct, process.default))
+            .collect();
+        assert_eq!(
+            processes,
+            vec![
+                (&process_type!("web"), true, api >= &API_0_6),
+                (&process_type!("worker"), false, false)
+            ],
+            "{}",
+            path.display()
+        );
+        assert_eq!(
+            launch.labels[0].key,
+            "com.example.ruby-version",
+            "{}",
+            path.display()
+        );
+        assert_eq!(
+            launch.slices[0].paths,
+            vec!["public/assets/**/*", "vendor/*"],
+            "{}",
+            path.display()
+        );
+        assert_eq!(
+            launch.bom[0].metadata.version.as_deref(),
+            Some("3.0.1"),
+            "{}",
+            path.display()
+        );
+        assert_serializes_to(&launch.for_api(api), path, raw);
+    }
+
+    fn check_layer_toml(api: &BuildpackApi, path: &Path, raw: &str) {
+        let layer: LayerContentMetadata<toml::Value> =
+            toml::from_str(raw).unwrap_or_else(|error| panic!("{}: {}", path.display(), error));
+
+        assert!(
+            layer.launch && !layer.build && layer.cache,
+            "{}",
+            path.display()
+        );
+        assert_eq!(
+            layer.metadata["version"].as_str(),
+            Some("3.0.1"),
+            "{}",
+            path.display()
+        );
+        assert_serializes_to(&layer.for_api(api), path, raw);
+    }
+
+    fn check_build_toml(_: &BuildpackApi, path: &Path, raw: &str) {
+        let build: Build =
+            toml::from_str(raw).unwrap_or_else(|error| panic!("{}: {}", path.display(), error));
+
+        assert_eq!(build.bom[0].name, "bundler", "{}", path.display());
+        assert_eq!(build.unmet, vec![Unmet::new("node")], "{}", path.display());
+        assert_serializes_to(&build, path, raw);
+    }
+
+    fn check_build_plan_toml(_: &BuildpackApi, path: &Path, raw: &str) {
+        let build_plan: BuildPlan =
+            toml::from_str(raw).unwrap_or_else(|error| panic!("{}: {}", path.display(), error));
+
+        assert_eq!(
+            build_plan.requires[0].metadata["version"].as_str(),
+            Some("3.0.1"),
+            "{}",
+            path.display()
+        );
+        let alternative: Vec<&str> = build_plan.or[0]
+            .provides
+            .iter()
+            .map(|provide| provide.name.as_str())
+            .collect();
+        assert_eq!(alternative, vec!["ruby", "bundler"], "{}", path.display());
+        assert_serializes_to(&build_plan, path, raw);
+    }
+
+    fn check_plan_toml(_: &BuildpackApi, path: &Path, raw: &str) {
+        let buildpack_plan: BuildpackPlan =
+            toml::from_str(raw).unwrap_or_else(|error| panic!("{}: {}", path.display(), error));
+
+        let names: Vec<&str> = buildpack_plan
+            .entries
+            .iter()
+            .map(|entry| entry.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["ruby", "bundler"], "{}", path.display());
+        assert_eq!(
+            buildpack_plan.entries[0].metadata["version"].as_str(),
+            Some("3.0.1"),
+            "{}",
+            path.display()
+        );
+        assert_serializes_to(&buildpack_plan, path, raw);
+    }
+
+    fn check_store_toml(_: &BuildpackApi, path: &Path, raw: &str) {
+        let store: Store =
+            toml::from_str(raw).unwrap_or_else(|error| panic!("{}: {}", path.display(), error));
+
+        assert_eq!(
+            store.metadata["gems"]["count"].as_integer(),
+            Some(42),
+            "{}",
+            path.display()
+        );
+        assert_serializes_to(&store, path, raw);
+    }
+
+    proptest! {
+        #[test]
+        fn bom_entry_round_trips(entry in bom_entry()) {
+            prop_assert_eq!(toml_round_trip(&entry), entry);
+        }
+
+        #[test]
+        fn build_round_trips(build in build()) {
+            prop_assert_eq!(toml_round_trip(&build), build);
+        }
+
+        #[test]
+        fn build_plan_round_trips(build_plan in build_plan()) {
+            prop_assert_eq!(toml_round_trip(&build_plan), build_plan);
+        }
+
+        #[test]
+        fn buildpack_plan_round_trips(buildpack_plan in buildpack_plan()) {
+            prop_assert_eq!(toml_round_trip(&buildpack_plan), buildpack_plan);
+        }
+
+        #[test]
+        fn buildpack_toml_round_trips(buildpack_toml in buildpack_toml()) {
+            prop_assert_eq!(toml_round_trip(&buildpack_toml), buildpack_toml);
+        }
+
+        #[test]
+        fn dependencies_metadata_round_trips(metadata in dependencies_metadata()) {
+            prop_assert_eq!(toml_round_trip(&metadata), metadata);
+        }
+
+        #[test]
+        fn launch_round_trips(launch in launch()) {
+            prop_assert_eq!(toml_round_trip(&launch), launch);
+        }
+
+        #[test]
+        fn store_round_trips(store in store()) {
+            prop_assert_eq!(toml_round_trip(&store), store);
+        }
+
+        #[test]
+        fn procfile_parses((contents, procfile) in procfile()) {
+            prop_assert_eq!(Procfile::from_str(&contents).unwrap(), procfile);
+        }
+
+        #[test]
+        fn sbom_round_trips(
+            components in prop::collection::vec(sbom_component(), 0..4),
+            format in prop::sample::select(SbomFormat::ALL.to_vec()),
+        ) {
+            let sbom = Sbom { components, ..Sbom::new() };
+            let read = Sbom::from_json(format, &sbom.to_json(format, "test").unwrap()).unwrap();
+
+            let expected: Vec<Component> = if format == SbomFormat::SyftJson {
+                sbom.components
+                    .into_iter()
+                    .map(|component| Component {
+                        uri: None,
+                        sha256: None,
+                        ..component
+                    })
+                    .collect()
+            } else {
+                sbom.components
+            };
+            prop_assert_eq!(read.components, expected);
+        }
+
+        #[test]
+        fn sbom_format_round_trips(format in prop::sample::select(SbomFormat::ALL.to_vec())) {
+            let serialized = serde_json::to_value(format).unwrap();
+
+            prop_assert_eq!(serialized.as_str(), Some(format.media_type()));
+            prop_assert_eq!(serde_json::from_value::<SbomFormat>(serialized).unwrap(), format);
+        }
+    }
+}